@@ -34,36 +34,22 @@ sudo raspi-config nonint do_i2c 0
 # Set download url based on architecture
 LATEST_RELEAST_URL="https://github.com/jackra1n/RustBerry-PoE-Monitor/releases/latest/download/rustberry-poe-monitor-$ARCH"
 
-# Download latest binary from github and place it in /usr/local/bin
+# Download latest binary from github
 echo "Downloading latest $ARCH binary from github"
-curl -sSL $LATEST_RELEAST_URL -o /usr/local/bin/rustberry-poe-monitor
-
-# Make binary executable
-chmod +x /usr/local/bin/rustberry-poe-monitor
-
-# Create systemd service
-echo "Creating systemd service to run as user '$ORIGINAL_USER' (this should be your user)"
-sudo cat <<EOF > /etc/systemd/system/rustberry-poe-monitor.service
-[Unit]
-Description=RustBerry PoE Monitor
-After=network.target
-
-[Service]
-# Run the service as the user who ran the install script
-User=$ORIGINAL_USER
-ExecStart=/usr/local/bin/rustberry-poe-monitor
-Restart=always
-RestartSec=30
-
-[Install]
-WantedBy=multi-user.target
-EOF
+DOWNLOAD=$(mktemp)
+trap 'rm -f "$DOWNLOAD"' EXIT
+if ! curl -fsSL "$LATEST_RELEAST_URL" -o "$DOWNLOAD"; then
+    echo "Download failed"
+    exit 1
+fi
+chmod +x "$DOWNLOAD"
 
-# Enable service
-echo "Enabling systemd service"
-sudo systemctl daemon-reload
-sudo systemctl enable rustberry-poe-monitor.service
-sudo systemctl start rustberry-poe-monitor.service
+# The binary installs itself, the systemd service and a default config
+echo "Installing the service to run as user '$ORIGINAL_USER' (this should be your user)"
+if ! "$DOWNLOAD" install --user "$ORIGINAL_USER"; then
+    echo "Installation failed"
+    exit 1
+fi
 
 echo "Installation complete"
-echo "Please reboot your Raspberry Pi to make sure all changes take effect"
\ No newline at end of file
+echo "Please reboot your Raspberry Pi to make sure all changes take effect"
@@ -0,0 +1,401 @@
+use crate::config::AlertsConfig;
+use crate::notifier::{self, Notification, NotificationState, Notifier};
+use anyhow::Result;
+use log::{debug, info};
+use std::collections::{HashMap, VecDeque};
+use std::mem::discriminant;
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum AlertKind {
+    HighTemperature,
+    DiskFull,
+    SensorFailure,
+    FanStuck,
+    UnderVoltage,
+    /// A watched systemd unit, by name.
+    UnitFailed(String),
+    /// Containers by name.
+    ContainerUnhealthy(String),
+    ContainerRestartLoop(String),
+    /// A reachability check by name.
+    CheckFailed(String),
+}
+
+impl AlertKind {
+    pub fn id(&self) -> String {
+        match self {
+            AlertKind::HighTemperature => "high_temperature".to_string(),
+            AlertKind::DiskFull => "disk_full".to_string(),
+            AlertKind::SensorFailure => "sensor_failure".to_string(),
+            AlertKind::FanStuck => "fan_stuck".to_string(),
+            AlertKind::UnderVoltage => "under_voltage".to_string(),
+            AlertKind::UnitFailed(unit) => format!("unit_failed:{}", unit),
+            AlertKind::ContainerUnhealthy(name) => format!("container_unhealthy:{}", name),
+            AlertKind::ContainerRestartLoop(name) => format!("container_restart_loop:{}", name),
+            AlertKind::CheckFailed(name) => format!("check_failed:{}", name),
+        }
+    }
+
+    pub fn title(&self) -> String {
+        match self {
+            AlertKind::HighTemperature => "High CPU temperature".to_string(),
+            AlertKind::DiskFull => "Disk almost full".to_string(),
+            AlertKind::SensorFailure => "Temperature sensor failure".to_string(),
+            AlertKind::FanStuck => "Fan running continuously".to_string(),
+            AlertKind::UnderVoltage => "Under-voltage detected".to_string(),
+            AlertKind::UnitFailed(unit) => format!("{} failed", unit),
+            AlertKind::ContainerUnhealthy(name) => format!("Container {} unhealthy", name),
+            AlertKind::ContainerRestartLoop(name) => format!("Container {} restarting", name),
+            AlertKind::CheckFailed(name) => format!("{} unreachable", name),
+        }
+    }
+
+    /// The unit, container or check the alert is about.
+    pub fn subject(&self) -> Option<&str> {
+        match self {
+            AlertKind::UnitFailed(name)
+            | AlertKind::ContainerUnhealthy(name)
+            | AlertKind::ContainerRestartLoop(name)
+            | AlertKind::CheckFailed(name) => Some(name),
+            _ => None,
+        }
+    }
+}
+
+/// Builds the notification text for an alert about the named subject.
+type SubjectMessage = fn(&str) -> String;
+
+/// One alert rule that is raised per unit, container or check.
+struct SubjectAlerts<'a> {
+    enabled: bool,
+    /// The subjects in a bad state, `None` if they couldn't be read.
+    subjects: Option<&'a [String]>,
+    kind: fn(String) -> AlertKind,
+    firing: SubjectMessage,
+    resolved: SubjectMessage,
+}
+
+/// Values the alert rules are evaluated against on every tick.
+pub struct AlertInputs<'a> {
+    pub cpu_temp: Option<f32>,
+    pub disk_usage: f64,
+    pub fan_running: bool,
+    /// Reported by the Pi firmware, false on other boards.
+    pub under_voltage: bool,
+    /// Watched systemd units in the failed state.
+    pub failed_units: &'a [String],
+    /// `None` while the container API can't be reached, which keeps the
+    /// container alerts as they are.
+    pub unhealthy_containers: Option<&'a [String]>,
+    pub restart_loop_containers: Option<&'a [String]>,
+    /// Names of the reachability checks that failed their last run.
+    pub failed_checks: &'a [String],
+}
+
+struct ActiveAlert {
+    notified: bool,
+}
+
+pub struct AlertManager {
+    config: AlertsConfig,
+    notifier: Option<Notifier>,
+    active: HashMap<AlertKind, ActiveAlert>,
+    last_notified: HashMap<AlertKind, Instant>,
+    sent_times: VecDeque<Instant>,
+    fan_running_since: Option<Instant>,
+}
+
+impl AlertManager {
+    pub fn new(config: AlertsConfig) -> Result<Self> {
+        let notifier = if config.enabled {
+            let channels = notifier::build_channels(&config);
+            info!(
+                "Alerts enabled with {} notification channel(s)",
+                channels.len()
+            );
+            Some(Notifier::spawn(channels)?)
+        } else {
+            debug!("Alert notifications disabled");
+            None
+        };
+
+        Ok(AlertManager {
+            config,
+            notifier,
+            active: HashMap::new(),
+            last_notified: HashMap::new(),
+            sent_times: VecDeque::new(),
+            fan_running_since: None,
+        })
+    }
+
+    /// Evaluates the threshold rules and sends notifications for alerts
+    /// that started or cleared. Returns the alerts that started this tick.
+    pub fn evaluate(&mut self, now: Instant, inputs: &AlertInputs) -> Vec<AlertKind> {
+        let mut fired = Vec::new();
+
+        match inputs.cpu_temp {
+            Some(temp) => {
+                self.resolve(
+                    now,
+                    AlertKind::SensorFailure,
+                    "Temperature sensor readable again",
+                );
+                if temp >= self.config.temp_threshold {
+                    let message = format!(
+                        "CPU temperature is {:.1}°C (threshold {:.1}°C)",
+                        temp, self.config.temp_threshold
+                    );
+                    if self.fire(now, AlertKind::HighTemperature, message) {
+                        fired.push(AlertKind::HighTemperature);
+                    }
+                } else if temp <= self.config.temp_clear {
+                    let message = format!("CPU temperature back to {:.1}°C", temp);
+                    self.resolve(now, AlertKind::HighTemperature, &message);
+                }
+            }
+            None => {
+                let message = "Failed to read the CPU temperature sensor".to_string();
+                if self.fire(now, AlertKind::SensorFailure, message) {
+                    fired.push(AlertKind::SensorFailure);
+                }
+            }
+        }
+
+        if inputs.disk_usage >= self.config.disk_threshold {
+            let message = format!(
+                "Disk usage is {:.1}% (threshold {:.1}%)",
+                inputs.disk_usage, self.config.disk_threshold
+            );
+            if self.fire(now, AlertKind::DiskFull, message) {
+                fired.push(AlertKind::DiskFull);
+            }
+        } else if inputs.disk_usage <= self.config.disk_clear {
+            let message = format!("Disk usage back to {:.1}%", inputs.disk_usage);
+            self.resolve(now, AlertKind::DiskFull, &message);
+        }
+
+        if inputs.fan_running {
+            let since = *self.fan_running_since.get_or_insert(now);
+            let running_for = now.duration_since(since);
+            if self.config.fan_stuck_after > 0 && running_for >= self.config.fan_stuck_after() {
+                let message = format!(
+                    "Fan has been running for {} minutes without cooling below the off threshold",
+                    running_for.as_secs() / 60
+                );
+                if self.fire(now, AlertKind::FanStuck, message) {
+                    fired.push(AlertKind::FanStuck);
+                }
+            }
+        } else {
+            self.fan_running_since = None;
+            self.resolve(now, AlertKind::FanStuck, "Fan turned off");
+        }
+
+        if self.config.under_voltage && inputs.under_voltage {
+            let message =
+                "Supply voltage is too low, check the PoE switch, cable and HAT".to_string();
+            if self.fire(now, AlertKind::UnderVoltage, message) {
+                fired.push(AlertKind::UnderVoltage);
+            }
+        } else {
+            self.resolve(
+                now,
+                AlertKind::UnderVoltage,
+                "Supply voltage back to normal",
+            );
+        }
+
+        let rules = [
+            SubjectAlerts {
+                enabled: self.config.failed_units,
+                subjects: Some(inputs.failed_units),
+                kind: AlertKind::UnitFailed,
+                firing: |unit| format!("systemd unit {} entered the failed state", unit),
+                resolved: |unit| format!("systemd unit {} is no longer failed", unit),
+            },
+            SubjectAlerts {
+                enabled: self.config.containers,
+                subjects: inputs.unhealthy_containers,
+                kind: AlertKind::ContainerUnhealthy,
+                firing: |name| format!("Container {} failed its health check", name),
+                resolved: |name| format!("Container {} is healthy again", name),
+            },
+            SubjectAlerts {
+                enabled: self.config.containers,
+                subjects: inputs.restart_loop_containers,
+                kind: AlertKind::ContainerRestartLoop,
+                firing: |name| format!("Container {} keeps restarting", name),
+                resolved: |name| format!("Container {} stopped restarting", name),
+            },
+            SubjectAlerts {
+                enabled: self.config.checks,
+                subjects: Some(inputs.failed_checks),
+                kind: AlertKind::CheckFailed,
+                firing: |name| format!("{} can't be reached", name),
+                resolved: |name| format!("{} is reachable again", name),
+            },
+        ];
+        for rule in rules {
+            self.evaluate_subjects(now, rule, &mut fired);
+        }
+
+        fired
+    }
+
+    /// Raises an alert for each listed subject and clears those whose
+    /// subject is no longer listed, or all of them if the rule is disabled.
+    /// Unknown subjects leave the alerts as they are.
+    fn evaluate_subjects(&mut self, now: Instant, rule: SubjectAlerts, fired: &mut Vec<AlertKind>) {
+        let subjects = match (rule.enabled, rule.subjects) {
+            (true, Some(subjects)) => subjects,
+            (true, None) => return,
+            (false, _) => &[],
+        };
+        for subject in subjects {
+            let alert = (rule.kind)(subject.clone());
+            if self.fire(now, alert.clone(), (rule.firing)(subject)) {
+                fired.push(alert);
+            }
+        }
+        let same_kind = discriminant(&(rule.kind)(String::new()));
+        let recovered: Vec<AlertKind> = self
+            .active
+            .keys()
+            .filter(|active| discriminant(*active) == same_kind)
+            .filter(|active| {
+                !subjects
+                    .iter()
+                    .any(|subject| active.subject() == Some(subject))
+            })
+            .cloned()
+            .collect();
+        for alert in recovered {
+            let message = (rule.resolved)(alert.subject().unwrap_or_default());
+            self.resolve(now, alert, &message);
+        }
+    }
+
+    /// Whether `kind` is currently raised.
+    pub fn is_active(&self, kind: &AlertKind) -> bool {
+        self.active.contains_key(kind)
+    }
+
+    /// Whether any alert is currently raised.
+    pub fn has_active(&self) -> bool {
+        !self.active.is_empty()
+    }
+
+    /// Marks `kind` as active. Returns true if it was not active before.
+    fn fire(&mut self, now: Instant, kind: AlertKind, message: String) -> bool {
+        if self.active.contains_key(&kind) {
+            return false;
+        }
+        info!("Alert raised: {}", message);
+        let notified = self.dispatch(now, &kind, NotificationState::Firing, message);
+        self.active.insert(kind, ActiveAlert { notified });
+        true
+    }
+
+    fn resolve(&mut self, now: Instant, kind: AlertKind, message: &str) {
+        if let Some(alert) = self.active.remove(&kind) {
+            info!("Alert cleared: {}", message);
+            // A recovery is only interesting if the matching alert went out.
+            if alert.notified && self.config.send_recovery {
+                self.dispatch(now, &kind, NotificationState::Resolved, message.to_string());
+            }
+        }
+    }
+
+    fn dispatch(
+        &mut self,
+        now: Instant,
+        kind: &AlertKind,
+        state: NotificationState,
+        message: String,
+    ) -> bool {
+        let Some(notifier) = &self.notifier else {
+            return false;
+        };
+
+        if state == NotificationState::Firing {
+            if let Some(last) = self.last_notified.get(kind) {
+                if now.duration_since(*last) < self.config.cooldown() {
+                    debug!("Alert '{}' is in cooldown, not notifying", kind.id());
+                    return false;
+                }
+            }
+        }
+
+        while let Some(sent) = self.sent_times.front() {
+            if now.duration_since(*sent) >= Duration::from_secs(3600) {
+                self.sent_times.pop_front();
+            } else {
+                break;
+            }
+        }
+        if self.sent_times.len() >= self.config.max_per_hour as usize {
+            debug!(
+                "Hourly notification limit reached, dropping '{}'",
+                kind.id()
+            );
+            return false;
+        }
+
+        self.sent_times.push_back(now);
+        if state == NotificationState::Firing {
+            self.last_notified.insert(kind.clone(), now);
+        }
+        notifier.send(Notification::new(&kind.id(), state, kind.title(), message));
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn inputs<'a>(unhealthy_containers: Option<&'a [String]>) -> AlertInputs<'a> {
+        AlertInputs {
+            cpu_temp: Some(45.0),
+            disk_usage: 20.0,
+            fan_running: false,
+            under_voltage: false,
+            failed_units: &[],
+            unhealthy_containers,
+            restart_loop_containers: None,
+            failed_checks: &[],
+        }
+    }
+
+    #[test]
+    fn unknown_container_states_keep_the_alerts() {
+        let mut alerts = AlertManager::new(AlertsConfig::default()).unwrap();
+        let web = AlertKind::ContainerUnhealthy("web".to_string());
+        let now = Instant::now();
+
+        let fired = alerts.evaluate(now, &inputs(Some(&["web".to_string()])));
+        assert_eq!(fired, std::slice::from_ref(&web));
+
+        // The API went away: neither cleared nor raised again.
+        assert!(alerts.evaluate(now, &inputs(None)).is_empty());
+        assert!(alerts.is_active(&web));
+
+        alerts.evaluate(now, &inputs(Some(&[])));
+        assert!(!alerts.is_active(&web));
+    }
+
+    #[test]
+    fn disabled_subject_alerts_clear() {
+        let mut alerts = AlertManager::new(AlertsConfig::default()).unwrap();
+        let web = AlertKind::ContainerUnhealthy("web".to_string());
+        let now = Instant::now();
+        alerts.evaluate(now, &inputs(Some(&["web".to_string()])));
+
+        alerts.config.containers = false;
+        alerts.evaluate(now, &inputs(None));
+        assert!(!alerts.is_active(&web));
+        assert!(!alerts.has_active());
+    }
+}
@@ -0,0 +1,476 @@
+use crate::config::{BurnInConfig, ShiftPattern};
+use crate::framebuffer::Framebuffer;
+use anyhow::{anyhow, Context, Result};
+use embedded_graphics::prelude::Point;
+use log::{debug, info, warn};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// How often the accumulated pixel on-time is written to disk.
+const USAGE_SAVE_INTERVAL: Duration = Duration::from_secs(600);
+
+const USAGE_FILE_MAGIC: &[u8; 4] = b"RBPX";
+const USAGE_FILE_VERSION: u8 = 1;
+
+/// Moves the whole layout around inside a small bounding box so that the
+/// static labels don't always light the same pixels.
+pub struct PixelShifter {
+    pattern: ShiftPattern,
+    interval: Duration,
+    max_x: i32,
+    max_y: i32,
+    orbit: Vec<Point>,
+    step: usize,
+    offset: Point,
+    last_shift: Instant,
+    rng: u64,
+}
+
+impl PixelShifter {
+    pub fn new(config: &BurnInConfig, now: Instant) -> Self {
+        let max_x = config.max_shift_x as i32;
+        let max_y = config.max_shift_y as i32;
+        let seed = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_nanos() as u64)
+            .unwrap_or(0);
+        PixelShifter {
+            pattern: config.shift_pattern,
+            interval: config.shift_interval(),
+            max_x,
+            max_y,
+            orbit: orbit_path(max_x, max_y),
+            step: 0,
+            offset: Point::zero(),
+            last_shift: now,
+            rng: seed | 1,
+        }
+    }
+
+    pub fn update(&mut self, now: Instant) -> Point {
+        if self.interval.is_zero() || now.duration_since(self.last_shift) < self.interval {
+            return self.offset;
+        }
+        self.offset = match self.pattern {
+            ShiftPattern::Alternate => {
+                if self.offset == Point::zero() {
+                    Point::new(self.max_x, self.max_y)
+                } else {
+                    Point::zero()
+                }
+            }
+            ShiftPattern::Orbit => {
+                self.step = (self.step + 1) % self.orbit.len();
+                self.orbit[self.step]
+            }
+            ShiftPattern::RandomWalk => self.random_step(),
+        };
+        self.last_shift = now;
+        debug!("Shifting display pixels to offset: {:?}", self.offset);
+        self.offset
+    }
+
+    fn random_step(&mut self) -> Point {
+        if self.max_x == 0 && self.max_y == 0 {
+            return Point::zero();
+        }
+        loop {
+            // xorshift64, plenty for picking a direction
+            self.rng ^= self.rng << 13;
+            self.rng ^= self.rng >> 7;
+            self.rng ^= self.rng << 17;
+            let delta = match self.rng % 4 {
+                0 => Point::new(1, 0),
+                1 => Point::new(-1, 0),
+                2 => Point::new(0, 1),
+                _ => Point::new(0, -1),
+            };
+            let next = self.offset + delta;
+            if (0..=self.max_x).contains(&next.x) && (0..=self.max_y).contains(&next.y) {
+                return next;
+            }
+        }
+    }
+}
+
+/// Offsets visited by the orbit pattern: the outline of the bounding box,
+/// or a back-and-forth line if the box is only one pixel high or wide.
+fn orbit_path(max_x: i32, max_y: i32) -> Vec<Point> {
+    if max_x == 0 || max_y == 0 {
+        let line: Vec<Point> = (0..=max_x)
+            .flat_map(|x| (0..=max_y).map(move |y| Point::new(x, y)))
+            .collect();
+        let back = line.iter().rev().skip(1).take(line.len().saturating_sub(2));
+        return line.iter().chain(back).copied().collect();
+    }
+    let mut path = Vec::new();
+    path.extend((0..max_x).map(|x| Point::new(x, 0)));
+    path.extend((0..max_y).map(|y| Point::new(max_x, y)));
+    path.extend((1..=max_x).rev().map(|x| Point::new(x, max_y)));
+    path.extend((1..=max_y).rev().map(|y| Point::new(0, y)));
+    path
+}
+
+/// Periodically shows the screen in inverse video for a few seconds, which
+/// evens out the wear of pixels that are normally always off.
+pub struct InverseRefresh {
+    interval: Duration,
+    duration: Duration,
+    started: Instant,
+}
+
+impl InverseRefresh {
+    pub fn new(config: &BurnInConfig, now: Instant) -> Self {
+        InverseRefresh {
+            interval: config.invert_interval(),
+            duration: config.invert_duration(),
+            started: now,
+        }
+    }
+
+    pub fn is_inverted(&self, now: Instant) -> bool {
+        if self.interval.is_zero() {
+            return false;
+        }
+        let elapsed = now.duration_since(self.started).as_secs();
+        elapsed >= self.interval.as_secs()
+            && elapsed % self.interval.as_secs() < self.duration.as_secs()
+    }
+}
+
+/// Accumulated time every pixel has been lit, persisted across restarts.
+pub struct PixelUsage {
+    width: u32,
+    height: u32,
+    on_time_ms: Vec<u64>,
+    path: PathBuf,
+    last_record: Option<Instant>,
+    last_save: Instant,
+}
+
+impl PixelUsage {
+    pub fn load_or_new(path: &Path, width: u32, height: u32, now: Instant) -> Self {
+        let on_time_ms = match read_usage_file(path) {
+            Ok((w, h, data)) if w == width && h == height => {
+                info!("Loaded pixel usage data from {}", path.display());
+                data
+            }
+            Ok((w, h, _)) => {
+                warn!(
+                    "Pixel usage data in {} is for a {}x{} panel, starting over",
+                    path.display(),
+                    w,
+                    h
+                );
+                vec![0; (width * height) as usize]
+            }
+            Err(e) => {
+                debug!("No pixel usage data loaded: {:#}", e);
+                vec![0; (width * height) as usize]
+            }
+        };
+        PixelUsage {
+            width,
+            height,
+            on_time_ms,
+            path: path.to_path_buf(),
+            last_record: None,
+            last_save: now,
+        }
+    }
+
+    /// Credits the time since the previous frame to every pixel lit in
+    /// `frame`.
+    pub fn record(&mut self, frame: &Framebuffer, now: Instant) {
+        if let Some(last) = self.last_record {
+            let elapsed = now.duration_since(last).as_millis() as u64;
+            for (x, y) in frame.lit_pixels() {
+                if x < self.width && y < self.height {
+                    let on_time = &mut self.on_time_ms[(y * self.width + x) as usize];
+                    *on_time = on_time.saturating_add(elapsed);
+                }
+            }
+        }
+        self.last_record = Some(now);
+
+        if now.duration_since(self.last_save) >= USAGE_SAVE_INTERVAL {
+            self.last_save = now;
+            if let Err(e) = self.save() {
+                warn!("Failed to save pixel usage data: {:#}", e);
+            }
+        }
+    }
+
+    /// Stops crediting time while the panel is off.
+    pub fn pause(&mut self) {
+        self.last_record = None;
+    }
+
+    fn save(&self) -> Result<()> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut data = Vec::with_capacity(9 + self.on_time_ms.len() * 8);
+        data.extend_from_slice(USAGE_FILE_MAGIC);
+        data.push(USAGE_FILE_VERSION);
+        data.extend_from_slice(&(self.width as u16).to_le_bytes());
+        data.extend_from_slice(&(self.height as u16).to_le_bytes());
+        for value in &self.on_time_ms {
+            data.extend_from_slice(&value.to_le_bytes());
+        }
+
+        let tmp_path = self.path.with_extension("tmp");
+        fs::write(&tmp_path, data)?;
+        fs::rename(&tmp_path, &self.path)?;
+        debug!("Saved pixel usage data to {}", self.path.display());
+        Ok(())
+    }
+}
+
+impl Drop for PixelUsage {
+    fn drop(&mut self) {
+        if let Err(e) = self.save() {
+            warn!("Failed to save pixel usage data: {:#}", e);
+        }
+    }
+}
+
+fn read_usage_file(path: &Path) -> Result<(u32, u32, Vec<u64>)> {
+    let data = fs::read(path).with_context(|| format!("failed to read {}", path.display()))?;
+    if data.len() < 9 || &data[0..4] != USAGE_FILE_MAGIC || data[4] != USAGE_FILE_VERSION {
+        return Err(anyhow!("{} is not a pixel usage file", path.display()));
+    }
+    let width = u16::from_le_bytes([data[5], data[6]]) as u32;
+    let height = u16::from_le_bytes([data[7], data[8]]) as u32;
+    if width == 0 || height == 0 {
+        return Err(anyhow!("{} is for an empty panel", path.display()));
+    }
+    let values = &data[9..];
+    if values.len() != (width * height) as usize * 8 {
+        return Err(anyhow!("{} is truncated", path.display()));
+    }
+    let on_time_ms = values
+        .chunks_exact(8)
+        .map(|chunk| u64::from_le_bytes(chunk.try_into().unwrap()))
+        .collect();
+    Ok((width, height, on_time_ms))
+}
+
+/// Prints a summary and a character heatmap of the recorded pixel usage.
+pub fn print_report(path: &Path) -> Result<()> {
+    let (width, height, on_time_ms) = read_usage_file(path)
+        .context("no pixel usage recorded yet, enable track_pixels in [display.burn_in]")?;
+
+    let hours = |ms: u64| ms as f64 / 3_600_000.0;
+    let max = on_time_ms.iter().copied().max().unwrap_or(0);
+    let lit: Vec<u64> = on_time_ms.iter().copied().filter(|v| *v > 0).collect();
+    let average = if lit.is_empty() {
+        0
+    } else {
+        let total: u128 = lit.iter().map(|v| *v as u128).sum();
+        (total / lit.len() as u128) as u64
+    };
+
+    println!(
+        "Pixel usage for {}x{} panel ({})",
+        width,
+        height,
+        path.display()
+    );
+    println!("Most used pixel:      {:.1} h", hours(max));
+    println!("Average lit pixel:    {:.1} h", hours(average));
+    println!(
+        "Never lit:            {} of {} pixels",
+        on_time_ms.len() - lit.len(),
+        on_time_ms.len()
+    );
+    println!();
+
+    const SHADES: &[u8] = b" .:-=+*#%@";
+    let border = "-".repeat(width as usize);
+    println!("+{}+", border);
+    for row in on_time_ms.chunks(width as usize) {
+        let line: String = row
+            .iter()
+            .map(|value| {
+                if *value == 0 || max == 0 {
+                    ' '
+                } else {
+                    // Saturates only for values close to `max`, which get
+                    // the darkest shade anyway.
+                    let scaled = value.saturating_mul(SHADES.len() as u64 - 2) / max;
+                    let shade = 1 + (scaled as usize).min(SHADES.len() - 2);
+                    SHADES[shade] as char
+                }
+            })
+            .collect();
+        println!("|{}|", line);
+    }
+    println!("+{}+", border);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    fn config(pattern: ShiftPattern, max_x: u8, max_y: u8) -> BurnInConfig {
+        BurnInConfig {
+            shift_pattern: pattern,
+            shift_interval: 60,
+            max_shift_x: max_x,
+            max_shift_y: max_y,
+            invert_interval: 3600,
+            invert_duration: 5,
+            ..BurnInConfig::default()
+        }
+    }
+
+    /// The offsets of `count` shifts, one interval apart.
+    fn shifts(config: &BurnInConfig, count: u64) -> Vec<Point> {
+        let start = Instant::now();
+        let mut shifter = PixelShifter::new(config, start);
+        (1..=count)
+            .map(|n| shifter.update(start + config.shift_interval() * n as u32))
+            .collect()
+    }
+
+    fn points(coordinates: &[(i32, i32)]) -> Vec<Point> {
+        coordinates.iter().map(|&(x, y)| Point::new(x, y)).collect()
+    }
+
+    #[test]
+    fn orbit_path_follows_the_outline() {
+        assert_eq!(
+            orbit_path(2, 1),
+            points(&[(0, 0), (1, 0), (2, 0), (2, 1), (1, 1), (0, 1)])
+        );
+        // One pixel high or wide: back and forth without repeating the ends.
+        assert_eq!(
+            orbit_path(3, 0),
+            points(&[(0, 0), (1, 0), (2, 0), (3, 0), (2, 0), (1, 0)])
+        );
+        assert_eq!(orbit_path(0, 1), points(&[(0, 0), (0, 1)]));
+        assert_eq!(orbit_path(0, 0), points(&[(0, 0)]));
+    }
+
+    #[test]
+    fn orbit_wraps_around() {
+        assert_eq!(
+            shifts(&config(ShiftPattern::Orbit, 1, 1), 5),
+            points(&[(1, 0), (1, 1), (0, 1), (0, 0), (1, 0)])
+        );
+        assert_eq!(
+            shifts(&config(ShiftPattern::Orbit, 0, 0), 2),
+            points(&[(0, 0), (0, 0)])
+        );
+    }
+
+    #[test]
+    fn alternate_jumps_between_the_corners() {
+        assert_eq!(
+            shifts(&config(ShiftPattern::Alternate, 2, 1), 3),
+            points(&[(2, 1), (0, 0), (2, 1)])
+        );
+    }
+
+    #[test]
+    fn random_walk_stays_in_the_box() {
+        let walk = shifts(&config(ShiftPattern::RandomWalk, 2, 1), 200);
+        let mut previous = Point::zero();
+        for offset in walk {
+            assert!((0..=2).contains(&offset.x) && (0..=1).contains(&offset.y));
+            let step = offset - previous;
+            assert_eq!(step.x.abs() + step.y.abs(), 1, "{:?}", step);
+            previous = offset;
+        }
+        assert_eq!(
+            shifts(&config(ShiftPattern::RandomWalk, 0, 0), 3),
+            points(&[(0, 0), (0, 0), (0, 0)])
+        );
+    }
+
+    #[test]
+    fn shifts_wait_for_the_interval() {
+        let config = config(ShiftPattern::Alternate, 1, 1);
+        let start = Instant::now();
+        let mut shifter = PixelShifter::new(&config, start);
+        assert_eq!(
+            shifter.update(start + Duration::from_secs(59)),
+            Point::zero()
+        );
+        assert_eq!(
+            shifter.update(start + Duration::from_secs(60)),
+            Point::new(1, 1)
+        );
+
+        let disabled = BurnInConfig {
+            shift_interval: 0,
+            ..config
+        };
+        let mut shifter = PixelShifter::new(&disabled, start);
+        assert_eq!(
+            shifter.update(start + Duration::from_secs(600)),
+            Point::zero()
+        );
+    }
+
+    #[test]
+    fn inverse_refresh_runs_for_its_duration_every_interval() {
+        let start = Instant::now();
+        let refresh = InverseRefresh::new(&config(ShiftPattern::Orbit, 1, 1), start);
+        let inverted = |secs| refresh.is_inverted(start + Duration::from_secs(secs));
+        assert!(!inverted(0));
+        assert!(!inverted(3599));
+        assert!(inverted(3600));
+        assert!(inverted(3604));
+        assert!(!inverted(3605));
+        assert!(inverted(7200));
+
+        let disabled = BurnInConfig {
+            invert_interval: 0,
+            ..config(ShiftPattern::Orbit, 1, 1)
+        };
+        let refresh = InverseRefresh::new(&disabled, start);
+        assert!(!refresh.is_inverted(start + Duration::from_secs(3600)));
+    }
+
+    fn usage_file(name: &str, width: u16, height: u16, values: &[u64]) -> PathBuf {
+        let path = env::temp_dir().join(format!(
+            "rustberry-usage-{}-{}.bin",
+            std::process::id(),
+            name
+        ));
+        let mut data = USAGE_FILE_MAGIC.to_vec();
+        data.push(USAGE_FILE_VERSION);
+        data.extend_from_slice(&width.to_le_bytes());
+        data.extend_from_slice(&height.to_le_bytes());
+        for value in values {
+            data.extend_from_slice(&value.to_le_bytes());
+        }
+        fs::write(&path, data).unwrap();
+        path
+    }
+
+    #[test]
+    fn usage_files_are_checked() {
+        let path = usage_file("valid", 2, 1, &[5, u64::MAX]);
+        assert_eq!(read_usage_file(&path).unwrap(), (2, 1, vec![5, u64::MAX]));
+        // Huge on-times from a corrupt file still make a report.
+        print_report(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        for (name, width, height, values) in [
+            ("empty", 0, 0, &[][..]),
+            ("no-rows", 4, 0, &[][..]),
+            ("truncated", 2, 2, &[1, 2, 3][..]),
+        ] {
+            let path = usage_file(name, width, height, values);
+            assert!(read_usage_file(&path).is_err(), "{}", name);
+            assert!(print_report(&path).is_err(), "{}", name);
+            fs::remove_file(&path).unwrap();
+        }
+    }
+}
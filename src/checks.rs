@@ -0,0 +1,130 @@
+use crate::config::{CheckConfig, CheckTarget};
+use anyhow::{anyhow, Context, Result};
+use log::{debug, info, warn};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::process::Command;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Latest outcome of one reachability check.
+#[derive(Debug, Clone)]
+pub struct CheckResult {
+    pub name: String,
+    /// Latency, or why the target couldn't be reached. `None` until the
+    /// check ran once.
+    pub outcome: Option<Result<Duration, String>>,
+}
+
+impl CheckResult {
+    pub fn is_failed(&self) -> bool {
+        matches!(self.outcome, Some(Err(_)))
+    }
+}
+
+/// Runs every check on its own thread, so a slow target neither delays the
+/// display nor the other checks.
+pub struct Checks {
+    results: Arc<Mutex<Vec<CheckResult>>>,
+}
+
+impl Checks {
+    pub fn start(configs: &[CheckConfig]) -> Result<Self> {
+        let results: Vec<CheckResult> = configs
+            .iter()
+            .map(|config| CheckResult {
+                name: config.display_name(),
+                outcome: None,
+            })
+            .collect();
+        let results = Arc::new(Mutex::new(results));
+
+        for (index, config) in configs.iter().enumerate() {
+            let config = config.clone();
+            let shared = Arc::clone(&results);
+            thread::Builder::new()
+                .name(format!("check-{}", index))
+                .spawn(move || run_periodically(&config, index, &shared))?;
+        }
+        if !configs.is_empty() {
+            info!("Running {} reachability check(s)", configs.len());
+        }
+        Ok(Checks { results })
+    }
+
+    pub fn results(&self) -> Vec<CheckResult> {
+        self.results.lock().unwrap().clone()
+    }
+}
+
+fn run_periodically(config: &CheckConfig, index: usize, results: &Mutex<Vec<CheckResult>>) {
+    let name = config.display_name();
+    let mut failing = false;
+    loop {
+        let outcome = match &config.target {
+            CheckTarget::Tcp(target) => check_tcp(target, config.timeout()),
+            CheckTarget::Ping(host) => check_ping(host, config.timeout()),
+        };
+        match &outcome {
+            Ok(latency) => {
+                if failing {
+                    info!("Check {} OK again", name);
+                }
+                debug!("Check {} OK in {:?}", name, latency);
+            }
+            Err(e) if !failing => warn!("Check {} failed: {:#}", name, e),
+            Err(e) => debug!("Check {} still failing: {:#}", name, e),
+        }
+        failing = outcome.is_err();
+        results.lock().unwrap()[index].outcome = Some(outcome.map_err(|e| format!("{:#}", e)));
+        thread::sleep(config.interval());
+    }
+}
+
+/// Time to open a TCP connection to `target`, trying each of its
+/// addresses until one answers or the timeout is used up.
+fn check_tcp(target: &str, timeout: Duration) -> Result<Duration> {
+    let start = Instant::now();
+    let addresses = target
+        .to_socket_addrs()
+        .with_context(|| format!("failed to resolve {}", target))?;
+    let mut last_error = None;
+    for address in addresses {
+        let remaining = timeout.saturating_sub(start.elapsed());
+        if remaining.is_zero() {
+            break;
+        }
+        match TcpStream::connect_timeout(&address, remaining) {
+            Ok(_) => return Ok(start.elapsed()),
+            Err(e) => last_error = Some(e),
+        }
+    }
+    match last_error {
+        Some(e) => Err(anyhow!(e).context(format!("failed to connect to {}", target))),
+        None => Err(anyhow!("timed out connecting to {}", target)),
+    }
+}
+
+/// Round trip time of one ICMP echo request. Uses the system `ping`, which
+/// is allowed to send ICMP without running the monitor as root.
+fn check_ping(host: &str, timeout: Duration) -> Result<Duration> {
+    let start = Instant::now();
+    // ping waits whole seconds.
+    let wait = timeout.as_millis().div_ceil(1000).max(1).to_string();
+    let output = Command::new("ping")
+        .args(["-c", "1", "-n", "-W", &wait, host])
+        .output()
+        .context("failed to run ping")?;
+    if !output.status.success() {
+        return Err(anyhow!("no reply from {}", host));
+    }
+    // Replies end in `time=0.428 ms`.
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let latency = stdout
+        .split("time=")
+        .nth(1)
+        .and_then(|rest| rest.split_whitespace().next())
+        .and_then(|ms| ms.parse::<f64>().ok())
+        .map(|ms| Duration::from_secs_f64(ms / 1000.0));
+    Ok(latency.unwrap_or_else(|| start.elapsed()))
+}
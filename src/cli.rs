@@ -0,0 +1,119 @@
+use crate::messages::Priority;
+use clap::{Parser, Subcommand};
+use std::path::PathBuf;
+use std::time::Duration;
+
+#[derive(Debug, Parser)]
+#[command(version, about)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Commands>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Commands {
+    /// Send a test notification through every configured alert channel
+    TestAlert,
+    /// Print the per-pixel on-time recorded with `track_pixels`
+    BurnInReport,
+    /// Print the current readings and fan thresholds once
+    Status {
+        /// Print JSON instead of text
+        #[arg(long)]
+        json: bool,
+    },
+    /// Wake the display of the running monitor and restore its brightness
+    Wake,
+    /// Show a message on the display of the running monitor
+    Notify {
+        text: String,
+        /// How long to show the message, e.g. 30s, 5m or 2h; until cleared
+        /// if left out
+        #[arg(long = "for", value_parser = parse_duration)]
+        duration: Option<Duration>,
+        #[arg(long, value_enum, default_value_t)]
+        priority: Priority,
+        /// Replaces an earlier message with the same id
+        #[arg(long)]
+        id: Option<String>,
+    },
+    /// Remove the message with the given id, or all messages
+    Clear {
+        #[arg(long)]
+        id: Option<String>,
+    },
+    /// Check the I2C bus, the display and the fan, and print what to fix
+    Diagnose,
+    /// Install the binary, the systemd service and a default config
+    Install {
+        /// Install below this directory instead of /, without enabling the
+        /// service
+        #[arg(long, default_value = "/")]
+        root: PathBuf,
+        /// User the service runs as; the user who ran sudo if left out
+        #[arg(long)]
+        user: Option<String>,
+    },
+    /// Stop and remove the systemd service and the binary
+    Uninstall {
+        #[arg(long, default_value = "/")]
+        root: PathBuf,
+        /// Also remove the config of this user; the user who ran sudo if
+        /// left out
+        #[arg(long)]
+        user: Option<String>,
+        /// Also remove the config
+        #[arg(long)]
+        purge: bool,
+    },
+}
+
+/// Parses a duration such as `90`, `30s`, `5m` or `2h`.
+fn parse_duration(value: &str) -> Result<Duration, String> {
+    let (number, unit) = value
+        .find(|c: char| !c.is_ascii_digit())
+        .map_or((value, ""), |index| value.split_at(index));
+    let number: u64 = number
+        .parse()
+        .map_err(|_| format!("invalid duration {:?}", value))?;
+    let factor = match unit {
+        "" | "s" => 1,
+        "m" => 60,
+        "h" => 3600,
+        _ => return Err(format!("invalid unit {:?}, expected s, m or h", unit)),
+    };
+    let seconds = number
+        .checked_mul(factor)
+        .ok_or_else(|| "duration too long".to_string())?;
+    Ok(Duration::from_secs(seconds))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn durations_with_units() {
+        assert_eq!(parse_duration("90"), Ok(Duration::from_secs(90)));
+        assert_eq!(parse_duration("30s"), Ok(Duration::from_secs(30)));
+        assert_eq!(parse_duration("5m"), Ok(Duration::from_secs(300)));
+        assert_eq!(parse_duration("2h"), Ok(Duration::from_secs(7200)));
+    }
+
+    #[test]
+    fn invalid_durations() {
+        assert!(parse_duration("").unwrap_err().contains("invalid duration"));
+        assert!(parse_duration("-5s")
+            .unwrap_err()
+            .contains("invalid duration"));
+        assert!(parse_duration("5d").unwrap_err().contains("invalid unit"));
+        assert_eq!(
+            parse_duration("18446744073709551615m"),
+            Err("duration too long".to_string())
+        );
+        assert_eq!(
+            parse_duration("18446744073709551615"),
+            Ok(Duration::from_secs(u64::MAX))
+        );
+    }
+}
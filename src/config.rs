@@ -1,20 +1,51 @@
 use log::{debug, info, warn};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::env;
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::time::Duration;
 
 #[derive(Debug, Deserialize, Serialize)]
 pub struct Config {
     pub display: DisplayConfig,
     pub fan: FanConfig,
+    #[serde(default)]
+    pub alerts: AlertsConfig,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub schedule: Vec<ScheduleConfig>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub check: Vec<CheckConfig>,
+    #[serde(default)]
+    pub control: ControlConfig,
+    #[serde(default)]
+    pub web: WebConfig,
+    #[serde(default)]
+    pub status: StatusConfig,
+    #[serde(default)]
+    pub services: ServicesConfig,
+    #[serde(default)]
+    pub containers: ContainersConfig,
+    #[serde(default)]
+    pub format: FormatConfig,
 }
 
 #[derive(Debug, Deserialize, Serialize)]
 pub struct DisplayConfig {
+    #[serde(default = "default_controller")]
+    pub controller: Controller,
+    #[serde(default = "default_height")]
+    pub height: u32,
+    #[serde(default = "default_rotation")]
+    pub rotation: u16,
     #[serde(default = "default_brightness")]
-    pub brightness: u8,
+    pub brightness: BrightnessSetting,
+    #[serde(default = "default_idle_brightness")]
+    pub idle_brightness: BrightnessSetting,
+    #[serde(default = "default_night_brightness")]
+    pub night_brightness: BrightnessSetting,
+    #[serde(default = "default_fade_duration_ms")]
+    pub fade_duration_ms: u64,
     #[serde(default = "default_screen_timeout")]
     pub screen_timeout: u64,
     #[serde(default = "default_periodic_off")]
@@ -25,16 +56,441 @@ pub struct DisplayConfig {
     pub periodic_off_duration: u64,
     #[serde(default = "default_refresh_interval_ms")]
     pub refresh_interval_ms: u64,
+    #[serde(default = "default_wake_on_alert")]
+    pub wake_on_alert: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub wake_pin: Option<u8>,
+    #[serde(default = "default_splash_duration")]
+    pub splash_duration: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub splash_logo: Option<PathBuf>,
+    #[serde(default = "default_pages")]
+    pub pages: Vec<Page>,
+    #[serde(default = "default_page_interval")]
+    pub page_interval: u64,
+    #[serde(default = "default_process_sort")]
+    pub process_sort: ProcessSort,
+    #[serde(default = "default_process_count")]
+    pub process_count: usize,
+    #[serde(default = "default_ram_used")]
+    pub ram_used: RamUsed,
+    #[serde(default)]
+    pub burn_in: BurnInConfig,
+    #[serde(default)]
+    pub overflow: OverflowConfig,
+    #[serde(default)]
+    pub fonts: FontsConfig,
+    #[serde(default)]
+    pub labels: LabelsConfig,
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub icons: HashMap<String, PathBuf>,
+}
+
+/// A screen in the page rotation.
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Page {
+    Overview,
+    Cpu,
+    System,
+    Processes,
+    Services,
+    Containers,
+    Checks,
+    Memory,
+}
+
+/// What the processes page ranks by.
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ProcessSort {
+    Cpu,
+    Memory,
+}
+
+/// What counts as used RAM: everything but the memory that is
+/// `available`, everything but free memory and the `cache`, or everything
+/// but `free` memory.
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum RamUsed {
+    Available,
+    Cache,
+    Free,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ShiftPattern {
+    Alternate,
+    Orbit,
+    RandomWalk,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct BurnInConfig {
+    #[serde(default = "default_shift_pattern")]
+    pub shift_pattern: ShiftPattern,
+    #[serde(default = "default_shift_interval")]
+    pub shift_interval: u64,
+    #[serde(default = "default_max_shift_x")]
+    pub max_shift_x: u8,
+    #[serde(default = "default_max_shift_y")]
+    pub max_shift_y: u8,
+    #[serde(default = "default_invert_interval")]
+    pub invert_interval: u64,
+    #[serde(default = "default_invert_duration")]
+    pub invert_duration: u64,
+    #[serde(default = "default_track_pixels")]
+    pub track_pixels: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub usage_file: Option<PathBuf>,
+}
+
+/// What to do with text that is wider than the space reserved for it.
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Overflow {
+    Truncate,
+    Shrink,
+    Marquee,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize, Serialize)]
+pub struct OverflowConfig {
+    #[serde(default = "default_overflow_hostname")]
+    pub hostname: Overflow,
+    #[serde(default = "default_overflow_ip")]
+    pub ip: Overflow,
+    #[serde(default = "default_overflow_value")]
+    pub cpu: Overflow,
+    #[serde(default = "default_overflow_value")]
+    pub ram: Overflow,
+    #[serde(default = "default_overflow_value")]
+    pub temp: Overflow,
+    #[serde(default = "default_overflow_value")]
+    pub disk: Overflow,
+    #[serde(default = "default_overflow_message")]
+    pub message: Overflow,
+    #[serde(default = "default_marquee_speed")]
+    pub marquee_speed: u32,
+}
+
+/// BDF or PCF files replacing the built-in fonts.
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct FontsConfig {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub value: Option<PathBuf>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub label: Option<PathBuf>,
+}
+
+/// Label texts. A label written as `:name:` is drawn as that icon.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct LabelsConfig {
+    #[serde(default)]
+    pub ip: String,
+    #[serde(default = "default_label_cpu")]
+    pub cpu: String,
+    #[serde(default = "default_label_ram")]
+    pub ram: String,
+    #[serde(default = "default_label_temp")]
+    pub temp: String,
+    #[serde(default = "default_label_disk")]
+    pub disk: String,
+    #[serde(default = "default_label_fan_on")]
+    pub fan_on: String,
+    #[serde(default = "default_label_fan_off")]
+    pub fan_off: String,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Controller {
+    Ssd1306,
+    Sh1106,
+}
+
+/// Either one of the five 0-4 presets or raw SSD1306 contrast (0-255) and
+/// precharge (1-15) values.
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(untagged)]
+pub enum BrightnessSetting {
+    Level(u8),
+    Custom {
+        contrast: u8,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        precharge: Option<u8>,
+    },
 }
 
 #[derive(Debug, Deserialize, Serialize)]
 pub struct FanConfig {
     pub temp_on: f32,
     pub temp_off: f32,
+    #[serde(default = "default_fan_safety_temp")]
+    pub safety_temp: f32,
 }
 
-fn default_brightness() -> u8 {
-    2
+/// Dashboard served over HTTP. Controls are only accepted with the token.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct WebConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default = "default_web_listen")]
+    pub listen: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub token: Option<String>,
+}
+
+/// Where the running monitor keeps a JSON copy of its latest readings.
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct StatusConfig {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file: Option<PathBuf>,
+}
+
+/// systemd units shown on the services page and alerted on when they fail.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct ServicesConfig {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub units: Vec<String>,
+    #[serde(default = "default_services_interval")]
+    pub interval: u64,
+}
+
+/// Docker or Podman API shown on the containers page.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct ContainersConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default = "default_containers_socket")]
+    pub socket: PathBuf,
+    #[serde(default = "default_containers_interval")]
+    pub interval: u64,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct ControlConfig {
+    #[serde(default = "default_control_enabled")]
+    pub enabled: bool,
+    #[serde(default = "default_control_socket")]
+    pub socket: PathBuf,
+    /// Permissions of the socket, clients need write access to connect.
+    #[serde(default = "default_control_socket_mode")]
+    pub socket_mode: u32,
+    /// Group the socket is handed to, the service user's group if missing.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub socket_group: Option<String>,
+}
+
+/// How values are formatted on the display and in machine-readable output.
+#[derive(Debug, Clone, Copy, Default, Deserialize, Serialize)]
+pub struct FormatConfig {
+    #[serde(default)]
+    pub cpu: PercentFormat,
+    #[serde(default)]
+    pub temp: TempFormat,
+    #[serde(default)]
+    pub ram: SizeFormat,
+    #[serde(default)]
+    pub disk: SizeFormat,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize, Serialize)]
+pub struct PercentFormat {
+    #[serde(default = "default_precision")]
+    pub precision: usize,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum TempUnit {
+    Celsius,
+    Fahrenheit,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize, Serialize)]
+pub struct TempFormat {
+    #[serde(default = "default_temp_unit")]
+    pub unit: TempUnit,
+    #[serde(default = "default_precision")]
+    pub precision: usize,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum SizeDisplay {
+    Percent,
+    Used,
+    Free,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum SizePrefix {
+    Iec,
+    Si,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize, Serialize)]
+pub struct SizeFormat {
+    #[serde(default = "default_size_display")]
+    pub show: SizeDisplay,
+    #[serde(default = "default_precision")]
+    pub precision: usize,
+    #[serde(default = "default_size_prefix")]
+    pub prefix: SizePrefix,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ScheduleDisplay {
+    On,
+    Dim,
+    Off,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ScheduleConfig {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(default = "default_schedule_days")]
+    pub days: Vec<String>,
+    pub start: String,
+    pub end: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub display: Option<ScheduleDisplay>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fan_temp_on: Option<f32>,
+}
+
+/// A reachability check, e.g. `tcp = "nas.local:445"` or
+/// `ping = "192.168.1.1"`.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct CheckConfig {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(flatten)]
+    pub target: CheckTarget,
+    #[serde(default = "default_check_interval")]
+    pub interval: u64,
+    #[serde(default = "default_check_timeout_ms")]
+    pub timeout_ms: u64,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CheckTarget {
+    /// `host:port` to open a TCP connection to.
+    Tcp(String),
+    /// Host to send an ICMP echo request to with `ping`.
+    Ping(String),
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct AlertsConfig {
+    #[serde(default = "default_alerts_enabled")]
+    pub enabled: bool,
+    #[serde(default = "default_alert_temp_threshold")]
+    pub temp_threshold: f32,
+    #[serde(default = "default_alert_temp_clear")]
+    pub temp_clear: f32,
+    #[serde(default = "default_alert_disk_threshold")]
+    pub disk_threshold: f64,
+    #[serde(default = "default_alert_disk_clear")]
+    pub disk_clear: f64,
+    #[serde(default = "default_alert_fan_stuck_after")]
+    pub fan_stuck_after: u64,
+    #[serde(default = "default_alert_under_voltage")]
+    pub under_voltage: bool,
+    #[serde(default = "default_alert_failed_units")]
+    pub failed_units: bool,
+    #[serde(default = "default_alert_containers")]
+    pub containers: bool,
+    #[serde(default = "default_alert_checks")]
+    pub checks: bool,
+    #[serde(default = "default_alert_cooldown")]
+    pub cooldown: u64,
+    #[serde(default = "default_alert_max_per_hour")]
+    pub max_per_hour: u32,
+    #[serde(default = "default_alert_send_recovery")]
+    pub send_recovery: bool,
+    #[serde(default = "default_alert_timeout")]
+    pub timeout: u64,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub webhook: Vec<WebhookConfig>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub push: Vec<PushConfig>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub smtp: Vec<SmtpConfig>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct WebhookConfig {
+    pub url: String,
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub headers: HashMap<String, String>,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum PushKind {
+    Ntfy,
+    Gotify,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct PushConfig {
+    pub kind: PushKind,
+    pub url: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub token: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum SmtpSecurity {
+    None,
+    Starttls,
+    Tls,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct SmtpConfig {
+    pub host: String,
+    #[serde(default = "default_smtp_port")]
+    pub port: u16,
+    #[serde(default = "default_smtp_security")]
+    pub security: SmtpSecurity,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub username: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub password: Option<String>,
+    pub from: String,
+    pub to: Vec<String>,
+}
+
+fn default_controller() -> Controller {
+    Controller::Ssd1306
+}
+fn default_height() -> u32 {
+    32
+}
+fn default_rotation() -> u16 {
+    0
+}
+fn default_brightness() -> BrightnessSetting {
+    BrightnessSetting::Level(2)
+}
+fn default_idle_brightness() -> BrightnessSetting {
+    BrightnessSetting::Level(0)
+}
+fn default_night_brightness() -> BrightnessSetting {
+    BrightnessSetting::Level(0)
+}
+fn default_fade_duration_ms() -> u64 {
+    500
 }
 fn default_screen_timeout() -> u64 {
     300
@@ -51,6 +507,176 @@ fn default_periodic_off_duration_seconds() -> u64 {
 fn default_refresh_interval_ms() -> u64 {
     1000
 }
+fn default_shift_pattern() -> ShiftPattern {
+    ShiftPattern::Alternate
+}
+fn default_shift_interval() -> u64 {
+    60
+}
+fn default_max_shift_x() -> u8 {
+    1
+}
+fn default_max_shift_y() -> u8 {
+    0
+}
+fn default_invert_interval() -> u64 {
+    0
+}
+fn default_invert_duration() -> u64 {
+    10
+}
+fn default_track_pixels() -> bool {
+    false
+}
+fn default_overflow_hostname() -> Overflow {
+    Overflow::Truncate
+}
+fn default_overflow_ip() -> Overflow {
+    Overflow::Shrink
+}
+fn default_overflow_value() -> Overflow {
+    Overflow::Shrink
+}
+fn default_overflow_message() -> Overflow {
+    Overflow::Marquee
+}
+fn default_marquee_speed() -> u32 {
+    8
+}
+fn default_label_cpu() -> String {
+    "CPU".to_string()
+}
+fn default_label_ram() -> String {
+    "RAM".to_string()
+}
+fn default_label_temp() -> String {
+    ":thermometer:".to_string()
+}
+fn default_label_disk() -> String {
+    "DISK".to_string()
+}
+fn default_label_fan_on() -> String {
+    "FAN ON".to_string()
+}
+fn default_label_fan_off() -> String {
+    "FAN OFF".to_string()
+}
+fn default_wake_on_alert() -> bool {
+    true
+}
+fn default_splash_duration() -> u64 {
+    2
+}
+fn default_pages() -> Vec<Page> {
+    vec![Page::Overview]
+}
+fn default_page_interval() -> u64 {
+    10
+}
+fn default_process_sort() -> ProcessSort {
+    ProcessSort::Cpu
+}
+fn default_process_count() -> usize {
+    4
+}
+fn default_ram_used() -> RamUsed {
+    RamUsed::Available
+}
+fn default_control_enabled() -> bool {
+    true
+}
+/// The unit's `RuntimeDirectory`, the same for the service and the CLI
+/// whatever their environment.
+fn default_control_socket() -> PathBuf {
+    PathBuf::from("/run/rustberry-poe-monitor/control.sock")
+}
+fn default_control_socket_mode() -> u32 {
+    0o660
+}
+fn default_web_listen() -> String {
+    "0.0.0.0:8080".to_string()
+}
+fn default_services_interval() -> u64 {
+    10
+}
+fn default_containers_socket() -> PathBuf {
+    PathBuf::from("/var/run/docker.sock")
+}
+fn default_containers_interval() -> u64 {
+    10
+}
+fn default_fan_safety_temp() -> f32 {
+    75.0
+}
+fn default_precision() -> usize {
+    1
+}
+fn default_temp_unit() -> TempUnit {
+    TempUnit::Celsius
+}
+fn default_size_display() -> SizeDisplay {
+    SizeDisplay::Percent
+}
+fn default_size_prefix() -> SizePrefix {
+    SizePrefix::Iec
+}
+fn default_check_interval() -> u64 {
+    30
+}
+fn default_check_timeout_ms() -> u64 {
+    2000
+}
+fn default_schedule_days() -> Vec<String> {
+    vec!["daily".to_string()]
+}
+fn default_alerts_enabled() -> bool {
+    false
+}
+fn default_alert_temp_threshold() -> f32 {
+    75.0
+}
+fn default_alert_temp_clear() -> f32 {
+    70.0
+}
+fn default_alert_disk_threshold() -> f64 {
+    90.0
+}
+fn default_alert_disk_clear() -> f64 {
+    85.0
+}
+fn default_alert_fan_stuck_after() -> u64 {
+    3600
+}
+fn default_alert_under_voltage() -> bool {
+    true
+}
+fn default_alert_failed_units() -> bool {
+    true
+}
+fn default_alert_containers() -> bool {
+    true
+}
+fn default_alert_checks() -> bool {
+    true
+}
+fn default_alert_cooldown() -> u64 {
+    900
+}
+fn default_alert_max_per_hour() -> u32 {
+    20
+}
+fn default_alert_send_recovery() -> bool {
+    true
+}
+fn default_alert_timeout() -> u64 {
+    10
+}
+fn default_smtp_port() -> u16 {
+    587
+}
+fn default_smtp_security() -> SmtpSecurity {
+    SmtpSecurity::Starttls
+}
 
 impl Config {
     pub fn load() -> Result<Self, Box<dyn std::error::Error>> {
@@ -70,19 +696,16 @@ impl Config {
             debug!("Loading config file from: {:?}", config_path);
             let config_str = fs::read_to_string(config_path)?;
             let config: Config = toml::from_str(&config_str)?;
+            for check in &config.check {
+                check.validate()?;
+            }
             Ok(config)
         }
     }
 
     fn get_config_path() -> Result<PathBuf, Box<dyn std::error::Error>> {
         match env::var("HOME") {
-            Ok(home_dir) => {
-                let mut path = PathBuf::from(home_dir);
-                path.push(".config");
-                path.push("rustberry-poe-monitor");
-                path.push("config.toml");
-                Ok(path)
-            }
+            Ok(home_dir) => Ok(Self::path_in_home(Path::new(&home_dir))),
             Err(_) => {
                 warn!("HOME environment variable not set. Using config.toml in current directory.");
                 Ok(PathBuf::from("config.toml"))
@@ -90,6 +713,13 @@ impl Config {
         }
     }
 
+    /// The config file of a user with the home directory `home`.
+    pub fn path_in_home(home: &Path) -> PathBuf {
+        home.join(".config")
+            .join("rustberry-poe-monitor")
+            .join("config.toml")
+    }
+
     pub fn display_timeout(&self) -> Duration {
         Duration::from_secs(self.display.screen_timeout)
     }
@@ -105,23 +735,310 @@ impl Config {
     pub fn refresh_interval(&self) -> Duration {
         Duration::from_millis(self.display.refresh_interval_ms)
     }
+
+    pub fn splash_duration(&self) -> Duration {
+        Duration::from_secs(self.display.splash_duration)
+    }
+
+    pub fn page_interval(&self) -> Duration {
+        Duration::from_secs(self.display.page_interval)
+    }
+}
+
+impl BurnInConfig {
+    pub fn shift_interval(&self) -> Duration {
+        Duration::from_secs(self.shift_interval)
+    }
+
+    pub fn invert_interval(&self) -> Duration {
+        Duration::from_secs(self.invert_interval)
+    }
+
+    pub fn invert_duration(&self) -> Duration {
+        Duration::from_secs(self.invert_duration)
+    }
+
+    /// Where the accumulated per-pixel on-time is kept between restarts.
+    pub fn usage_file_path(&self) -> PathBuf {
+        if let Some(path) = &self.usage_file {
+            return path.clone();
+        }
+        let state_dir = env::var("XDG_STATE_HOME")
+            .map(PathBuf::from)
+            .or_else(|_| env::var("HOME").map(|home| PathBuf::from(home).join(".local/state")))
+            .unwrap_or_else(|_| PathBuf::from("."));
+        state_dir
+            .join("rustberry-poe-monitor")
+            .join("pixel-usage.bin")
+    }
+}
+
+impl Default for BurnInConfig {
+    fn default() -> Self {
+        BurnInConfig {
+            shift_pattern: default_shift_pattern(),
+            shift_interval: default_shift_interval(),
+            max_shift_x: default_max_shift_x(),
+            max_shift_y: default_max_shift_y(),
+            invert_interval: default_invert_interval(),
+            invert_duration: default_invert_duration(),
+            track_pixels: default_track_pixels(),
+            usage_file: None,
+        }
+    }
+}
+
+impl Default for OverflowConfig {
+    fn default() -> Self {
+        OverflowConfig {
+            hostname: default_overflow_hostname(),
+            ip: default_overflow_ip(),
+            cpu: default_overflow_value(),
+            ram: default_overflow_value(),
+            temp: default_overflow_value(),
+            disk: default_overflow_value(),
+            message: default_overflow_message(),
+            marquee_speed: default_marquee_speed(),
+        }
+    }
+}
+
+impl Default for LabelsConfig {
+    fn default() -> Self {
+        LabelsConfig {
+            ip: String::new(),
+            cpu: default_label_cpu(),
+            ram: default_label_ram(),
+            temp: default_label_temp(),
+            disk: default_label_disk(),
+            fan_on: default_label_fan_on(),
+            fan_off: default_label_fan_off(),
+        }
+    }
+}
+
+impl Default for ControlConfig {
+    fn default() -> Self {
+        ControlConfig {
+            enabled: default_control_enabled(),
+            socket: default_control_socket(),
+            socket_mode: default_control_socket_mode(),
+            socket_group: None,
+        }
+    }
+}
+
+impl ServicesConfig {
+    pub fn interval(&self) -> Duration {
+        Duration::from_secs(self.interval)
+    }
+}
+
+impl Default for ServicesConfig {
+    fn default() -> Self {
+        ServicesConfig {
+            units: Vec::new(),
+            interval: default_services_interval(),
+        }
+    }
+}
+
+impl CheckConfig {
+    /// The configured name, or the target if there is none.
+    pub fn display_name(&self) -> String {
+        match (&self.name, &self.target) {
+            (Some(name), _) => name.clone(),
+            (None, CheckTarget::Tcp(target) | CheckTarget::Ping(target)) => target.clone(),
+        }
+    }
+
+    pub fn interval(&self) -> Duration {
+        Duration::from_secs(self.interval)
+    }
+
+    pub fn timeout(&self) -> Duration {
+        Duration::from_millis(self.timeout_ms)
+    }
+
+    /// Zero would run the check in a tight loop or make it fail every time.
+    pub fn validate(&self) -> Result<(), String> {
+        if self.interval == 0 {
+            return Err(format!(
+                "check {}: interval must be at least 1 second",
+                self.display_name()
+            ));
+        }
+        if self.timeout_ms == 0 {
+            return Err(format!(
+                "check {}: timeout_ms must be at least 1",
+                self.display_name()
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl ContainersConfig {
+    pub fn interval(&self) -> Duration {
+        Duration::from_secs(self.interval)
+    }
+}
+
+impl Default for ContainersConfig {
+    fn default() -> Self {
+        ContainersConfig {
+            enabled: false,
+            socket: default_containers_socket(),
+            interval: default_containers_interval(),
+        }
+    }
+}
+
+impl Default for WebConfig {
+    fn default() -> Self {
+        WebConfig {
+            enabled: false,
+            listen: default_web_listen(),
+            token: None,
+        }
+    }
+}
+
+impl Default for PercentFormat {
+    fn default() -> Self {
+        PercentFormat {
+            precision: default_precision(),
+        }
+    }
+}
+
+impl Default for TempFormat {
+    fn default() -> Self {
+        TempFormat {
+            unit: default_temp_unit(),
+            precision: default_precision(),
+        }
+    }
+}
+
+impl Default for SizeFormat {
+    fn default() -> Self {
+        SizeFormat {
+            show: default_size_display(),
+            precision: default_precision(),
+            prefix: default_size_prefix(),
+        }
+    }
+}
+
+impl AlertsConfig {
+    pub fn fan_stuck_after(&self) -> Duration {
+        Duration::from_secs(self.fan_stuck_after)
+    }
+
+    pub fn cooldown(&self) -> Duration {
+        Duration::from_secs(self.cooldown)
+    }
+
+    pub fn timeout(&self) -> Duration {
+        Duration::from_secs(self.timeout)
+    }
+}
+
+impl Default for AlertsConfig {
+    fn default() -> Self {
+        AlertsConfig {
+            enabled: default_alerts_enabled(),
+            temp_threshold: default_alert_temp_threshold(),
+            temp_clear: default_alert_temp_clear(),
+            disk_threshold: default_alert_disk_threshold(),
+            disk_clear: default_alert_disk_clear(),
+            fan_stuck_after: default_alert_fan_stuck_after(),
+            under_voltage: default_alert_under_voltage(),
+            failed_units: default_alert_failed_units(),
+            containers: default_alert_containers(),
+            checks: default_alert_checks(),
+            cooldown: default_alert_cooldown(),
+            max_per_hour: default_alert_max_per_hour(),
+            send_recovery: default_alert_send_recovery(),
+            timeout: default_alert_timeout(),
+            webhook: Vec::new(),
+            push: Vec::new(),
+            smtp: Vec::new(),
+        }
+    }
 }
 
 impl Default for Config {
     fn default() -> Self {
         Config {
             display: DisplayConfig {
+                controller: default_controller(),
+                height: default_height(),
+                rotation: default_rotation(),
                 brightness: default_brightness(),
+                idle_brightness: default_idle_brightness(),
+                night_brightness: default_night_brightness(),
+                fade_duration_ms: default_fade_duration_ms(),
                 screen_timeout: default_screen_timeout(),
                 enable_periodic_off: default_periodic_off(),
                 periodic_on_duration: default_periodic_on_duration_seconds(),
                 periodic_off_duration: default_periodic_off_duration_seconds(),
                 refresh_interval_ms: default_refresh_interval_ms(),
+                wake_on_alert: default_wake_on_alert(),
+                wake_pin: None,
+                splash_duration: default_splash_duration(),
+                splash_logo: None,
+                pages: default_pages(),
+                page_interval: default_page_interval(),
+                process_sort: default_process_sort(),
+                process_count: default_process_count(),
+                ram_used: default_ram_used(),
+                burn_in: BurnInConfig::default(),
+                overflow: OverflowConfig::default(),
+                fonts: FontsConfig::default(),
+                labels: LabelsConfig::default(),
+                icons: HashMap::new(),
             },
             fan: FanConfig {
                 temp_on: 60.0,
                 temp_off: 50.0,
+                safety_temp: default_fan_safety_temp(),
             },
+            alerts: AlertsConfig::default(),
+            schedule: Vec::new(),
+            check: Vec::new(),
+            control: ControlConfig::default(),
+            web: WebConfig::default(),
+            status: StatusConfig::default(),
+            services: ServicesConfig::default(),
+            containers: ContainersConfig::default(),
+            format: FormatConfig::default(),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check(toml: &str) -> CheckConfig {
+        toml::from_str(toml).unwrap()
+    }
+
+    #[test]
+    fn checks_need_an_interval_and_a_timeout() {
+        assert!(check(r#"tcp = "nas.local:445""#).validate().is_ok());
+        let error = check("ping = \"192.168.1.1\"\ninterval = 0")
+            .validate()
+            .unwrap_err();
+        assert_eq!(
+            error,
+            "check 192.168.1.1: interval must be at least 1 second"
+        );
+        let error = check("name = \"NAS\"\ntcp = \"nas.local:445\"\ntimeout_ms = 0")
+            .validate()
+            .unwrap_err();
+        assert_eq!(error, "check NAS: timeout_ms must be at least 1");
+    }
+}
@@ -0,0 +1,536 @@
+use crate::config::ContainersConfig;
+use anyhow::{anyhow, Context, Result};
+use log::{debug, info, warn};
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use std::collections::{HashMap, VecDeque};
+use std::io::{Read, Write};
+use std::os::unix::net::UnixStream;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Longest time a single API request may take.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A container that restarted this many times within `RESTART_LOOP_WINDOW`
+/// is reported as restart-looping.
+const RESTART_LOOP_COUNT: usize = 3;
+const RESTART_LOOP_WINDOW: Duration = Duration::from_secs(600);
+
+/// One container as shown on the containers page.
+#[derive(Debug, Clone)]
+pub struct ContainerStatus {
+    pub name: String,
+    /// `running`, `exited`, `restarting`, `paused`, ...
+    pub state: String,
+    /// `healthy`, `unhealthy` or `starting` for containers with a health
+    /// check.
+    pub health: Option<String>,
+    /// Like `docker stats`, 100% is one full core. `None` until two samples
+    /// were taken, and for stopped containers.
+    pub cpu_percent: Option<f32>,
+    /// Memory in use without the page cache, like `docker stats`.
+    pub memory_bytes: Option<u64>,
+    pub restart_loop: bool,
+}
+
+impl ContainerStatus {
+    pub fn is_running(&self) -> bool {
+        self.state == "running"
+    }
+
+    pub fn is_unhealthy(&self) -> bool {
+        self.health.as_deref() == Some("unhealthy")
+    }
+}
+
+/// Latest result of polling the container API.
+#[derive(Debug, Clone, Default)]
+pub struct ContainerSummary {
+    pub containers: Vec<ContainerStatus>,
+    /// Set while the API can't be reached.
+    pub error: Option<String>,
+}
+
+impl ContainerSummary {
+    pub fn running(&self) -> usize {
+        self.containers.iter().filter(|c| c.is_running()).count()
+    }
+
+    pub fn stopped(&self) -> usize {
+        self.containers.len() - self.running()
+    }
+
+    pub fn unhealthy(&self) -> Vec<String> {
+        self.names(ContainerStatus::is_unhealthy)
+    }
+
+    pub fn restart_loops(&self) -> Vec<String> {
+        self.names(|container| container.restart_loop)
+    }
+
+    fn names(&self, filter: impl Fn(&ContainerStatus) -> bool) -> Vec<String> {
+        self.containers
+            .iter()
+            .filter(|container| filter(container))
+            .map(|container| container.name.clone())
+            .collect()
+    }
+}
+
+/// Polls a Docker compatible API on its own thread, so a slow or hanging
+/// daemon never delays the display.
+pub struct ContainerMonitor {
+    summary: Arc<Mutex<ContainerSummary>>,
+}
+
+impl ContainerMonitor {
+    pub fn start(config: &ContainersConfig) -> Result<Self> {
+        let summary = Arc::new(Mutex::new(ContainerSummary::default()));
+        let mut poller = Poller::new(config.socket.clone());
+        let interval = config.interval();
+        let shared = Arc::clone(&summary);
+        thread::Builder::new()
+            .name("containers".to_string())
+            .spawn(move || loop {
+                let result = poller.poll(Instant::now());
+                *shared.lock().unwrap() = result;
+                thread::sleep(interval);
+            })?;
+        info!("Watching containers via {}", config.socket.display());
+        Ok(ContainerMonitor { summary })
+    }
+
+    pub fn summary(&self) -> ContainerSummary {
+        self.summary.lock().unwrap().clone()
+    }
+}
+
+/// State kept between two polls: CPU counters for the usage and restart
+/// times for loop detection, both by container id.
+struct Poller {
+    socket: PathBuf,
+    cpu_samples: HashMap<String, (u64, u64)>,
+    restarts: HashMap<String, (u64, VecDeque<Instant>)>,
+    failed: bool,
+}
+
+impl Poller {
+    fn new(socket: PathBuf) -> Self {
+        Poller {
+            socket,
+            cpu_samples: HashMap::new(),
+            restarts: HashMap::new(),
+            failed: false,
+        }
+    }
+
+    fn poll(&mut self, now: Instant) -> ContainerSummary {
+        match self.read_containers(now) {
+            Ok(containers) => {
+                if self.failed {
+                    info!("Container API reachable again");
+                    self.failed = false;
+                }
+                ContainerSummary {
+                    containers,
+                    error: None,
+                }
+            }
+            Err(e) => {
+                // Logged once, until the API works again.
+                if !self.failed {
+                    warn!("Failed to read containers: {:#}", e);
+                    self.failed = true;
+                }
+                ContainerSummary {
+                    containers: Vec::new(),
+                    error: Some(format!("{:#}", e)),
+                }
+            }
+        }
+    }
+
+    fn read_containers(&mut self, now: Instant) -> Result<Vec<ContainerStatus>> {
+        let list: Vec<ListEntry> = get_json(&self.socket, "/containers/json?all=true")?;
+        let mut containers = Vec::with_capacity(list.len());
+        for entry in &list {
+            // Containers can go away between listing and inspecting them,
+            // `--rm` ones in particular, so one failing doesn't fail the poll.
+            let inspect: Inspect =
+                match get_json(&self.socket, &format!("/containers/{}/json", entry.id)) {
+                    Ok(inspect) => inspect,
+                    Err(e) => {
+                        debug!("Skipping container {}: {:#}", entry.id, e);
+                        continue;
+                    }
+                };
+            let running = inspect.state.status == "running";
+            let (cpu_percent, memory_bytes) = if running {
+                self.read_stats(&entry.id).unwrap_or_else(|e| {
+                    debug!("No stats for container {}: {:#}", entry.id, e);
+                    (None, None)
+                })
+            } else {
+                self.cpu_samples.remove(&entry.id);
+                (None, None)
+            };
+            containers.push(ContainerStatus {
+                name: inspect.name.trim_start_matches('/').to_string(),
+                state: inspect.state.status,
+                health: inspect.state.health.map(|health| health.status),
+                cpu_percent,
+                memory_bytes,
+                restart_loop: self.track_restarts(&entry.id, inspect.restart_count, now),
+            });
+        }
+
+        let ids: Vec<&str> = list.iter().map(|entry| entry.id.as_str()).collect();
+        self.cpu_samples.retain(|id, _| ids.contains(&id.as_str()));
+        self.restarts.retain(|id, _| ids.contains(&id.as_str()));
+        Ok(containers)
+    }
+
+    /// CPU usage since the previous poll and current memory use. Uses a
+    /// one-shot sample, so the API doesn't wait a second per container.
+    fn read_stats(&mut self, id: &str) -> Result<(Option<f32>, Option<u64>)> {
+        let path = format!("/containers/{}/stats?stream=false&one-shot=true", id);
+        let stats: Stats = get_json(&self.socket, &path)?;
+
+        let cpu = &stats.cpu_stats;
+        let sample = (cpu.cpu_usage.total_usage, cpu.system_cpu_usage.unwrap_or(0));
+        let previous = self.cpu_samples.insert(id.to_string(), sample);
+        let cpu_percent = previous.and_then(|(total, system)| {
+            let cpu_delta = sample.0.checked_sub(total)?;
+            let system_delta = sample.1.checked_sub(system).filter(|delta| *delta > 0)?;
+            let cores = cpu.online_cpus.unwrap_or(1) as f32;
+            Some(cpu_delta as f32 / system_delta as f32 * cores * 100.0)
+        });
+
+        let memory = &stats.memory_stats;
+        // cgroup v2 calls the page cache `inactive_file`, v1
+        // `total_inactive_file`.
+        let cache = memory
+            .stats
+            .as_ref()
+            .and_then(|stats| {
+                stats
+                    .get("inactive_file")
+                    .or_else(|| stats.get("total_inactive_file"))
+            })
+            .copied()
+            .unwrap_or(0);
+        let memory_bytes = memory.usage.map(|usage| usage.saturating_sub(cache));
+        Ok((cpu_percent, memory_bytes))
+    }
+
+    /// Records new restarts of `id` and tells whether it restarted too often
+    /// recently.
+    fn track_restarts(&mut self, id: &str, count: u64, now: Instant) -> bool {
+        let (last_count, times) = self
+            .restarts
+            .entry(id.to_string())
+            .or_insert_with(|| (count, VecDeque::new()));
+        for _ in *last_count..count {
+            times.push_back(now);
+        }
+        *last_count = count;
+        while times
+            .front()
+            .is_some_and(|time| now.duration_since(*time) > RESTART_LOOP_WINDOW)
+        {
+            times.pop_front();
+        }
+        times.len() >= RESTART_LOOP_COUNT
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct ListEntry {
+    id: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct Inspect {
+    name: String,
+    #[serde(default)]
+    restart_count: u64,
+    state: InspectState,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct InspectState {
+    status: String,
+    health: Option<Health>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct Health {
+    status: String,
+}
+
+#[derive(Deserialize)]
+struct Stats {
+    cpu_stats: CpuStats,
+    memory_stats: MemoryStats,
+}
+
+#[derive(Deserialize)]
+struct CpuStats {
+    cpu_usage: CpuUsage,
+    system_cpu_usage: Option<u64>,
+    online_cpus: Option<u32>,
+}
+
+#[derive(Deserialize)]
+struct CpuUsage {
+    total_usage: u64,
+}
+
+#[derive(Deserialize)]
+struct MemoryStats {
+    usage: Option<u64>,
+    stats: Option<HashMap<String, u64>>,
+}
+
+/// Sends a GET request over the API socket and parses the JSON body.
+fn get_json<T: DeserializeOwned>(socket: &Path, path: &str) -> Result<T> {
+    let body = get(socket, path)?;
+    serde_json::from_slice(&body).with_context(|| format!("invalid response to {}", path))
+}
+
+/// A minimal HTTP/1.0 client. HTTP/1.0 makes the server close the
+/// connection after the response, so the body is simply everything that
+/// follows the headers.
+fn get(socket: &Path, path: &str) -> Result<Vec<u8>> {
+    let mut stream = UnixStream::connect(socket)
+        .with_context(|| format!("failed to connect to {}", socket.display()))?;
+    stream.set_read_timeout(Some(REQUEST_TIMEOUT))?;
+    stream.set_write_timeout(Some(REQUEST_TIMEOUT))?;
+    write!(stream, "GET {} HTTP/1.0\r\nHost: localhost\r\n\r\n", path)?;
+    let mut response = Vec::new();
+    stream.read_to_end(&mut response)?;
+
+    let split = response
+        .windows(4)
+        .position(|window| window == b"\r\n\r\n")
+        .ok_or_else(|| anyhow!("incomplete response to {}", path))?;
+    let head = String::from_utf8_lossy(&response[..split]).to_string();
+    let body = response.split_off(split + 4);
+
+    let status = head
+        .split_whitespace()
+        .nth(1)
+        .and_then(|code| code.parse::<u16>().ok())
+        .ok_or_else(|| anyhow!("invalid response to {}", path))?;
+    if !(200..300).contains(&status) {
+        return Err(anyhow!(
+            "{} returned {}: {}",
+            path,
+            status,
+            String::from_utf8_lossy(&body).trim()
+        ));
+    }
+    debug!("GET {} returned {} bytes", path, body.len());
+
+    let chunked = head.lines().any(|line| {
+        line.to_ascii_lowercase()
+            .starts_with("transfer-encoding: chunked")
+    });
+    if chunked {
+        dechunk(&body).ok_or_else(|| anyhow!("invalid chunked response to {}", path))
+    } else {
+        Ok(body)
+    }
+}
+
+/// Joins the chunks of a chunked body, for servers that send one despite
+/// the HTTP/1.0 request.
+fn dechunk(mut body: &[u8]) -> Option<Vec<u8>> {
+    let mut data = Vec::new();
+    loop {
+        let line_end = body.windows(2).position(|window| window == b"\r\n")?;
+        let size_field = std::str::from_utf8(&body[..line_end]).ok()?;
+        let size_field = size_field.split(';').next()?.trim();
+        let size = usize::from_str_radix(size_field, 16).ok()?;
+        if size == 0 {
+            return Some(data);
+        }
+        let start = line_end + 2;
+        data.extend_from_slice(body.get(start..start + size)?);
+        body = body.get(start + size + 2..)?;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader};
+    use std::os::unix::net::UnixListener;
+
+    /// Serves `routes` on a socket in the temp directory until the test ends.
+    /// Paths without a route get a 404, like removed containers.
+    fn api_stand_in(name: &str, routes: Arc<Mutex<HashMap<String, String>>>) -> PathBuf {
+        let socket = std::env::temp_dir().join(format!(
+            "rustberry-containers-{}-{}.sock",
+            std::process::id(),
+            name
+        ));
+        let _ = std::fs::remove_file(&socket);
+        let listener = UnixListener::bind(&socket).unwrap();
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = BufReader::new(stream.unwrap());
+                let mut request = String::new();
+                stream.read_line(&mut request).unwrap();
+                loop {
+                    let mut line = String::new();
+                    stream.read_line(&mut line).unwrap();
+                    if line == "\r\n" || line.is_empty() {
+                        break;
+                    }
+                }
+                let path = request.split_whitespace().nth(1).unwrap_or_default();
+                let response = match routes.lock().unwrap().get(path) {
+                    Some(body) => format!("HTTP/1.0 200 OK\r\n\r\n{}", body),
+                    None => "HTTP/1.0 404 Not Found\r\n\r\nno such container".to_string(),
+                };
+                stream.get_mut().write_all(response.as_bytes()).unwrap();
+            }
+        });
+        socket
+    }
+
+    fn stats_path(id: &str) -> String {
+        format!("/containers/{}/stats?stream=false&one-shot=true", id)
+    }
+
+    fn inspect(name: &str, status: &str, restart_count: u64) -> String {
+        format!(
+            r#"{{"Name":"/{}","RestartCount":{},"State":{{"Status":"{}"}}}}"#,
+            name, restart_count, status
+        )
+    }
+
+    fn stats(total_usage: u64, system_usage: u64) -> String {
+        format!(
+            r#"{{"cpu_stats":{{"cpu_usage":{{"total_usage":{}}},"system_cpu_usage":{},"online_cpus":4}},
+                "memory_stats":{{"usage":50000000,"stats":{{"inactive_file":10000000}}}}}}"#,
+            total_usage, system_usage
+        )
+    }
+
+    #[test]
+    fn dechunk_joins_the_chunks() {
+        assert_eq!(
+            dechunk(b"4\r\n[1,2\r\n2;ext=1\r\n,3\r\n1\r\n]\r\n0\r\n\r\n").as_deref(),
+            Some(&b"[1,2,3]"[..])
+        );
+        assert_eq!(dechunk(b"0\r\n\r\n").as_deref(), Some(&b""[..]));
+    }
+
+    #[test]
+    fn dechunk_rejects_broken_bodies() {
+        // Cut off in the middle of a chunk, before the last chunk and with a
+        // size that isn't hex.
+        assert_eq!(dechunk(b"a\r\n[1,2"), None);
+        assert_eq!(dechunk(b"2\r\n[]\r\n"), None);
+        assert_eq!(dechunk(b"zz\r\n[]\r\n0\r\n\r\n"), None);
+    }
+
+    #[test]
+    fn poll_computes_the_cpu_usage_between_two_samples() {
+        let routes = Arc::new(Mutex::new(HashMap::from([
+            (
+                "/containers/json?all=true".to_string(),
+                r#"[{"Id":"abc"},{"Id":"def"}]"#.to_string(),
+            ),
+            (
+                "/containers/abc/json".to_string(),
+                inspect("web", "running", 0),
+            ),
+            (
+                "/containers/def/json".to_string(),
+                inspect("backup", "exited", 0),
+            ),
+            (stats_path("abc"), stats(1_000_000, 100_000_000)),
+        ])));
+        let mut poller = Poller::new(api_stand_in("cpu", Arc::clone(&routes)));
+        let start = Instant::now();
+
+        let summary = poller.poll(start);
+        assert_eq!(summary.error, None);
+        assert_eq!(summary.running(), 1);
+        assert_eq!(summary.stopped(), 1);
+        let web = &summary.containers[0];
+        assert_eq!(web.name, "web");
+        assert_eq!(web.cpu_percent, None);
+        assert_eq!(web.memory_bytes, Some(40_000_000));
+        assert_eq!(summary.containers[1].memory_bytes, None);
+
+        // 1.25% of the system time on four cores is 5% of one core.
+        routes
+            .lock()
+            .unwrap()
+            .insert(stats_path("abc"), stats(6_000_000, 500_000_000));
+        let summary = poller.poll(start + Duration::from_secs(5));
+        let cpu_percent = summary.containers[0].cpu_percent.unwrap();
+        assert!((cpu_percent - 5.0).abs() < 0.01, "{}", cpu_percent);
+    }
+
+    #[test]
+    fn poll_skips_containers_that_went_away() {
+        let routes = Arc::new(Mutex::new(HashMap::from([
+            (
+                "/containers/json?all=true".to_string(),
+                r#"[{"Id":"gone"},{"Id":"abc"}]"#.to_string(),
+            ),
+            (
+                "/containers/abc/json".to_string(),
+                inspect("web", "running", 0),
+            ),
+        ])));
+        let mut poller = Poller::new(api_stand_in("gone", routes));
+
+        // Neither the 404 for `gone` nor the missing stats of `web` fail the
+        // poll.
+        let summary = poller.poll(Instant::now());
+        assert_eq!(summary.error, None);
+        assert_eq!(summary.containers.len(), 1);
+        assert_eq!(summary.containers[0].name, "web");
+        assert_eq!(summary.containers[0].memory_bytes, None);
+    }
+
+    #[test]
+    fn poll_reports_an_unreachable_api() {
+        let socket = std::env::temp_dir().join("rustberry-containers-missing.sock");
+        let summary = Poller::new(socket).poll(Instant::now());
+        assert!(summary.containers.is_empty());
+        assert!(summary.error.unwrap().contains("failed to connect"));
+    }
+
+    #[test]
+    fn restarts_within_the_window_are_a_loop() {
+        let mut poller = Poller::new(PathBuf::new());
+        let start = Instant::now();
+        let minute = Duration::from_secs(60);
+
+        // Restarts before the first poll don't count.
+        assert!(!poller.track_restarts("abc", 7, start));
+        assert!(!poller.track_restarts("abc", 8, start + minute));
+        assert!(!poller.track_restarts("abc", 9, start + 2 * minute));
+        assert!(poller.track_restarts("abc", 10, start + 3 * minute));
+        assert!(poller.track_restarts("abc", 10, start + 4 * minute));
+
+        // The first restart left the window.
+        let later = start + minute + RESTART_LOOP_WINDOW + Duration::from_secs(1);
+        assert!(!poller.track_restarts("abc", 10, later));
+        assert!(!poller.track_restarts("def", 0, later));
+    }
+}
@@ -0,0 +1,317 @@
+use crate::config::ControlConfig;
+use crate::fan_controller::FanMode;
+use crate::messages::Priority;
+use anyhow::{anyhow, Context, Result};
+use log::{debug, info, warn};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::{BufRead, BufReader, Write};
+use std::os::unix::fs::{chown, PermissionsExt};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::Sender;
+use std::thread;
+use std::time::Duration;
+
+/// Longest `notify` duration in seconds, a year. Leaving the duration out
+/// shows a message until it's cleared.
+const MAX_MESSAGE_DURATION: u64 = 365 * 24 * 3600;
+
+/// Highest of the brightness presets.
+const MAX_BRIGHTNESS_LEVEL: u8 = 4;
+
+/// Commands accepted on the control socket, one JSON object per line,
+/// e.g. `{"command":"wake"}` or
+/// `{"command":"notify","text":"Backup running","duration":30}`.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(tag = "command", rename_all = "snake_case")]
+pub enum ControlCommand {
+    Wake,
+    Notify {
+        text: String,
+        #[serde(default)]
+        priority: Priority,
+        /// Seconds to show the message, until cleared if missing.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        duration: Option<u64>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        id: Option<String>,
+    },
+    /// Removes the message with `id`, or all messages.
+    Clear {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        id: Option<String>,
+    },
+    /// Turns the display off until it is turned on again or woken.
+    Display {
+        on: bool,
+    },
+    /// Sets the active brightness to a preset from 0 to 4.
+    Brightness {
+        level: u8,
+    },
+    Fan {
+        mode: FanMode,
+    },
+    /// Ends the main loop. Sent on SIGTERM and SIGINT, never accepted from
+    /// clients.
+    #[serde(skip)]
+    Shutdown,
+}
+
+impl ControlCommand {
+    /// Checks the values the JSON types alone don't restrict.
+    fn validate(&self) -> Result<(), String> {
+        match self {
+            ControlCommand::Notify {
+                duration: Some(duration),
+                ..
+            } if *duration > MAX_MESSAGE_DURATION => Err(format!(
+                "duration {}s is too long, at most {}s, leave it out to show the message until cleared",
+                duration, MAX_MESSAGE_DURATION
+            )),
+            ControlCommand::Brightness { level } if *level > MAX_BRIGHTNESS_LEVEL => Err(format!(
+                "invalid brightness level {}, expected 0-{}",
+                level, MAX_BRIGHTNESS_LEVEL
+            )),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct ControlResponse {
+    pub ok: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Listens on a Unix socket and forwards parsed commands to the main loop.
+pub struct ControlServer {
+    path: PathBuf,
+}
+
+impl ControlServer {
+    pub fn start(config: &ControlConfig, sender: Sender<ControlCommand>) -> Result<Self> {
+        let path = config.socket.as_path();
+        if path.exists() {
+            if UnixStream::connect(path).is_ok() {
+                return Err(anyhow!(
+                    "control socket {} is in use, is another instance running?",
+                    path.display()
+                ));
+            }
+            debug!("Removing stale control socket {}", path.display());
+            fs::remove_file(path)?;
+        }
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let listener = UnixListener::bind(path)
+            .with_context(|| format!("failed to bind control socket {}", path.display()))?;
+        set_access(path, config)?;
+        info!(
+            "Listening for control commands on {} (mode {:o})",
+            path.display(),
+            config.socket_mode
+        );
+
+        thread::Builder::new()
+            .name("control".to_string())
+            .spawn(move || {
+                for stream in listener.incoming() {
+                    match stream {
+                        Ok(stream) => {
+                            if let Err(e) = handle_client(stream, &sender) {
+                                debug!("Control client error: {:#}", e);
+                            }
+                        }
+                        Err(e) => warn!("Control socket accept failed: {}", e),
+                    }
+                }
+            })?;
+
+        Ok(ControlServer {
+            path: path.to_path_buf(),
+        })
+    }
+}
+
+impl Drop for ControlServer {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+/// Applies the configured mode and group to the socket, which otherwise
+/// only the service user could connect to.
+fn set_access(path: &Path, config: &ControlConfig) -> Result<()> {
+    fs::set_permissions(path, fs::Permissions::from_mode(config.socket_mode))
+        .with_context(|| format!("failed to set the mode of {}", path.display()))?;
+    if let Some(group) = &config.socket_group {
+        let groups = fs::read_to_string("/etc/group").unwrap_or_default();
+        let gid = group_id(&groups, group)
+            .ok_or_else(|| anyhow!("unknown control socket group {}", group))?;
+        chown(path, None, Some(gid))
+            .with_context(|| format!("failed to hand {} to group {}", path.display(), group))?;
+    }
+    Ok(())
+}
+
+/// The id of the group `name` in /etc/group content, or `name` itself if
+/// it's numeric.
+fn group_id(groups: &str, name: &str) -> Option<u32> {
+    if let Ok(gid) = name.parse() {
+        return Some(gid);
+    }
+    // name:password:gid:members
+    groups
+        .lines()
+        .map(|line| line.split(':').collect::<Vec<_>>())
+        .find(|fields| fields.len() >= 3 && fields[0] == name)
+        .and_then(|fields| fields[2].parse().ok())
+}
+
+fn handle_client(stream: UnixStream, sender: &Sender<ControlCommand>) -> Result<()> {
+    stream.set_read_timeout(Some(Duration::from_secs(5)))?;
+    let mut writer = stream.try_clone()?;
+    let reader = BufReader::new(stream);
+
+    for line in reader.lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let response = dispatch(&line, sender);
+        serde_json::to_writer(&mut writer, &response)?;
+        writer.write_all(b"\n")?;
+    }
+    Ok(())
+}
+
+/// Parses and checks a JSON command and forwards it to the main loop.
+pub fn dispatch(json: &str, sender: &Sender<ControlCommand>) -> ControlResponse {
+    let command = serde_json::from_str::<ControlCommand>(json)
+        .map_err(|e| e.to_string())
+        .and_then(|command| command.validate().map(|()| command));
+    match command {
+        Ok(command) => {
+            debug!("Control command received: {:?}", command);
+            match sender.send(command) {
+                Ok(()) => ControlResponse {
+                    ok: true,
+                    error: None,
+                },
+                Err(_) => ControlResponse {
+                    ok: false,
+                    error: Some("monitor is shutting down".to_string()),
+                },
+            }
+        }
+        Err(e) => ControlResponse {
+            ok: false,
+            error: Some(format!("invalid command: {}", e)),
+        },
+    }
+}
+
+/// Sends a single command to a running monitor and waits for its reply.
+pub fn send(path: &Path, command: &ControlCommand) -> Result<()> {
+    let mut stream = UnixStream::connect(path).with_context(|| {
+        format!(
+            "failed to connect to {}, is rustberry-poe-monitor running?",
+            path.display()
+        )
+    })?;
+    stream.set_read_timeout(Some(Duration::from_secs(5)))?;
+
+    serde_json::to_writer(&mut stream, command)?;
+    stream.write_all(b"\n")?;
+
+    let mut line = String::new();
+    BufReader::new(&stream).read_line(&mut line)?;
+    let response: ControlResponse =
+        serde_json::from_str(&line).context("invalid response from monitor")?;
+    if response.ok {
+        Ok(())
+    } else {
+        Err(anyhow!(response.error.unwrap_or_default()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc;
+
+    fn error(json: &str) -> String {
+        let (sender, events) = mpsc::channel();
+        let response = dispatch(json, &sender);
+        assert!(!response.ok);
+        assert!(events.try_recv().is_err());
+        response.error.unwrap()
+    }
+
+    #[test]
+    fn dispatch_forwards_valid_commands() {
+        let (sender, events) = mpsc::channel();
+        let json = r#"{"command":"notify","text":"Backup running","duration":30}"#;
+        assert!(dispatch(json, &sender).ok);
+        assert!(dispatch(r#"{"command":"brightness","level":4}"#, &sender).ok);
+        let commands: Vec<ControlCommand> = events.try_iter().collect();
+        assert!(matches!(
+            commands[..],
+            [
+                ControlCommand::Notify {
+                    duration: Some(30),
+                    ..
+                },
+                ControlCommand::Brightness { level: 4 }
+            ]
+        ));
+    }
+
+    #[test]
+    fn dispatch_rejects_out_of_range_values() {
+        assert!(error(r#"{"command":"brightness","level":5}"#)
+            .contains("invalid brightness level 5, expected 0-4"));
+        let huge = format!(
+            r#"{{"command":"notify","text":"x","duration":{}}}"#,
+            u64::MAX
+        );
+        assert!(error(&huge).contains("too long"));
+        assert!(error(r#"{"command":"shutdown"}"#).contains("unknown variant"));
+        assert!(error("wake").contains("invalid command"));
+    }
+
+    #[test]
+    fn group_ids_by_name_or_number() {
+        let groups = "root:x:0:\ni2c:x:998:pi\nmonitor:x:1001:pi,backup\n";
+        assert_eq!(group_id(groups, "monitor"), Some(1001));
+        assert_eq!(group_id(groups, "1234"), Some(1234));
+        assert_eq!(group_id(groups, "nosuch"), None);
+    }
+
+    #[test]
+    fn socket_gets_the_configured_mode() {
+        let config = ControlConfig {
+            socket: std::env::temp_dir()
+                .join(format!("rustberry-control-{}.sock", std::process::id())),
+            socket_mode: 0o660,
+            ..ControlConfig::default()
+        };
+        let (sender, events) = mpsc::channel();
+        let server = ControlServer::start(&config, sender).unwrap();
+        let mode = fs::metadata(&config.socket).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o660);
+
+        send(&config.socket, &ControlCommand::Wake).unwrap();
+        assert!(matches!(events.recv().unwrap(), ControlCommand::Wake));
+        let error = send(&config.socket, &ControlCommand::Brightness { level: 9 });
+        assert!(format!("{:#}", error.unwrap_err()).contains("invalid brightness level 9"));
+
+        drop(server);
+        assert!(!config.socket.exists());
+    }
+}
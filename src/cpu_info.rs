@@ -0,0 +1,66 @@
+use log::debug;
+use std::fs;
+use std::path::Path;
+use sysinfo::System;
+
+const CPUFREQ_DIR: &str = "/sys/devices/system/cpu/cpufreq";
+
+/// A cpufreq policy, the group of cores that always share a frequency.
+pub struct CpuPolicy {
+    pub name: String,
+    pub cur_khz: Option<u64>,
+    pub max_khz: Option<u64>,
+    pub governor: Option<String>,
+}
+
+/// Load, frequency scaling and per-core usage for the CPU page.
+pub struct CpuInfo {
+    /// 1, 5 and 15 minute load averages.
+    pub load_average: [f64; 3],
+    pub policies: Vec<CpuPolicy>,
+    /// Usage of each core in percent, from the last `refresh_cpu_usage`.
+    pub core_usage: Vec<f32>,
+}
+
+impl CpuInfo {
+    pub fn read(sys: &System) -> Self {
+        let load = System::load_average();
+        CpuInfo {
+            load_average: [load.one, load.five, load.fifteen],
+            policies: read_policies(),
+            core_usage: sys.cpus().iter().map(|cpu| cpu.cpu_usage()).collect(),
+        }
+    }
+}
+
+fn read_policies() -> Vec<CpuPolicy> {
+    let entries = match fs::read_dir(CPUFREQ_DIR) {
+        Ok(entries) => entries,
+        Err(e) => {
+            debug!("No cpufreq policies in {}: {}", CPUFREQ_DIR, e);
+            return Vec::new();
+        }
+    };
+    let mut policies: Vec<CpuPolicy> = entries
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_name().to_string_lossy().starts_with("policy"))
+        .map(|entry| {
+            let dir = entry.path();
+            CpuPolicy {
+                name: entry.file_name().to_string_lossy().to_string(),
+                cur_khz: read_value(&dir, "scaling_cur_freq").and_then(|v| v.parse().ok()),
+                max_khz: read_value(&dir, "cpuinfo_max_freq").and_then(|v| v.parse().ok()),
+                governor: read_value(&dir, "scaling_governor"),
+            }
+        })
+        .collect();
+    // policy10 sorts after policy2.
+    policies.sort_by_key(|policy| policy.name["policy".len()..].parse::<u32>().unwrap_or(0));
+    policies
+}
+
+fn read_value(dir: &Path, name: &str) -> Option<String> {
+    fs::read_to_string(dir.join(name))
+        .ok()
+        .map(|value| value.trim().to_string())
+}
@@ -0,0 +1,245 @@
+use crate::config::Config;
+use crate::display::PoeDisplay;
+use crate::fan_controller::{FanController, I2C_BUS_PATH};
+use anyhow::{anyhow, Result};
+use linux_embedded_hal::i2cdev::core::I2CDevice;
+use linux_embedded_hal::i2cdev::linux::{LinuxI2CDevice, LinuxI2CError};
+use std::env;
+use std::fs::{self, OpenOptions};
+use std::io::{self, ErrorKind};
+use std::path::Path;
+use std::thread;
+use std::time::Duration;
+
+/// Where the PoE HAT (B) puts its OLED and the PCF8574 switching the fan.
+const DISPLAY_ADDRESS: u16 = 0x3C;
+const EXPANDER_ADDRESS: u16 = 0x20;
+const TEST_PATTERN_TIME: Duration = Duration::from_secs(3);
+const FAN_PULSE_TIME: Duration = Duration::from_secs(2);
+
+/// Runs the checks one by one and prints each result as it comes in, so
+/// the display and fan tests can be watched. Fails if any check failed.
+pub fn run(config: &Config) -> Result<()> {
+    let mut report = Report::default();
+    if config.control.socket.exists() {
+        println!("Note: the monitor seems to be running and may draw over the test pattern,");
+        println!("      stop it with `sudo systemctl stop rustberry-poe-monitor` first");
+        println!();
+    }
+
+    let devices = check_bus(&mut report);
+    let has = |address| {
+        devices
+            .as_ref()
+            .is_some_and(|found| found.contains(&address))
+    };
+    let display_found = has(DISPLAY_ADDRESS);
+    let expander_found = has(EXPANDER_ADDRESS);
+    if devices.is_some() {
+        let display = if display_found {
+            Ok(format!("SSD1306/SH1106 at {:#04x}", DISPLAY_ADDRESS))
+        } else {
+            Err(Failure::new(
+                format!("nothing at {:#04x}", DISPLAY_ADDRESS),
+                "Check that the HAT sits on all 40 pins of the header",
+            ))
+        };
+        report.check("Display", display);
+        let expander = if expander_found {
+            Ok(format!("PCF8574 at {:#04x}", EXPANDER_ADDRESS))
+        } else {
+            Err(Failure::new(
+                format!("nothing at {:#04x}", EXPANDER_ADDRESS),
+                "The fan needs the PCF8574 of the Waveshare PoE HAT (B), other HATs aren't supported",
+            ))
+        };
+        report.check("Fan expander", expander);
+    }
+
+    if display_found {
+        println!(
+            "Showing a test pattern for {}s, check the display...",
+            TEST_PATTERN_TIME.as_secs()
+        );
+        report.check("Test pattern", test_display(config));
+    } else {
+        report.skip("Test pattern", "no display found");
+    }
+    if expander_found {
+        println!(
+            "Switching the fan on for {}s, listen for it...",
+            FAN_PULSE_TIME.as_secs()
+        );
+        report.check("Fan pulse", pulse_fan(config));
+    } else {
+        report.skip("Fan pulse", "no fan expander found");
+    }
+
+    report.summary()
+}
+
+/// Checks that the bus exists and can be opened, then scans it. `None` if
+/// it can't be scanned.
+fn check_bus(report: &mut Report) -> Option<Vec<u16>> {
+    if !Path::new(I2C_BUS_PATH).exists() {
+        let others = other_buses();
+        let detail = if others.is_empty() {
+            format!("{} missing", I2C_BUS_PATH)
+        } else {
+            format!("{} missing, found {}", I2C_BUS_PATH, others.join(", "))
+        };
+        report.check(
+            "I2C bus",
+            Err(Failure::new(
+                detail,
+                "Enable I2C with `sudo raspi-config nonint do_i2c 0` and reboot",
+            )),
+        );
+        return None;
+    }
+    report.check("I2C bus", Ok(I2C_BUS_PATH.to_string()));
+
+    match OpenOptions::new().read(true).write(true).open(I2C_BUS_PATH) {
+        Ok(_) => report.check("I2C access", Ok("read and write".to_string())),
+        Err(e) if e.kind() == ErrorKind::PermissionDenied => {
+            let user = env::var("USER").unwrap_or_else(|_| "<user>".to_string());
+            let hint = format!(
+                "Add the user to the i2c group with `sudo usermod -aG i2c {}` and log in again",
+                user
+            );
+            report.check("I2C access", Err(Failure::new(e.to_string(), hint)));
+            return None;
+        }
+        Err(e) => {
+            let hint = "Check that nothing else holds the bus";
+            report.check("I2C access", Err(Failure::new(e.to_string(), hint)));
+            return None;
+        }
+    }
+
+    let devices = scan(I2C_BUS_PATH);
+    let listed: Vec<String> = devices.iter().map(|a| format!("{:#04x}", a)).collect();
+    if devices.is_empty() {
+        report.check(
+            "I2C scan",
+            Err(Failure::new(
+                "no device answered",
+                "Check that the HAT sits on all 40 pins of the header",
+            )),
+        );
+    } else {
+        report.check("I2C scan", Ok(format!("found {}", listed.join(", "))));
+    }
+    Some(devices)
+}
+
+fn other_buses() -> Vec<String> {
+    let mut buses: Vec<String> = fs::read_dir("/dev")
+        .map(|entries| {
+            entries
+                .filter_map(|entry| entry.ok())
+                .map(|entry| entry.file_name().to_string_lossy().to_string())
+                .filter(|name| name.starts_with("i2c-"))
+                .map(|name| format!("/dev/{}", name))
+                .collect()
+        })
+        .unwrap_or_default();
+    buses.sort();
+    buses
+}
+
+/// Addresses that answer a one byte read, like `i2cdetect -r`. Addresses
+/// claimed by a kernel driver count as present without being read.
+fn scan(path: &str) -> Vec<u16> {
+    (0x08..=0x77)
+        .filter(|address| match LinuxI2CDevice::new(path, *address) {
+            Ok(mut device) => device.read(&mut [0]).is_ok(),
+            Err(LinuxI2CError::Errno(errno)) => {
+                io::Error::from_raw_os_error(errno).kind() == ErrorKind::ResourceBusy
+            }
+            Err(LinuxI2CError::Io(_)) => false,
+        })
+        .collect()
+}
+
+fn test_display(config: &Config) -> Result<String, Failure> {
+    let hint = "Check `controller` and `height` in the [display] config";
+    let mut display =
+        PoeDisplay::new(&config.display).map_err(|e| Failure::new(e.to_string(), hint))?;
+    display
+        .show_test_pattern()
+        .map_err(|e| Failure::new(format!("{:?}", e), hint))?;
+    thread::sleep(TEST_PATTERN_TIME);
+    display
+        .display_off()
+        .map_err(|e| Failure::new(format!("{:?}", e), hint))?;
+    Ok("drawn".to_string())
+}
+
+fn pulse_fan(config: &Config) -> Result<String, Failure> {
+    let hint = "Check that the fan cable is plugged into the HAT";
+    let failed = |e: &dyn std::fmt::Display| Failure::new(e.to_string(), hint);
+    let mut fan =
+        FanController::new(config.fan.temp_on, config.fan.temp_off).map_err(|e| failed(&e))?;
+    fan.fan_on().map_err(|e| failed(&e))?;
+    thread::sleep(FAN_PULSE_TIME);
+    fan.fan_off().map_err(|e| failed(&e))?;
+    Ok(format!("switched p0 on for {}s", FAN_PULSE_TIME.as_secs()))
+}
+
+struct Failure {
+    detail: String,
+    hint: String,
+}
+
+impl Failure {
+    fn new(detail: impl Into<String>, hint: impl Into<String>) -> Self {
+        Failure {
+            detail: detail.into(),
+            hint: hint.into(),
+        }
+    }
+}
+
+#[derive(Default)]
+struct Report {
+    passed: usize,
+    failed: usize,
+    /// One per problem, in the order they were found.
+    hints: Vec<String>,
+}
+
+impl Report {
+    fn check(&mut self, name: &str, result: Result<String, Failure>) {
+        match result {
+            Ok(detail) => {
+                println!("PASS  {:<14}{}", name, detail);
+                self.passed += 1;
+            }
+            Err(failure) => {
+                println!("FAIL  {:<14}{}", name, failure.detail);
+                self.failed += 1;
+                if !self.hints.contains(&failure.hint) {
+                    self.hints.push(failure.hint);
+                }
+            }
+        }
+    }
+
+    fn skip(&self, name: &str, reason: &str) {
+        println!("SKIP  {:<14}{}", name, reason);
+    }
+
+    fn summary(&self) -> Result<()> {
+        println!();
+        if self.failed == 0 {
+            println!("All {} checks passed", self.passed);
+            return Ok(());
+        }
+        println!("To fix:");
+        for hint in &self.hints {
+            println!("  - {}", hint);
+        }
+        Err(anyhow!("{} check(s) failed", self.failed))
+    }
+}
@@ -1,27 +1,198 @@
-use crate::config::DisplayConfig as AppDisplayConfig;
-use crate::display_types::{Display, FONT_5X8, FONT_6X12, PCSENIOR8_STYLE, PROFONT12};
+use crate::checks::CheckResult;
+use crate::config::{
+    BrightnessSetting, DisplayConfig as AppDisplayConfig, LabelsConfig, Overflow, OverflowConfig,
+    ProcessSort, RamUsed,
+};
+use crate::containers::ContainerSummary;
+use crate::cpu_info::CpuInfo;
+use crate::display_types::{FONT_6X12, PCSENIOR8_STYLE, PROFONT12};
+use crate::fonts::Fonts;
+use crate::framebuffer::Framebuffer;
+use crate::icons::{self, Icon, Icons};
+use crate::layout::{self, Align, Slot};
+use crate::memory::MemoryInfo;
+use crate::pages;
+use crate::panel::{BrightnessLevel, Panel};
+use crate::processes::TopProcess;
+use crate::services::UnitStatus;
+use crate::system_info::SystemInfo;
+use crate::units::{Reading, Unit};
 use display_interface::DisplayError;
-use embedded_graphics::{pixelcolor::BinaryColor, prelude::*, text::Text};
+use embedded_graphics::{
+    pixelcolor::BinaryColor,
+    prelude::*,
+    primitives::{PrimitiveStyle, Rectangle},
+    text::Text,
+};
 use linux_embedded_hal::I2cdev;
 use log::{debug, info, warn};
+use std::path::Path;
+use std::time::{Duration, Instant};
 
-use ssd1306::mode::DisplayConfig;
-use ssd1306::prelude::Brightness;
-use ssd1306::{prelude::*, I2CDisplayInterface, Ssd1306};
+/// Interval between two contrast steps while fading.
+const FADE_STEP: Duration = Duration::from_millis(20);
+
+/// Which of the configured brightness levels the display should use.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BrightnessMode {
+    Active,
+    Idle,
+    Night,
+}
+
+/// Pre-formatted values shown on the overview screen.
+pub struct Overview<'a> {
+    pub hostname: &'a str,
+    pub ip_address: &'a str,
+    pub cpu_usage: &'a Reading,
+    pub temp: &'a Reading,
+    pub ram_usage: &'a Reading,
+    pub disk_usage: &'a Reading,
+    pub fan_running: bool,
+    pub alert_active: bool,
+    pub under_voltage: bool,
+}
+
+/// What to draw on the next update, one variant per page.
+pub enum Screen<'a> {
+    Overview(Overview<'a>),
+    Cpu(&'a CpuInfo),
+    System(&'a SystemInfo),
+    Processes(&'a [TopProcess], ProcessSort),
+    Services(&'a [UnitStatus]),
+    /// The summary and how long the page has been up, which selects the
+    /// containers shown.
+    Containers(&'a ContainerSummary, Duration),
+    Checks(&'a [CheckResult]),
+    /// The readings and the definition of used RAM.
+    Memory(&'a MemoryInfo, RamUsed),
+}
+
+/// A brightness change in progress.
+struct Fade {
+    from: BrightnessLevel,
+    target: BrightnessLevel,
+    started: Instant,
+    next_step: Instant,
+}
 
 pub struct PoeDisplay {
-    display: Display,
+    display: Panel,
+    framebuffer: Framebuffer,
+    inverted: bool,
+    active_brightness: BrightnessLevel,
+    idle_brightness: BrightnessLevel,
+    night_brightness: BrightnessLevel,
+    /// The level last written to the panel.
+    current_brightness: BrightnessLevel,
+    fade_duration: Duration,
+    fade: Option<Fade>,
+    overflow: OverflowConfig,
+    fonts: Fonts,
+    labels: LabelsConfig,
+    icons: Icons,
+    started: Instant,
+    frame: u32,
 }
 
 impl PoeDisplay {
     pub fn new(display_config: &AppDisplayConfig) -> Result<Self, Box<dyn std::error::Error>> {
         let i2c = I2cdev::new("/dev/i2c-1")?;
-        let display = initialize_display(i2c, display_config)?;
-        Ok(PoeDisplay { display })
+        let active_brightness = map_brightness_value(display_config.brightness);
+        let display = initialize_display(i2c, display_config, active_brightness)?;
+        let (width, height) = display.dimensions();
+        Ok(PoeDisplay {
+            display,
+            framebuffer: Framebuffer::new(width as u32, height as u32),
+            inverted: false,
+            active_brightness,
+            idle_brightness: map_brightness_value(display_config.idle_brightness),
+            night_brightness: map_brightness_value(display_config.night_brightness),
+            current_brightness: active_brightness,
+            fade_duration: Duration::from_millis(display_config.fade_duration_ms),
+            fade: None,
+            overflow: display_config.overflow,
+            fonts: Fonts::load(&display_config.fonts),
+            labels: display_config.labels.clone(),
+            icons: Icons::load(&display_config.icons),
+            started: Instant::now(),
+            frame: 0,
+        })
+    }
+
+    /// Starts fading to the level configured for `mode`, continued by
+    /// `advance_fade`. Does nothing if the display is already at or fading
+    /// to that level.
+    pub fn set_brightness_mode(&mut self, mode: BrightnessMode) -> Result<(), DisplayError> {
+        let target = match mode {
+            BrightnessMode::Active => self.active_brightness,
+            BrightnessMode::Idle => self.idle_brightness,
+            BrightnessMode::Night => self.night_brightness,
+        };
+        let fading_to = self.fade.as_ref().map(|fade| fade.target);
+        if target == fading_to.unwrap_or(self.current_brightness) {
+            return Ok(());
+        }
+        debug!("Fading to {:?} brightness: {:?}", mode, target);
+        if self.fade_duration < FADE_STEP * 2 {
+            return self.set_brightness_now(target);
+        }
+        let now = Instant::now();
+        self.fade = Some(Fade {
+            from: self.current_brightness,
+            target,
+            started: now,
+            next_step: now,
+        });
+        self.advance_fade(now)
+    }
+
+    /// Writes the next step of a running fade once it's due. Called while
+    /// the main loop waits, so fading never holds up a refresh.
+    pub fn advance_fade(&mut self, now: Instant) -> Result<(), DisplayError> {
+        let Some(fade) = self.fade.as_mut() else {
+            return Ok(());
+        };
+        if now < fade.next_step {
+            return Ok(());
+        }
+        let elapsed = now.duration_since(fade.started);
+        if elapsed >= self.fade_duration {
+            let target = fade.target;
+            return self.set_brightness_now(target);
+        }
+
+        // Raise the precharge before brightening and lower it only once the
+        // fade has finished, so the panel never flickers darker mid-fade.
+        let delta = fade.target.contrast as i64 - fade.from.contrast as i64;
+        let contrast = fade.from.contrast as i64
+            + delta * elapsed.as_millis() as i64 / self.fade_duration.as_millis() as i64;
+        let level = BrightnessLevel {
+            precharge: fade.from.precharge.max(fade.target.precharge),
+            contrast: contrast as u8,
+        };
+        fade.next_step = now + FADE_STEP;
+        self.display.set_brightness(level)?;
+        self.current_brightness = level;
+        Ok(())
     }
 
-    pub fn set_brightness(&mut self, brightness: Brightness) -> Result<(), DisplayError> {
-        self.display.set_brightness(brightness)
+    /// Writes `level` right away, ending any fade.
+    fn set_brightness_now(&mut self, level: BrightnessLevel) -> Result<(), DisplayError> {
+        self.fade = None;
+        self.display.set_brightness(level)?;
+        self.current_brightness = level;
+        Ok(())
+    }
+
+    /// When `advance_fade` has the next step to write, `None` unless fading.
+    pub fn next_fade_step(&self) -> Option<Instant> {
+        self.fade.as_ref().map(|fade| fade.next_step)
+    }
+
+    /// Replaces the configured active brightness until the next restart.
+    pub fn set_active_brightness(&mut self, setting: BrightnessSetting) {
+        self.active_brightness = map_brightness_value(setting);
     }
 
     pub fn display_off(&mut self) -> Result<(), DisplayError> {
@@ -34,94 +205,572 @@ impl PoeDisplay {
         self.display.set_display_on(true)
     }
 
+    /// The frame currently shown on the panel.
+    pub fn framebuffer(&self) -> &Framebuffer {
+        &self.framebuffer
+    }
+
+    /// Shows the following frames in inverse video.
+    pub fn set_inverted(&mut self, inverted: bool) {
+        if inverted != self.inverted {
+            debug!("Inverse video {}", if inverted { "ON" } else { "OFF" });
+            self.inverted = inverted;
+        }
+    }
+
+    /// Draws `screen` with the pushed `message`, if any, which the overview
+    /// shows in place of the IP address and other pages in a bottom bar.
     pub fn update(
         &mut self,
-        ip_address: &str,
-        cpu_usage: String,
-        temp: String,
-        ram_usage: String,
-        disk_usage: &str,
+        screen: &Screen,
+        message: Option<&str>,
         offset: Point,
     ) -> Result<(), DisplayError> {
-        let disp = &mut self.display;
+        let elapsed_ms = self.started.elapsed().as_millis() as u64;
+        let scroll = (elapsed_ms * self.overflow.marquee_speed as u64 / 1000) as u32;
+        self.frame = self.frame.wrapping_add(1);
+        let style = ScreenStyle {
+            fonts: &self.fonts,
+            labels: &self.labels,
+            overflow: &self.overflow,
+            icons: &self.icons,
+            scroll,
+            fan_frame: match screen {
+                Screen::Overview(overview) => overview.fan_running.then_some(self.frame),
+                _ => None,
+            },
+        };
+        let framebuffer = &mut self.framebuffer;
+        let Ok(()) = match screen {
+            Screen::Overview(overview) => {
+                draw_overview(framebuffer, overview, message, &style, offset)
+            }
+            Screen::Cpu(info) => pages::draw_cpu(framebuffer, info, &style, offset),
+            Screen::System(info) => pages::draw_system(framebuffer, info, &style, offset),
+            Screen::Processes(processes, sort) => {
+                pages::draw_processes(framebuffer, processes, *sort, &style, offset)
+            }
+            Screen::Services(statuses) => {
+                pages::draw_services(framebuffer, statuses, &style, offset)
+            }
+            Screen::Containers(summary, shown_for) => {
+                pages::draw_containers(framebuffer, summary, *shown_for, &style, offset)
+            }
+            Screen::Checks(results) => pages::draw_checks(framebuffer, results, &style, offset),
+            Screen::Memory(info, ram_used) => {
+                pages::draw_memory(framebuffer, info, *ram_used, &style, offset)
+            }
+        };
+        let Ok(()) = match (screen, message) {
+            (Screen::Overview(_), _) | (_, None) => Ok(()),
+            (_, Some(message)) => draw_message_banner(framebuffer, message, &style, offset),
+        };
+        self.flush()
+    }
+
+    /// Shows the startup screen with the version and, if configured, the
+    /// logo. A logo that can't be loaded is reported and left out.
+    pub fn show_splash(&mut self, logo: Option<&Path>) -> Result<(), DisplayError> {
+        let logo = logo.and_then(|path| match Icon::load(path) {
+            Ok(logo) => Some(logo),
+            Err(e) => {
+                warn!("Failed to load splash logo: {:#}", e);
+                None
+            }
+        });
+        let version = env!("CARGO_PKG_VERSION");
+        let Ok(()) = draw_splash(&mut self.framebuffer, logo.as_ref(), &self.fonts, version);
+        self.flush()
+    }
+
+    /// Shows a message over the whole screen at full brightness, e.g. the
+    /// error that stopped the monitor. The brightness is set without fading,
+    /// the monitor may exit before a fade would get anywhere.
+    pub fn show_message(&mut self, title: &str, message: &str) -> Result<(), DisplayError> {
+        self.inverted = false;
+        self.display_on()?;
+        self.set_brightness_now(self.active_brightness)?;
+        let Ok(()) = draw_message(&mut self.framebuffer, title, message, &self.fonts);
+        self.flush()
+    }
+
+    /// Shows a pattern that makes missing rows, columns and a wrong height
+    /// or rotation easy to spot, at full brightness.
+    pub fn show_test_pattern(&mut self) -> Result<(), DisplayError> {
+        self.inverted = false;
+        self.display_on()?;
+        self.set_brightness_now(self.active_brightness)?;
+        let Ok(()) = draw_test_pattern(&mut self.framebuffer, &self.fonts);
+        self.flush()
+    }
+
+    /// Copies the framebuffer to the panel.
+    fn flush(&mut self) -> Result<(), DisplayError> {
+        if self.inverted {
+            self.framebuffer.invert();
+        }
+        self.display.flush(&self.framebuffer)
+    }
+}
+
+/// Everything about the screens' look that comes from the config.
+pub struct ScreenStyle<'a> {
+    pub fonts: &'a Fonts,
+    pub labels: &'a LabelsConfig,
+    pub overflow: &'a OverflowConfig,
+    pub icons: &'a Icons,
+    /// Marquee position in pixels.
+    pub scroll: u32,
+    /// Animation frame of the fan icon, `None` while the fan stands still.
+    pub fan_frame: Option<u32>,
+}
 
-        let y_offset = 7;
-        let display_width = 128;
-        let char_width: i32 = 8;
+/// Draws IP, CPU, RAM, temperature and disk. Panels with 64 rows also get
+/// the hostname above and the fan state below.
+fn draw_overview<D>(
+    disp: &mut D,
+    overview: &Overview,
+    message: Option<&str>,
+    style: &ScreenStyle,
+    offset: Point,
+) -> Result<(), D::Error>
+where
+    D: DrawTarget<Color = BinaryColor>,
+{
+    let tall = disp.bounding_box().size.height >= 64;
+    let y_offset = if tall { 21 } else { 7 };
+    let display_width = 128;
 
-        let x_margin = Point::new(2, 0);
+    let Overview {
+        hostname,
+        ip_address,
+        cpu_usage,
+        temp,
+        ram_usage,
+        disk_usage,
+        fan_running,
+        alert_active,
+        under_voltage,
+    } = *overview;
+    let ScreenStyle {
+        fonts,
+        labels,
+        overflow,
+        icons,
+        scroll,
+        ..
+    } = *style;
 
-        disp.clear(BinaryColor::Off)?;
+    let slot =
+        |left, right, baseline, align| Slot::new(left, right, baseline, align).translate(offset);
 
-        let ip_width = ip_address.len() as i32 * char_width;
-        let ip_x_position = (display_width - ip_width) / 2;
-        let ip_pos = Point::new(ip_x_position, y_offset) + offset;
-        Text::new(ip_address, ip_pos, PCSENIOR8_STYLE).draw(disp)?;
+    disp.clear(BinaryColor::Off)?;
 
-        let cpu_width = cpu_usage.len() as i32 * char_width;
-        let cpu_pos = Point::new(34 - cpu_width, 12 + y_offset) + offset;
-        let next = Text::new(&cpu_usage, cpu_pos, PCSENIOR8_STYLE).draw(disp)?;
-        let next = Text::new("%", next, FONT_6X12).draw(disp)?;
-        Text::new("CPU", next + x_margin, FONT_5X8).draw(disp)?;
+    if tall {
+        let hostname_slot = slot(0, display_width, 7, Align::Center);
+        layout::draw_text(
+            disp,
+            hostname,
+            hostname_slot,
+            fonts.value,
+            &fonts.narrower_values,
+            overflow.hostname,
+            scroll,
+        )?;
+    }
+
+    // The IP row has an optional label in front, and at the end the warning
+    // icon while an alert is raised or the supply is under-voltage. Messages
+    // take the place of both the label and the address.
+    let mut ip_slot = slot(0, display_width, y_offset, Align::Center);
+    if !labels.ip.is_empty() && message.is_none() {
+        let prefix_slot = slot(0, display_width, y_offset, Align::Left);
+        ip_slot.left = draw_label(disp, &labels.ip, prefix_slot, style)?.x + 2;
+    }
+    if alert_active || under_voltage {
+        let icon_x = display_width - icons.width("warning").unwrap_or(0);
+        ip_slot.right = icon_x + offset.x - 2;
+        let icon_slot = slot(icon_x, display_width, y_offset, Align::Left);
+        draw_label(disp, ":warning:", icon_slot, style)?;
+    }
+    let (text, text_overflow) = match message {
+        Some(message) => (message, overflow.message),
+        None => (ip_address, overflow.ip),
+    };
+    layout::draw_text(
+        disp,
+        text,
+        ip_slot,
+        fonts.value,
+        &fonts.narrower_values,
+        text_overflow,
+        scroll,
+    )?;
+
+    // Left column: values end at x 34 and are followed by their unit and
+    // label. The right column starts after the longer of the two labels.
+    let label_width = label_width(&labels.cpu, style).max(label_width(&labels.ram, style));
+    let right_column = (42 + label_width + 2).min(70);
+
+    let left = |row| slot(0, 34, row + y_offset, Align::Right);
+    let right = |row| slot(right_column, 99, row + y_offset, Align::Right);
+    let left_end = right_column - 2 + offset.x;
+    let right_end = display_width + offset.x;
+    draw_metric(
+        disp,
+        cpu_usage,
+        &labels.cpu,
+        left(12),
+        left_end,
+        overflow.cpu,
+        style,
+    )?;
+    draw_metric(
+        disp,
+        ram_usage,
+        &labels.ram,
+        left(23),
+        left_end,
+        overflow.ram,
+        style,
+    )?;
+    draw_metric(
+        disp,
+        temp,
+        &labels.temp,
+        right(12),
+        right_end,
+        overflow.temp,
+        style,
+    )?;
+    draw_metric(
+        disp,
+        disk_usage,
+        &labels.disk,
+        right(23),
+        right_end,
+        overflow.disk,
+        style,
+    )?;
+
+    if tall {
+        let fan = if fan_running {
+            &labels.fan_on
+        } else {
+            &labels.fan_off
+        };
+        let fan_slot = slot(0, display_width, 60, Align::Center);
+        draw_label(disp, fan, fan_slot, style)?;
+    }
+
+    Ok(())
+}
 
-        let ram_width = ram_usage.len() as i32 * char_width;
-        let ram_pos = Point::new(34 - ram_width, 23 + y_offset) + offset;
-        let next = Text::new(&ram_usage, ram_pos, PCSENIOR8_STYLE).draw(disp)?;
-        let next = Text::new("%", next, FONT_6X12).draw(disp)?;
-        Text::new("RAM", next + x_margin, FONT_5X8).draw(disp)?;
+/// An outline along the edges and a checkerboard on the left half, with
+/// `TEST` and the panel size on the right. The top left square is lit, so
+/// the orientation shows.
+fn draw_test_pattern<D>(disp: &mut D, fonts: &Fonts) -> Result<(), D::Error>
+where
+    D: DrawTarget<Color = BinaryColor>,
+{
+    const SQUARE: u32 = 8;
+    let size = disp.bounding_box().size;
+    disp.clear(BinaryColor::Off)?;
 
-        let temp_width = temp.len() as i32 * char_width;
-        let temp_pos = Point::new(99 - temp_width, 12 + y_offset) + offset;
-        let next = Text::new(&temp, temp_pos, PCSENIOR8_STYLE).draw(disp)?;
-        let next = Text::new("°", next + Point::new(0, 3), PROFONT12).draw(disp)?;
-        Text::new("C", next - Point::new(0, 2), PCSENIOR8_STYLE).draw(disp)?;
+    let fill = PrimitiveStyle::with_fill(BinaryColor::On);
+    for row in 0..size.height.div_ceil(SQUARE) {
+        for column in (row % 2..size.width / 2 / SQUARE).step_by(2) {
+            let top_left = Point::new((column * SQUARE) as i32, (row * SQUARE) as i32);
+            Rectangle::new(top_left, Size::new(SQUARE, SQUARE))
+                .into_styled(fill)
+                .draw(disp)?;
+        }
+    }
+    Rectangle::new(Point::zero(), size)
+        .into_styled(PrimitiveStyle::with_stroke(BinaryColor::On, 1))
+        .draw(disp)?;
+
+    let (width, half) = (size.width as i32, size.width as i32 / 2);
+    let font = fonts.label;
+    let line_height = font.character_size.height as i32;
+    let middle = size.height as i32 / 2;
+    let dimensions = format!("{}x{}", size.width, size.height);
+    for (text, baseline) in [
+        ("TEST", middle - 1),
+        (dimensions.as_str(), middle + line_height - 1),
+    ] {
+        let slot = Slot::new(half, width, baseline, Align::Center);
+        layout::draw_text(disp, text, slot, font, &[], Overflow::Truncate, 0)?;
+    }
+    Ok(())
+}
 
-        let disk_width = disk_usage.len() as i32 * char_width;
-        let disk_pos = Point::new(99 - disk_width, 23 + y_offset) + offset;
-        let next = Text::new(disk_usage, disk_pos, PCSENIOR8_STYLE).draw(disp)?;
-        let next = Text::new("%", next, FONT_6X12).draw(disp)?;
-        Text::new("DISK", next + x_margin, FONT_5X8).draw(disp)?;
+/// Draws the name and version, next to the logo if there is one and
+/// centered otherwise.
+fn draw_splash<D>(
+    disp: &mut D,
+    logo: Option<&Icon>,
+    fonts: &Fonts,
+    version: &str,
+) -> Result<(), D::Error>
+where
+    D: DrawTarget<Color = BinaryColor>,
+{
+    let size = disp.bounding_box().size;
+    let (width, height) = (size.width as i32, size.height as i32);
+    disp.clear(BinaryColor::Off)?;
 
-        disp.flush()
+    let mut left = 0;
+    if let Some(logo) = logo {
+        let logo_size = logo.size();
+        let top = (height - logo_size.height as i32) / 2;
+        logo.draw(disp, Point::new(0, top.max(0)))?;
+        left = logo_size.width as i32 + 4;
     }
+
+    // Three lines, 10 pixels apart, centered vertically.
+    let top = (height - 28) / 2;
+    let line = |index| Slot::new(left, width, top + 7 + index * 10, Align::Center);
+    layout::draw_text(
+        disp,
+        "RustBerry",
+        line(0),
+        fonts.value,
+        &fonts.narrower_values,
+        Overflow::Shrink,
+        0,
+    )?;
+    layout::draw_text(
+        disp,
+        "PoE Monitor",
+        line(1),
+        fonts.value,
+        &fonts.narrower_values,
+        Overflow::Shrink,
+        0,
+    )?;
+    let version = format!("v{}", version);
+    layout::draw_text(
+        disp,
+        &version,
+        line(2),
+        fonts.label,
+        &[],
+        Overflow::Truncate,
+        0,
+    )?;
+    Ok(())
+}
+
+/// Draws a pushed message in a bar along the bottom edge, over whatever the
+/// page drew there.
+fn draw_message_banner<D>(
+    disp: &mut D,
+    message: &str,
+    style: &ScreenStyle,
+    offset: Point,
+) -> Result<(), D::Error>
+where
+    D: DrawTarget<Color = BinaryColor>,
+{
+    let size = disp.bounding_box().size;
+    let font = style.fonts.label;
+    let top = size.height as i32 - font.character_size.height as i32 - 1;
+    let bar = Size::new(size.width, size.height - top as u32);
+    Rectangle::new(Point::new(0, top) + offset, bar)
+        .into_styled(PrimitiveStyle::with_fill(BinaryColor::Off))
+        .draw(disp)?;
+    Rectangle::new(Point::new(0, top) + offset, Size::new(size.width, 1))
+        .into_styled(PrimitiveStyle::with_fill(BinaryColor::On))
+        .draw(disp)?;
+    let baseline = top + 1 + font.baseline as i32;
+    let slot = Slot::new(0, size.width as i32, baseline, Align::Center).translate(offset);
+    layout::draw_text(
+        disp,
+        message,
+        slot,
+        font,
+        &[],
+        style.overflow.message,
+        style.scroll,
+    )?;
+    Ok(())
 }
 
-fn map_brightness_value(value: u8) -> Brightness {
-    match value {
-        0 => Brightness::DIMMEST,
-        1 => Brightness::DIM,
-        2 => Brightness::NORMAL,
-        3 => Brightness::BRIGHT,
-        4 => Brightness::BRIGHTEST,
-        _ => {
+/// Draws `title` on the first line and `message` wrapped below it. What
+/// doesn't fit ends in an ellipsis.
+fn draw_message<D>(disp: &mut D, title: &str, message: &str, fonts: &Fonts) -> Result<(), D::Error>
+where
+    D: DrawTarget<Color = BinaryColor>,
+{
+    let size = disp.bounding_box().size;
+    let (width, height) = (size.width as i32, size.height as i32);
+    disp.clear(BinaryColor::Off)?;
+
+    let title_slot = Slot::new(0, width, 7, Align::Center);
+    layout::draw_text(
+        disp,
+        title,
+        title_slot,
+        fonts.value,
+        &fonts.narrower_values,
+        Overflow::Shrink,
+        0,
+    )?;
+
+    let font = fonts.label;
+    let line_height = font.character_size.height as i32;
+    let top = 8;
+    let max_lines = ((height - top) / line_height).max(1) as usize;
+    let mut lines = layout::wrap_text(message, font, width);
+    if lines.len() > max_lines {
+        // Joined again, the last line gets truncated with an ellipsis.
+        let rest = lines.split_off(max_lines - 1).join(" ");
+        lines.push(rest);
+    }
+    for (index, line) in lines.iter().enumerate() {
+        let baseline = top + index as i32 * line_height + font.baseline as i32;
+        let slot = Slot::new(0, width, baseline, Align::Left);
+        layout::draw_text(disp, line, slot, font, &[], Overflow::Truncate, 0)?;
+    }
+    Ok(())
+}
+
+/// Draws a value right-aligned in `slot`, followed by its unit and a label
+/// that may extend up to `label_right`.
+fn draw_metric<D>(
+    disp: &mut D,
+    reading: &Reading,
+    label: &str,
+    slot: Slot,
+    label_right: i32,
+    overflow: Overflow,
+    style: &ScreenStyle,
+) -> Result<(), D::Error>
+where
+    D: DrawTarget<Color = BinaryColor>,
+{
+    // Values without a unit symbol may use the space of the "%".
+    let slot = if reading.unit == Unit::None {
+        Slot {
+            right: slot.right + 6,
+            ..slot
+        }
+    } else {
+        slot
+    };
+    let fonts = style.fonts;
+    let next = layout::draw_text(
+        disp,
+        &reading.value,
+        slot,
+        fonts.value,
+        &fonts.narrower_values,
+        overflow,
+        style.scroll,
+    )?;
+    let next = draw_unit(disp, reading.unit, next)?;
+    let label_slot = Slot::new(next.x + 2, label_right, next.y, Align::Left);
+    draw_label(disp, label, label_slot, style)?;
+    Ok(())
+}
+
+/// Draws a label in the label font, or as an icon if written as `:name:`.
+fn draw_label<D>(
+    disp: &mut D,
+    label: &str,
+    slot: Slot,
+    style: &ScreenStyle,
+) -> Result<Point, D::Error>
+where
+    D: DrawTarget<Color = BinaryColor>,
+{
+    if let Some(name) = icons::icon_name(label) {
+        if let Some(width) = style.icons.width(name) {
+            let position = Point::new(slot.start_for(width), slot.baseline);
+            if let Some(next) = style.icons.draw(disp, name, position, style.fan_frame)? {
+                return Ok(next);
+            }
+        }
+    }
+    let font = style.fonts.label;
+    layout::draw_text(disp, label, slot, font, &[], Overflow::Truncate, 0)
+}
+
+fn label_width(label: &str, style: &ScreenStyle) -> i32 {
+    icons::icon_name(label)
+        .and_then(|name| style.icons.width(name))
+        .unwrap_or_else(|| layout::text_width(label, style.fonts.label))
+}
+
+/// Draws the unit symbol of a value at `position`, the end of the value.
+fn draw_unit<D>(disp: &mut D, unit: Unit, position: Point) -> Result<Point, D::Error>
+where
+    D: DrawTarget<Color = BinaryColor>,
+{
+    match unit {
+        Unit::Percent => Text::new("%", position, FONT_6X12).draw(disp),
+        Unit::Celsius | Unit::Fahrenheit => {
+            let letter = if unit == Unit::Celsius { "C" } else { "F" };
+            let next = Text::new("°", position + Point::new(0, 3), PROFONT12).draw(disp)?;
+            Text::new(letter, next - Point::new(0, 2), PCSENIOR8_STYLE).draw(disp)
+        }
+        Unit::None => Ok(position),
+    }
+}
+
+/// Maps the 0-4 presets onto the same values as the `ssd1306::Brightness`
+/// constants, and passes custom contrast/precharge pairs through.
+fn map_brightness_value(setting: BrightnessSetting) -> BrightnessLevel {
+    let (precharge, contrast) = match setting {
+        BrightnessSetting::Level(0) => (0x1, 0x00),
+        BrightnessSetting::Level(1) => (0x2, 0x2F),
+        BrightnessSetting::Level(2) => (0x2, 0x5F),
+        BrightnessSetting::Level(3) => (0x2, 0x9F),
+        BrightnessSetting::Level(4) => (0x2, 0xFF),
+        BrightnessSetting::Level(value) => {
             warn!(
                 "Invalid brightness value {} encountered, defaulting to DIMMEST",
                 value
             );
-            Brightness::DIMMEST
+            (0x1, 0x00)
         }
+        BrightnessSetting::Custom {
+            contrast,
+            precharge,
+        } => {
+            let precharge = precharge.unwrap_or(0x2);
+            if !(1..=15).contains(&precharge) {
+                warn!(
+                    "Invalid precharge value {} encountered, clamping to 1-15",
+                    precharge
+                );
+            }
+            (precharge.clamp(1, 15), contrast)
+        }
+    };
+    BrightnessLevel {
+        precharge,
+        contrast,
     }
 }
 
 fn initialize_display(
     i2c: I2cdev,
     display_config: &AppDisplayConfig,
-) -> Result<Display, Box<dyn std::error::Error>> {
-    let interface = I2CDisplayInterface::new(i2c);
-    let mut disp = Ssd1306::new(interface, DisplaySize128x32, DisplayRotation::Rotate0)
-        .into_buffered_graphics_mode();
+    brightness: BrightnessLevel,
+) -> Result<Panel, Box<dyn std::error::Error>> {
+    let mut disp = Panel::new(i2c, display_config)?;
 
     disp.init()
         .map_err(|e| format!("Display initialization error: {:?}", e))?;
 
-    let initial_brightness = map_brightness_value(display_config.brightness);
-
-    disp.set_brightness(initial_brightness)
+    disp.set_brightness(brightness)
         .map_err(|e| format!("Failed to set initial brightness: {:?}", e))?;
 
     info!(
-        "Display initialized with brightness: {:?}",
-        initial_brightness
+        "Display initialized: {:?} 128x{}, rotation {}, brightness: {:?}",
+        display_config.controller, display_config.height, display_config.rotation, brightness
     );
 
     Ok(disp)
@@ -1,3 +1,4 @@
+use crate::sh1106::Sh1106;
 use embedded_graphics::{
     image::ImageRaw,
     mono_font::{
@@ -11,9 +12,14 @@ use linux_embedded_hal::I2cdev;
 use profont::PROFONT_12_POINT;
 use ssd1306::{mode::BufferedGraphicsMode, prelude::*, Ssd1306};
 
-pub type Display =
+pub type Ssd1306Display128x32 =
     Ssd1306<I2CInterface<I2cdev>, DisplaySize128x32, BufferedGraphicsMode<DisplaySize128x32>>;
 
+pub type Ssd1306Display128x64 =
+    Ssd1306<I2CInterface<I2cdev>, DisplaySize128x64, BufferedGraphicsMode<DisplaySize128x64>>;
+
+pub type Sh1106Display = Sh1106<I2CInterface<I2cdev>>;
+
 pub const PROFONT12: MonoTextStyle<'_, BinaryColor> = MonoTextStyleBuilder::new()
     .font(&PROFONT_12_POINT)
     .text_color(BinaryColor::On)
@@ -24,11 +30,6 @@ pub const FONT_6X12: MonoTextStyle<'_, BinaryColor> = MonoTextStyleBuilder::new(
     .text_color(BinaryColor::On)
     .build();
 
-pub const FONT_5X8: MonoTextStyle<'_, BinaryColor> = MonoTextStyleBuilder::new()
-    .font(&ascii::FONT_5X8)
-    .text_color(BinaryColor::On)
-    .build();
-
 pub const GLYPH_MAPPING: StrGlyphMapping = StrGlyphMapping::new(" !\"#$%&'()*+,-./0123456789:;<=>?@ABCDEFGHIJKLMNOPQRSTUVWXYZ[\\]^_`abcdefghijklmnopqrstuvwxyz{|}~°", '?' as usize - ' ' as usize);
 
 pub const PCSENIOR8: MonoFont = MonoFont {
@@ -2,9 +2,22 @@ use anyhow::{anyhow, Result};
 use linux_embedded_hal::I2cdev;
 use log::debug;
 use pcf857x::OutputPin;
-use pcf857x::{Pcf8574, SlaveAddr};
+use pcf857x::{Pcf8574, PinFlag, SlaveAddr};
+use serde::{Deserialize, Serialize};
 
-const I2C_BUS_PATH: &str = "/dev/i2c-1";
+pub const I2C_BUS_PATH: &str = "/dev/i2c-1";
+
+/// Set from the dashboard or the control socket, `Auto` after a restart.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FanMode {
+    /// Switch on `temp_on` and off at `temp_off`.
+    #[default]
+    Auto,
+    On,
+    /// Stays off below `safety_temp`.
+    Off,
+}
 
 pub struct FanController {
     expander: Pcf8574<I2cdev>,
@@ -39,7 +52,10 @@ impl FanController {
     pub fn fan_on(&mut self) -> Result<(), Box<dyn std::error::Error>> {
         debug!("Sending fan on signal [p0: low]");
         let mut parts = self.expander.split();
-        parts.p0.set_low().unwrap();
+        parts
+            .p0
+            .set_low()
+            .map_err(|e| format!("Failed to switch the fan on: {:?}", e))?;
         self.is_running = true;
         Ok(())
     }
@@ -47,8 +63,31 @@ impl FanController {
     pub fn fan_off(&mut self) -> Result<(), Box<dyn std::error::Error>> {
         debug!("Sending fan off signal [p0: high]");
         let mut parts = self.expander.split();
-        parts.p0.set_high().unwrap();
+        parts
+            .p0
+            .set_high()
+            .map_err(|e| format!("Failed to switch the fan off: {:?}", e))?;
         self.is_running = false;
         Ok(())
     }
+
+    /// Reads one of the free expander pins (p1-p7) as an input. The pins are
+    /// quasi-bidirectional, so a button wired to ground reads as low.
+    pub fn is_pin_low(&mut self, pin: u8) -> Result<bool> {
+        let flag = match pin {
+            1 => PinFlag::P1,
+            2 => PinFlag::P2,
+            3 => PinFlag::P3,
+            4 => PinFlag::P4,
+            5 => PinFlag::P5,
+            6 => PinFlag::P6,
+            7 => PinFlag::P7,
+            _ => return Err(anyhow!("Invalid input pin p{}, expected p1-p7", pin)),
+        };
+        let bits = self
+            .expander
+            .get(flag)
+            .map_err(|e| anyhow!("Failed to read pin p{}: {:?}", pin, e))?;
+        Ok(bits & (1 << pin) == 0)
+    }
 }
@@ -0,0 +1,753 @@
+use crate::config::FontsConfig;
+use crate::display_types::PCSENIOR8;
+use anyhow::{anyhow, bail, Context, Result};
+use embedded_graphics::{
+    image::ImageRaw,
+    mono_font::{ascii, mapping::GlyphMapping, DecorationDimensions, MonoFont},
+    prelude::*,
+};
+use log::{error, info};
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+/// Glyphs per row in the generated font image.
+const GLYPHS_PER_ROW: u32 = 16;
+/// Largest glyph size and offset accepted, far more than fits on the panel.
+const MAX_GLYPH_SIZE: i32 = 64;
+
+const PCF_MAGIC: &[u8; 4] = b"\x01fcp";
+const PCF_METRICS: u32 = 1 << 2;
+const PCF_BITMAPS: u32 = 1 << 3;
+const PCF_BDF_ENCODINGS: u32 = 1 << 5;
+const PCF_COMPRESSED_METRICS: u32 = 0x100;
+
+/// Fonts used on the overview screen.
+pub struct Fonts {
+    pub value: &'static MonoFont<'static>,
+    /// Narrower fonts tried in order when a value is set to shrink.
+    pub narrower_values: Vec<&'static MonoFont<'static>>,
+    pub label: &'static MonoFont<'static>,
+}
+
+impl Fonts {
+    /// Loads the configured font files. A file that can't be used is
+    /// reported and replaced by the built-in font.
+    pub fn load(config: &FontsConfig) -> Self {
+        let mut fonts = Fonts::default();
+        if let Some(path) = &config.value {
+            if let Some(font) = load_or_report(path) {
+                let width = font.character_size.width;
+                fonts.narrower_values.insert(0, fonts.value);
+                fonts
+                    .narrower_values
+                    .retain(|f| f.character_size.width < width);
+                fonts.value = font;
+            }
+        }
+        if let Some(path) = &config.label {
+            if let Some(font) = load_or_report(path) {
+                fonts.label = font;
+            }
+        }
+        fonts
+    }
+}
+
+impl Default for Fonts {
+    fn default() -> Self {
+        Fonts {
+            value: &PCSENIOR8,
+            narrower_values: vec![&ascii::FONT_6X10, &ascii::FONT_5X8],
+            label: &ascii::FONT_5X8,
+        }
+    }
+}
+
+fn load_or_report(path: &Path) -> Option<&'static MonoFont<'static>> {
+    match load_font(path) {
+        Ok(font) => {
+            info!(
+                "Loaded font {} ({}x{})",
+                path.display(),
+                font.character_size.width,
+                font.character_size.height
+            );
+            // Fonts are loaded once at startup and live as long as the process.
+            Some(Box::leak(Box::new(font)))
+        }
+        Err(e) => {
+            error!("{:#}, using the built-in font instead", e);
+            None
+        }
+    }
+}
+
+/// Reads a BDF or PCF file and converts it into a fixed-cell font.
+pub fn load_font(path: &Path) -> Result<MonoFont<'static>> {
+    let data = fs::read(path).with_context(|| format!("Failed to read font {}", path.display()))?;
+    let glyphs = if data.starts_with(PCF_MAGIC) {
+        parse_pcf(&data)
+    } else if data.starts_with(b"STARTFONT") {
+        parse_bdf(&String::from_utf8_lossy(&data))
+    } else if data.starts_with(&[0x1f, 0x8b]) {
+        Err(anyhow!(
+            "compressed fonts are not supported, unpack it with gunzip"
+        ))
+    } else {
+        Err(anyhow!("not a BDF or PCF font"))
+    }
+    .with_context(|| format!("Invalid font {}", path.display()))?;
+
+    if glyphs.is_empty() {
+        bail!("Invalid font {}: no usable glyphs", path.display());
+    }
+    build_font(glyphs).with_context(|| format!("Invalid font {}", path.display()))
+}
+
+/// A single glyph bitmap, positioned like in BDF: `x_offset` from the
+/// origin and `y_offset` from the baseline to the bottom row.
+struct Glyph {
+    c: char,
+    advance: i32,
+    height: i32,
+    x_offset: i32,
+    y_offset: i32,
+    rows: Vec<Vec<bool>>,
+}
+
+/// Rejects glyph metrics no panel font has, which would otherwise make the
+/// conversion loop for ages or allocate a huge image.
+fn check_metrics(c: char, width: i32, height: i32, advance: i32, offsets: [i32; 2]) -> Result<()> {
+    let size = 0..=MAX_GLYPH_SIZE;
+    let offset = -MAX_GLYPH_SIZE..=MAX_GLYPH_SIZE;
+    let valid = [width, height, advance].iter().all(|n| size.contains(n))
+        && offsets.iter().all(|o| offset.contains(o));
+    if !valid {
+        bail!(
+            "glyph {:?} is {}x{} with advance {} and offset {:?}, expected sizes up to {}",
+            c,
+            width,
+            height,
+            advance,
+            offsets,
+            MAX_GLYPH_SIZE
+        );
+    }
+    Ok(())
+}
+
+fn parse_bdf(text: &str) -> Result<Vec<Glyph>> {
+    let mut glyphs = Vec::new();
+    let mut lines = text.lines();
+    let numbers = |args: &str| -> Result<Vec<i32>> {
+        args.split_whitespace()
+            .map(|n| n.parse().map_err(|_| anyhow!("invalid number {:?}", n)))
+            .collect()
+    };
+
+    while let Some(line) = lines.next() {
+        if !line.starts_with("STARTCHAR") {
+            continue;
+        }
+        let mut encoding = -1;
+        let mut advance = 0;
+        let mut bbx = [0; 4];
+        for line in lines.by_ref() {
+            let (keyword, args) = line.split_once(' ').unwrap_or((line, ""));
+            match keyword {
+                "ENCODING" => encoding = *numbers(args)?.first().unwrap_or(&-1),
+                "DWIDTH" => advance = *numbers(args)?.first().unwrap_or(&0),
+                "BBX" => {
+                    bbx = numbers(args)?
+                        .try_into()
+                        .map_err(|_| anyhow!("invalid BBX line {:?}", line))?
+                }
+                "BITMAP" => break,
+                _ => {}
+            }
+        }
+
+        let [width, height, x_offset, y_offset] = bbx;
+        let advance = if advance > 0 { advance } else { width };
+        let c = u32::try_from(encoding).ok().and_then(char::from_u32);
+        check_metrics(
+            c.unwrap_or(char::REPLACEMENT_CHARACTER),
+            width,
+            height,
+            advance,
+            [x_offset, y_offset],
+        )?;
+        let mut rows = Vec::new();
+        for line in lines.by_ref() {
+            if line.starts_with("ENDCHAR") {
+                break;
+            }
+            let bytes = (0..line.len() / 2)
+                .map(|i| {
+                    line.get(i * 2..i * 2 + 2)
+                        .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+                })
+                .collect::<Option<Vec<u8>>>()
+                .ok_or_else(|| anyhow!("invalid bitmap row {:?}", line))?;
+            rows.push(
+                (0..width as usize)
+                    .map(|x| bytes.get(x / 8).is_some_and(|b| b & (0x80 >> (x % 8)) != 0))
+                    .collect(),
+            );
+        }
+
+        if let Some(c) = c {
+            glyphs.push(Glyph {
+                c,
+                advance,
+                height,
+                x_offset,
+                y_offset,
+                rows,
+            });
+        }
+    }
+    Ok(glyphs)
+}
+
+/// Reader for the integers in a PCF table, whose byte order is given by
+/// the table format.
+struct PcfTable<'a> {
+    data: &'a [u8],
+    format: u32,
+    pos: usize,
+}
+
+impl<'a> PcfTable<'a> {
+    fn new(data: &'a [u8], offset: usize) -> Result<Self> {
+        let bytes = offset
+            .checked_add(4)
+            .and_then(|end| data.get(offset..end))
+            .ok_or_else(|| anyhow!("table outside of file"))?;
+        let format = u32::from_le_bytes(bytes.try_into().unwrap());
+        Ok(PcfTable {
+            data,
+            format,
+            pos: offset + 4,
+        })
+    }
+
+    fn big_endian(&self) -> bool {
+        self.format & 4 != 0
+    }
+
+    fn bytes(&mut self, len: usize) -> Result<&'a [u8]> {
+        let bytes = self
+            .pos
+            .checked_add(len)
+            .and_then(|end| self.data.get(self.pos..end))
+            .ok_or_else(|| anyhow!("unexpected end of file"))?;
+        self.pos += len;
+        Ok(bytes)
+    }
+
+    /// Bytes left after the read position, to sanity-check counts.
+    fn remaining(&self) -> usize {
+        self.data.len().saturating_sub(self.pos)
+    }
+
+    fn u8(&mut self) -> Result<u8> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16> {
+        let bytes = self.bytes(2)?.try_into().unwrap();
+        Ok(if self.big_endian() {
+            u16::from_be_bytes(bytes)
+        } else {
+            u16::from_le_bytes(bytes)
+        })
+    }
+
+    fn i16(&mut self) -> Result<i16> {
+        Ok(self.u16()? as i16)
+    }
+
+    fn u32(&mut self) -> Result<u32> {
+        let bytes = self.bytes(4)?.try_into().unwrap();
+        Ok(if self.big_endian() {
+            u32::from_be_bytes(bytes)
+        } else {
+            u32::from_le_bytes(bytes)
+        })
+    }
+}
+
+struct PcfMetrics {
+    left: i32,
+    right: i32,
+    advance: i32,
+    ascent: i32,
+    descent: i32,
+}
+
+fn parse_pcf(data: &[u8]) -> Result<Vec<Glyph>> {
+    let mut header = PcfTable {
+        data,
+        format: 0,
+        pos: 4,
+    };
+    let mut tables = HashMap::new();
+    for _ in 0..header.u32()? {
+        let kind = header.u32()?;
+        let _format = header.u32()?;
+        let _size = header.u32()?;
+        let offset = header.u32()? as usize;
+        tables.insert(kind, offset);
+    }
+    let table = |kind: u32, name: &str| -> Result<PcfTable> {
+        let offset = tables
+            .get(&kind)
+            .ok_or_else(|| anyhow!("missing {} table", name))?;
+        PcfTable::new(data, *offset)
+    };
+
+    let mut metrics_table = table(PCF_METRICS, "metrics")?;
+    let compressed = metrics_table.format & PCF_COMPRESSED_METRICS != 0;
+    let count = if compressed {
+        metrics_table.u16()? as usize
+    } else {
+        metrics_table.u32()? as usize
+    };
+    let metric_size = if compressed { 5 } else { 12 };
+    if count > metrics_table.remaining() / metric_size {
+        bail!("metrics table holds fewer than its {} glyphs", count);
+    }
+    let mut metrics = Vec::with_capacity(count);
+    for _ in 0..count {
+        let metric = if compressed {
+            let mut value = || -> Result<i32> { Ok(metrics_table.u8()? as i32 - 0x80) };
+            let (left, right, advance, ascent, descent) =
+                (value()?, value()?, value()?, value()?, value()?);
+            PcfMetrics {
+                left,
+                right,
+                advance,
+                ascent,
+                descent,
+            }
+        } else {
+            let metric = PcfMetrics {
+                left: metrics_table.i16()? as i32,
+                right: metrics_table.i16()? as i32,
+                advance: metrics_table.i16()? as i32,
+                ascent: metrics_table.i16()? as i32,
+                descent: metrics_table.i16()? as i32,
+            };
+            let _attributes = metrics_table.u16()?;
+            metric
+        };
+        metrics.push(metric);
+    }
+
+    let mut bitmaps = table(PCF_BITMAPS, "bitmaps")?;
+    if bitmaps.u32()? as usize != count {
+        bail!("bitmap and metrics tables disagree on the glyph count");
+    }
+    let offsets = (0..count)
+        .map(|_| bitmaps.u32().map(|o| o as usize))
+        .collect::<Result<Vec<_>>>()?;
+    for _ in 0..4 {
+        bitmaps.u32()?;
+    }
+    let bitmap_data = &data[bitmaps.pos..];
+    let pad = 1 << (bitmaps.format & 3);
+    let scan_unit = 1 << ((bitmaps.format >> 4) & 3);
+    let msb_bit_first = bitmaps.format & 8 != 0;
+    let swap_bytes = scan_unit > 1 && bitmaps.big_endian() != msb_bit_first;
+
+    let mut encodings = table(PCF_BDF_ENCODINGS, "encodings")?;
+    let (min_byte2, max_byte2) = (encodings.u16()? as u32, encodings.u16()? as u32);
+    let (min_byte1, max_byte1) = (encodings.u16()? as u32, encodings.u16()? as u32);
+    let _default_char = encodings.u16()?;
+
+    let mut glyphs = Vec::new();
+    for byte1 in min_byte1..=max_byte1 {
+        for byte2 in min_byte2..=max_byte2 {
+            let index = encodings.u16()? as usize;
+            let c = char::from_u32((byte1 << 8) | byte2);
+            let (Some(c), Some(metric)) = (c, metrics.get(index)) else {
+                continue;
+            };
+
+            let width = metric.right - metric.left;
+            let height = metric.ascent + metric.descent;
+            check_metrics(
+                c,
+                width,
+                height,
+                metric.advance,
+                [metric.left, -metric.descent],
+            )?;
+            let row_bytes = (width as usize).div_ceil(pad * 8) * pad;
+            let start = offsets[index];
+            let mut rows = Vec::with_capacity(height as usize);
+            for y in 0..height as usize {
+                let row_start = start.saturating_add(y * row_bytes);
+                let mut row = bitmap_data
+                    .get(row_start..row_start.saturating_add(row_bytes))
+                    .ok_or_else(|| anyhow!("glyph bitmap outside of file"))?
+                    .to_vec();
+                if swap_bytes {
+                    for unit in row.chunks_mut(scan_unit) {
+                        unit.reverse();
+                    }
+                }
+                rows.push(
+                    (0..width as usize)
+                        .map(|x| {
+                            let mask = if msb_bit_first {
+                                0x80 >> (x % 8)
+                            } else {
+                                1 << (x % 8)
+                            };
+                            row[x / 8] & mask != 0
+                        })
+                        .collect(),
+                );
+            }
+            glyphs.push(Glyph {
+                c,
+                advance: metric.advance,
+                height,
+                x_offset: metric.left,
+                y_offset: -metric.descent,
+                rows,
+            });
+        }
+    }
+    Ok(glyphs)
+}
+
+/// Maps characters to their position in a converted font.
+struct CharMapping {
+    indices: HashMap<char, usize>,
+    replacement: usize,
+}
+
+impl GlyphMapping for CharMapping {
+    fn index(&self, c: char) -> usize {
+        self.indices.get(&c).copied().unwrap_or(self.replacement)
+    }
+}
+
+/// Draws all glyphs into equally sized cells of a 1bpp image. The cell is
+/// as wide as the widest advance, so proportional fonts end up monospaced.
+fn build_font(glyphs: Vec<Glyph>) -> Result<MonoFont<'static>> {
+    let cell_width = glyphs.iter().map(|g| g.advance).max().unwrap_or(1).max(1);
+    let ascent = glyphs
+        .iter()
+        .map(|g| g.y_offset + g.height)
+        .max()
+        .unwrap_or(1)
+        .max(1);
+    let descent = glyphs.iter().map(|g| -g.y_offset).max().unwrap_or(0).max(0);
+    let cell_height = ascent + descent;
+
+    let too_large = || {
+        anyhow!(
+            "{} glyphs of {}x{} are too large",
+            glyphs.len(),
+            cell_width,
+            cell_height
+        )
+    };
+    let rows = u32::try_from(glyphs.len())
+        .map_err(|_| too_large())?
+        .div_ceil(GLYPHS_PER_ROW);
+    let image_width = GLYPHS_PER_ROW
+        .checked_mul(cell_width as u32)
+        .ok_or_else(too_large)?;
+    let stride = image_width.div_ceil(8) as usize;
+    let image_size = rows
+        .checked_mul(cell_height as u32)
+        .and_then(|height| stride.checked_mul(height as usize))
+        .ok_or_else(too_large)?;
+    let mut image = vec![0u8; image_size];
+    let mut indices = HashMap::new();
+
+    for (index, glyph) in glyphs.iter().enumerate() {
+        indices.insert(glyph.c, index);
+        let cell_x = (index as u32 % GLYPHS_PER_ROW) as i32 * cell_width;
+        let cell_y = (index as u32 / GLYPHS_PER_ROW) as i32 * cell_height;
+        let top = ascent - (glyph.y_offset + glyph.height);
+        for (row_index, row) in glyph.rows.iter().enumerate() {
+            let y = top + row_index as i32;
+            for (col, _) in row.iter().enumerate().filter(|(_, on)| **on) {
+                let x = glyph.x_offset + col as i32;
+                if (0..cell_width).contains(&x) && (0..cell_height).contains(&y) {
+                    let px = (cell_x + x) as usize;
+                    let py = (cell_y + y) as usize;
+                    image[py * stride + px / 8] |= 0x80 >> (px % 8);
+                }
+            }
+        }
+    }
+
+    let replacement = indices
+        .get(&'?')
+        .or_else(|| indices.get(&' '))
+        .copied()
+        .unwrap_or(0);
+    let mapping: &'static CharMapping = Box::leak(Box::new(CharMapping {
+        indices,
+        replacement,
+    }));
+    let image: &'static [u8] = image.leak();
+
+    let baseline = (ascent - 1) as u32;
+    Ok(MonoFont {
+        image: ImageRaw::new(image, image_width),
+        character_size: Size::new(cell_width as u32, cell_height as u32),
+        character_spacing: 0,
+        baseline,
+        underline: DecorationDimensions::new(baseline + 2, 1),
+        strikethrough: DecorationDimensions::new(cell_height as u32 / 2, 1),
+        glyph_mapping: mapping,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use embedded_graphics::image::GetPixel;
+    use embedded_graphics::pixelcolor::BinaryColor;
+
+    const BDF: &str = "STARTFONT 2.1
+FONT -test-fixed-medium-r-normal--4-40-75-75-c-50-iso10646-1
+SIZE 4 75 75
+FONTBOUNDINGBOX 4 4 0 -1
+CHARS 2
+STARTCHAR A
+ENCODING 65
+SWIDTH 500 0
+DWIDTH 5 0
+BBX 4 3 0 0
+BITMAP
+60
+90
+F0
+ENDCHAR
+STARTCHAR g
+ENCODING 103
+DWIDTH 5 0
+BBX 3 2 1 -1
+BITMAP
+E0
+20
+ENDCHAR
+ENDFONT
+";
+
+    fn bdf_with_char(char_lines: &str) -> String {
+        format!(
+            "STARTFONT 2.1\nSTARTCHAR x\nENCODING 120\n{}\nENDCHAR\nENDFONT\n",
+            char_lines
+        )
+    }
+
+    fn lit(font: &MonoFont, c: char) -> Vec<(u32, u32)> {
+        let index = font.glyph_mapping.index(c) as u32;
+        let size = font.character_size;
+        let cell_x = index % GLYPHS_PER_ROW * size.width;
+        let cell_y = index / GLYPHS_PER_ROW * size.height;
+        let mut pixels = Vec::new();
+        for y in 0..size.height {
+            for x in 0..size.width {
+                let point = Point::new((cell_x + x) as i32, (cell_y + y) as i32);
+                if font.image.pixel(point) == Some(BinaryColor::On) {
+                    pixels.push((x, y));
+                }
+            }
+        }
+        pixels
+    }
+
+    #[test]
+    fn bdf_glyphs_share_a_baseline() {
+        let font = build_font(parse_bdf(BDF).unwrap()).unwrap();
+        assert_eq!(font.character_size, Size::new(5, 4));
+        assert_eq!(font.baseline, 2);
+        assert_eq!(
+            lit(&font, 'A'),
+            [
+                (1, 0),
+                (2, 0),
+                (0, 1),
+                (3, 1),
+                (0, 2),
+                (1, 2),
+                (2, 2),
+                (3, 2)
+            ]
+        );
+        // Shifted right by its x offset, the descender below the baseline.
+        assert_eq!(lit(&font, 'g'), [(1, 2), (2, 2), (3, 2), (3, 3)]);
+        // Missing characters fall back to the first glyph without ? or space.
+        assert_eq!(lit(&font, 'Z'), lit(&font, 'A'));
+    }
+
+    #[test]
+    fn bdf_rejects_non_ascii_bitmap_rows() {
+        let bdf = bdf_with_char("BBX 8 1 0 0\nBITMAP\n0Ä");
+        let error = parse_bdf(&bdf).err().unwrap();
+        assert!(
+            error.to_string().contains("invalid bitmap row"),
+            "{}",
+            error
+        );
+    }
+
+    #[test]
+    fn bdf_rejects_out_of_range_metrics() {
+        for bbx in [
+            "BBX -5 8 0 0",
+            "BBX 8 -1 0 0",
+            "BBX 2000000000 8 0 0",
+            "BBX 8 65 0 0",
+            "BBX 8 8 0 -100000",
+            "DWIDTH 1000 0\nBBX 8 8 0 0",
+        ] {
+            let bdf = bdf_with_char(&format!("{}\nBITMAP\nFF", bbx));
+            assert!(parse_bdf(&bdf).is_err(), "{} accepted", bbx);
+        }
+        let bdf = bdf_with_char("BBX 64 64 0 -64\nBITMAP\nFF");
+        assert!(parse_bdf(&bdf).is_ok());
+    }
+
+    #[test]
+    fn bdf_rejects_invalid_numbers() {
+        assert!(parse_bdf(&bdf_with_char("BBX 8 8 0\nBITMAP")).is_err());
+        assert!(parse_bdf(&bdf_with_char("BBX 8 x 0 0\nBITMAP")).is_err());
+    }
+
+    /// A little-endian PCF with uncompressed metrics, one byte bitmap rows
+    /// and the given glyphs as `(char, metrics, rows)`, the metrics as
+    /// left, right, advance, ascent and descent.
+    fn pcf(glyphs: &[(char, [i16; 5], &[u8])]) -> Vec<u8> {
+        let mut metrics = 0u32.to_le_bytes().to_vec();
+        metrics.extend((glyphs.len() as u32).to_le_bytes());
+        let mut bitmaps = 8u32.to_le_bytes().to_vec();
+        bitmaps.extend((glyphs.len() as u32).to_le_bytes());
+        let mut bitmap_data: Vec<u8> = Vec::new();
+        for (_, metric, rows) in glyphs {
+            for value in metric {
+                metrics.extend(value.to_le_bytes());
+            }
+            metrics.extend(0u16.to_le_bytes());
+            bitmaps.extend((bitmap_data.len() as u32).to_le_bytes());
+            bitmap_data.extend(*rows);
+        }
+        bitmaps.extend([0; 16]);
+        bitmaps.extend(bitmap_data);
+
+        let (first, last) = (
+            glyphs.iter().map(|g| g.0 as u16).min().unwrap(),
+            glyphs.iter().map(|g| g.0 as u16).max().unwrap(),
+        );
+        let mut encodings = 0u32.to_le_bytes().to_vec();
+        for value in [first, last, 0, 0, 0] {
+            encodings.extend(value.to_le_bytes());
+        }
+        for code in first..=last {
+            let index = glyphs.iter().position(|g| g.0 as u16 == code);
+            encodings.extend(index.map_or(0xffff, |i| i as u16).to_le_bytes());
+        }
+
+        let tables = [
+            (PCF_METRICS, metrics),
+            (PCF_BITMAPS, bitmaps),
+            (PCF_BDF_ENCODINGS, encodings),
+        ];
+        let mut data = PCF_MAGIC.to_vec();
+        data.extend((tables.len() as u32).to_le_bytes());
+        let mut offset = 8 + tables.len() * 16;
+        for (kind, table) in &tables {
+            for value in [*kind, 0, table.len() as u32, offset as u32] {
+                data.extend(value.to_le_bytes());
+            }
+            offset += table.len();
+        }
+        for (_, table) in tables {
+            data.extend(table);
+        }
+        data
+    }
+
+    #[test]
+    fn pcf_glyphs_are_converted() {
+        let data = pcf(&[
+            ('A', [0, 4, 5, 3, 0], &[0x60, 0x90, 0xf0]),
+            ('C', [1, 4, 5, 1, 1], &[0xe0, 0x20]),
+        ]);
+        let glyphs = parse_pcf(&data).unwrap();
+        let chars: Vec<char> = glyphs.iter().map(|g| g.c).collect();
+        assert_eq!(chars, ['A', 'C']);
+
+        let font = build_font(glyphs).unwrap();
+        assert_eq!(font.character_size, Size::new(5, 4));
+        assert_eq!(
+            lit(&font, 'A'),
+            [
+                (1, 0),
+                (2, 0),
+                (0, 1),
+                (3, 1),
+                (0, 2),
+                (1, 2),
+                (2, 2),
+                (3, 2)
+            ]
+        );
+        assert_eq!(lit(&font, 'C'), [(1, 2), (2, 2), (3, 2), (3, 3)]);
+    }
+
+    #[test]
+    fn pcf_rejects_out_of_range_metrics() {
+        // Right of the left edge, a negative width.
+        assert!(parse_pcf(&pcf(&[('A', [4, 0, 5, 3, 0], &[0; 3])])).is_err());
+        assert!(parse_pcf(&pcf(&[('A', [0, 4, 5, 3000, 0], &[0; 3])])).is_err());
+        assert!(parse_pcf(&pcf(&[('A', [0, 4, -5, 3, 0], &[0; 3])])).is_err());
+    }
+
+    #[test]
+    fn pcf_rejects_counts_beyond_the_table() {
+        let mut data = pcf(&[('A', [0, 4, 5, 3, 0], &[0x60, 0x90, 0xf0])]);
+        let metrics_offset = u32::from_le_bytes(data[20..24].try_into().unwrap()) as usize;
+        data[metrics_offset + 4..metrics_offset + 8].copy_from_slice(&u32::MAX.to_le_bytes());
+        let error = parse_pcf(&data).err().unwrap();
+        assert!(error.to_string().contains("metrics table"), "{}", error);
+    }
+
+    #[test]
+    fn pcf_rejects_truncated_files() {
+        let data = pcf(&[('A', [0, 4, 5, 3, 0], &[0x60, 0x90, 0xf0])]);
+        for len in [4, 10, 60, data.len() - 1] {
+            assert!(parse_pcf(&data[..len]).is_err(), "{} bytes accepted", len);
+        }
+        // A bitmap offset past the end of the data.
+        let mut data = data;
+        let bitmaps_offset = u32::from_le_bytes(data[36..40].try_into().unwrap()) as usize;
+        data[bitmaps_offset + 8..bitmaps_offset + 12].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(parse_pcf(&data).is_err());
+    }
+
+    #[test]
+    fn build_font_rejects_cells_too_large_for_an_image() {
+        let glyph = Glyph {
+            c: 'A',
+            advance: i32::MAX,
+            height: 1,
+            x_offset: 0,
+            y_offset: 0,
+            rows: Vec::new(),
+        };
+        assert!(build_font(vec![glyph]).is_err());
+    }
+}
@@ -0,0 +1,91 @@
+use core::convert::Infallible;
+use embedded_graphics::{pixelcolor::BinaryColor, prelude::*};
+
+/// 1-bit in-memory copy of the panel contents.
+///
+/// Everything is drawn here first and then copied to the controller, so
+/// the exact image on the panel is available for burn-in tracking.
+#[derive(Clone)]
+pub struct Framebuffer {
+    width: u32,
+    height: u32,
+    pixels: Vec<bool>,
+}
+
+impl Framebuffer {
+    pub fn new(width: u32, height: u32) -> Self {
+        Framebuffer {
+            width,
+            height,
+            pixels: vec![false; (width * height) as usize],
+        }
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn invert(&mut self) {
+        for pixel in self.pixels.iter_mut() {
+            *pixel = !*pixel;
+        }
+    }
+
+    /// Pixels packed eight to a byte, first pixel in the highest bit, each
+    /// row padded to whole bytes.
+    pub fn packed_rows(&self) -> Vec<u8> {
+        let stride = self.width.div_ceil(8) as usize;
+        let mut data = vec![0u8; stride * self.height as usize];
+        for (x, y) in self.lit_pixels() {
+            data[y as usize * stride + x as usize / 8] |= 0x80 >> (x % 8);
+        }
+        data
+    }
+
+    /// Iterates over the coordinates of all lit pixels.
+    pub fn lit_pixels(&self) -> impl Iterator<Item = (u32, u32)> + '_ {
+        let width = self.width;
+        self.pixels
+            .iter()
+            .enumerate()
+            .filter(|(_, on)| **on)
+            .map(move |(i, _)| (i as u32 % width, i as u32 / width))
+    }
+}
+
+impl DrawTarget for Framebuffer {
+    type Color = BinaryColor;
+    type Error = Infallible;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Pixel<Self::Color>>,
+    {
+        for Pixel(point, color) in pixels {
+            if point.x >= 0
+                && point.y >= 0
+                && (point.x as u32) < self.width
+                && (point.y as u32) < self.height
+            {
+                let index = point.y as u32 * self.width + point.x as u32;
+                self.pixels[index as usize] = color.is_on();
+            }
+        }
+        Ok(())
+    }
+
+    fn clear(&mut self, color: Self::Color) -> Result<(), Self::Error> {
+        self.pixels.fill(color.is_on());
+        Ok(())
+    }
+}
+
+impl OriginDimensions for Framebuffer {
+    fn size(&self) -> Size {
+        Size::new(self.width, self.height)
+    }
+}
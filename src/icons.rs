@@ -0,0 +1,374 @@
+use anyhow::{anyhow, bail, Context, Result};
+use embedded_graphics::{image::Image, image::ImageRaw, pixelcolor::BinaryColor, prelude::*};
+use log::{error, info};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Icons and splash logos are at most as large as the biggest panel.
+const MAX_ICON_SIZE: u32 = 128;
+
+const THERMOMETER: [u8; 8] = [
+    0b00010000, 0b00101000, 0b00101000, 0b00111000, 0b00111000, 0b01111100, 0b01111100, 0b00111000,
+];
+
+/// Two fan positions, a quarter turn apart, alternated while it runs.
+const FAN: [[u8; 8]; 2] = [
+    [
+        0b01111000, 0b00111000, 0b10011001, 0b11100111, 0b11100111, 0b10011001, 0b00011100,
+        0b00011110,
+    ],
+    [
+        0b00111100, 0b00011001, 0b00011011, 0b11100111, 0b11100111, 0b11011000, 0b10011000,
+        0b00111100,
+    ],
+];
+
+const NETWORK: [u8; 8] = [
+    0b00111000, 0b00101000, 0b00111000, 0b00010000, 0b01111100, 0b01000100, 0b11101110, 0b11101110,
+];
+
+const DISK: [u8; 8] = [
+    0b01111110, 0b10000001, 0b10000001, 0b10000001, 0b11111111, 0b10000001, 0b10000101, 0b01111110,
+];
+
+const BOLT: [u8; 8] = [
+    0b00000110, 0b00001100, 0b00011000, 0b00111110, 0b01111100, 0b00011000, 0b00110000, 0b00100000,
+];
+
+const WARNING: [u8; 8] = [
+    0b00011000, 0b00011000, 0b00100100, 0b00100100, 0b01011010, 0b01000010, 0b10011001, 0b11111111,
+];
+
+/// A 1bpp bitmap, rows packed MSB first and padded to whole bytes like
+/// `ImageRaw` expects.
+pub struct Icon {
+    width: u32,
+    height: u32,
+    data: Vec<u8>,
+}
+
+impl Icon {
+    fn from_rows(width: u32, height: u32, pixel: impl Fn(u32, u32) -> bool) -> Self {
+        let stride = width.div_ceil(8);
+        let mut data = vec![0u8; (stride * height) as usize];
+        for y in 0..height {
+            for x in 0..width {
+                if pixel(x, y) {
+                    data[(y * stride + x / 8) as usize] |= 0x80 >> (x % 8);
+                }
+            }
+        }
+        Icon {
+            width,
+            height,
+            data,
+        }
+    }
+
+    /// Loads a PBM or XBM file.
+    pub fn load(path: &Path) -> Result<Self> {
+        let data = fs::read(path).with_context(|| format!("failed to read {}", path.display()))?;
+        if data.starts_with(b"P1") || data.starts_with(b"P4") {
+            parse_pbm(&data)
+        } else if String::from_utf8_lossy(&data).contains("_bits") {
+            parse_xbm(&String::from_utf8_lossy(&data))
+        } else {
+            Err(anyhow!("not a PBM or XBM image"))
+        }
+        .with_context(|| format!("invalid icon {}", path.display()))
+    }
+
+    pub fn size(&self) -> Size {
+        Size::new(self.width, self.height)
+    }
+
+    pub fn draw<D>(&self, disp: &mut D, top_left: Point) -> Result<(), D::Error>
+    where
+        D: DrawTarget<Color = BinaryColor>,
+    {
+        let raw = ImageRaw::<BinaryColor>::new(&self.data, self.width);
+        Image::new(&raw, top_left).draw(disp)
+    }
+}
+
+/// Built-in icons plus the ones loaded from the files in `[display.icons]`.
+pub struct Icons {
+    user: HashMap<String, Icon>,
+}
+
+impl Icons {
+    /// Loads the configured icon files. Files that can't be read are
+    /// reported and left out.
+    pub fn load(files: &HashMap<String, PathBuf>) -> Self {
+        let mut user = HashMap::new();
+        for (name, path) in files {
+            match Icon::load(path) {
+                Ok(icon) => {
+                    info!(
+                        "Loaded icon '{}' from {} ({}x{})",
+                        name,
+                        path.display(),
+                        icon.width,
+                        icon.height
+                    );
+                    user.insert(name.clone(), icon);
+                }
+                Err(e) => error!("Failed to load icon '{}': {:#}", name, e),
+            }
+        }
+        Icons { user }
+    }
+
+    /// Width of the icon `name`, or `None` for unknown names.
+    pub fn width(&self, name: &str) -> Option<i32> {
+        self.bitmap(name, None).map(|(_, width, _)| width as i32)
+    }
+
+    /// Draws the icon `name` with its bottom left corner at `position`.
+    /// The fan icon shows `fan_frame` while the fan runs and stands still
+    /// otherwise. Returns the point right of the icon, or `None` for unknown
+    /// names.
+    pub fn draw<D>(
+        &self,
+        disp: &mut D,
+        name: &str,
+        position: Point,
+        fan_frame: Option<u32>,
+    ) -> Result<Option<Point>, D::Error>
+    where
+        D: DrawTarget<Color = BinaryColor>,
+    {
+        let Some((data, width, height)) = self.bitmap(name, fan_frame) else {
+            return Ok(None);
+        };
+        let raw = ImageRaw::<BinaryColor>::new(data, width);
+        let top_left = position - Point::new(0, height as i32 - 1);
+        Image::new(&raw, top_left).draw(disp)?;
+        Ok(Some(Point::new(position.x + width as i32, position.y)))
+    }
+
+    /// User icons take precedence, so built-in ones can be replaced.
+    fn bitmap(&self, name: &str, fan_frame: Option<u32>) -> Option<(&[u8], u32, u32)> {
+        if let Some(icon) = self.user.get(name) {
+            return Some((&icon.data, icon.width, icon.height));
+        }
+        let data: &[u8] = match name {
+            "thermometer" => &THERMOMETER,
+            "fan" => &FAN[fan_frame.unwrap_or(0) as usize % FAN.len()],
+            "network" => &NETWORK,
+            "disk" => &DISK,
+            "bolt" => &BOLT,
+            "warning" => &WARNING,
+            _ => return None,
+        };
+        Some((data, 8, 8))
+    }
+}
+
+/// If `label` is an icon reference like `:fan:`, returns the icon name.
+pub fn icon_name(label: &str) -> Option<&str> {
+    label
+        .strip_prefix(':')
+        .and_then(|rest| rest.strip_suffix(':'))
+        .filter(|name| !name.is_empty())
+}
+
+/// Parses plain (P1) and raw (P4) portable bitmaps, where 1 is black. On
+/// the OLED black is drawn lit, so the image looks like it does on paper.
+fn parse_pbm(data: &[u8]) -> Result<Icon> {
+    // Header tokens: magic, width and height, separated by whitespace and
+    // comments.
+    let mut pos = 0;
+    let mut tokens = Vec::new();
+    while tokens.len() < 3 {
+        while pos < data.len() && (data[pos].is_ascii_whitespace() || data[pos] == b'#') {
+            if data[pos] == b'#' {
+                while pos < data.len() && data[pos] != b'\n' {
+                    pos += 1;
+                }
+            } else {
+                pos += 1;
+            }
+        }
+        let start = pos;
+        while pos < data.len() && !data[pos].is_ascii_whitespace() {
+            pos += 1;
+        }
+        if start == pos {
+            bail!("truncated header");
+        }
+        tokens.push(String::from_utf8_lossy(&data[start..pos]).to_string());
+    }
+    let size = |token: &str| -> Result<u32> {
+        token
+            .parse()
+            .map_err(|_| anyhow!("invalid size {:?}", token))
+    };
+    let (width, height) = (size(&tokens[1])?, size(&tokens[2])?);
+    let pixel_count = check_size(width, height)?;
+    // Exactly one whitespace character separates the header from raw data.
+    pos += 1;
+
+    if tokens[0] == "P4" {
+        let stride = width.div_ceil(8) as usize;
+        let pixels = data
+            .get(pos..pos + stride * height as usize)
+            .ok_or_else(|| anyhow!("truncated image data"))?;
+        Ok(Icon::from_rows(width, height, |x, y| {
+            pixels[y as usize * stride + x as usize / 8] & (0x80 >> (x % 8)) != 0
+        }))
+    } else {
+        let pixels: Vec<bool> = data[pos.min(data.len())..]
+            .iter()
+            .filter(|b| **b == b'0' || **b == b'1')
+            .map(|b| *b == b'1')
+            .collect();
+        if pixels.len() < pixel_count {
+            bail!("truncated image data");
+        }
+        Ok(Icon::from_rows(width, height, |x, y| {
+            pixels[(y * width + x) as usize]
+        }))
+    }
+}
+
+/// Rejects empty and oversized images. Returns the number of pixels.
+fn check_size(width: u32, height: u32) -> Result<usize> {
+    let range = 1..=MAX_ICON_SIZE;
+    if !range.contains(&width) || !range.contains(&height) {
+        bail!(
+            "unsupported size {}x{}, expected at most {}x{}",
+            width,
+            height,
+            MAX_ICON_SIZE,
+            MAX_ICON_SIZE
+        );
+    }
+    width
+        .checked_mul(height)
+        .map(|count| count as usize)
+        .ok_or_else(|| anyhow!("unsupported size {}x{}", width, height))
+}
+
+/// Parses an X bitmap, the C source format written by `bitmap` and GIMP.
+fn parse_xbm(text: &str) -> Result<Icon> {
+    let define = |suffix: &str| -> Result<u32> {
+        text.lines()
+            .filter_map(|line| line.trim().strip_prefix("#define"))
+            .find_map(|line| {
+                let mut parts = line.split_whitespace();
+                let name = parts.next()?;
+                name.ends_with(suffix).then(|| parts.next()?.parse().ok())?
+            })
+            .ok_or_else(|| anyhow!("missing {} definition", suffix))
+    };
+    let (width, height) = (define("_width")?, define("_height")?);
+    check_size(width, height)?;
+
+    let body = text
+        .split_once('{')
+        .and_then(|(_, rest)| rest.split_once('}'))
+        .map(|(body, _)| body)
+        .ok_or_else(|| anyhow!("missing bitmap data"))?;
+    let bytes = body
+        .split(',')
+        .map(str::trim)
+        .filter(|value| !value.is_empty())
+        .map(|value| {
+            let hex = value.trim_start_matches("0x").trim_start_matches("0X");
+            u8::from_str_radix(hex, 16).map_err(|_| anyhow!("invalid byte {:?}", value))
+        })
+        .collect::<Result<Vec<u8>>>()?;
+
+    // XBM rows are padded to whole bytes with the first pixel in the
+    // lowest bit.
+    let stride = width.div_ceil(8) as usize;
+    if bytes.len() < stride * height as usize {
+        bail!("truncated image data");
+    }
+    Ok(Icon::from_rows(width, height, |x, y| {
+        bytes[y as usize * stride + x as usize / 8] & (1 << (x % 8)) != 0
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use embedded_graphics::image::GetPixel;
+
+    /// The lit pixels of `icon` as rows of `#` and `.`.
+    fn rows(icon: &Icon) -> Vec<String> {
+        let raw = ImageRaw::<BinaryColor>::new(&icon.data, icon.width);
+        (0..icon.height as i32)
+            .map(|y| {
+                (0..icon.width as i32)
+                    .map(|x| match raw.pixel(Point::new(x, y)) {
+                        Some(BinaryColor::On) => '#',
+                        _ => '.',
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    fn error(result: Result<Icon>) -> String {
+        format!("{:#}", result.err().expect("parsing should fail"))
+    }
+
+    #[test]
+    fn plain_pbm_with_comments() {
+        let icon = parse_pbm(b"P1\n# a comment\n3 2\n1 0 1\n010\n").unwrap();
+        assert_eq!(icon.size(), Size::new(3, 2));
+        assert_eq!(rows(&icon), ["#.#", ".#."]);
+    }
+
+    #[test]
+    fn raw_pbm_with_padded_rows() {
+        let icon = parse_pbm(b"P4\n10 2\n\xff\xc0\x80\x40").unwrap();
+        assert_eq!(rows(&icon), ["##########", "#........#"]);
+    }
+
+    #[test]
+    fn broken_pbm_is_rejected() {
+        assert!(error(parse_pbm(b"P1\n3")).contains("truncated header"));
+        assert!(error(parse_pbm(b"P1 3 2\n1 0 1\n0")).contains("truncated image data"));
+        assert!(error(parse_pbm(b"P4 16 2\n\xff\xff\xff")).contains("truncated image data"));
+        assert!(error(parse_pbm(b"P1 three 2\n")).contains("invalid size"));
+        assert!(error(parse_pbm(b"P1 -3 2\n")).contains("invalid size"));
+        assert!(error(parse_pbm(b"P1 0 0\n")).contains("unsupported size 0x0"));
+        assert!(error(parse_pbm(b"P1 65536 65536\n")).contains("unsupported size"));
+        assert!(error(parse_pbm(b"P4 4294967295 2\n")).contains("unsupported size"));
+    }
+
+    const XBM: &str = "#define icon_width 10
+#define icon_height 2
+static unsigned char icon_bits[] = {
+   0xff, 0x03, 0x01, 0x02 };
+";
+
+    #[test]
+    fn xbm_with_the_first_pixel_in_the_lowest_bit() {
+        let icon = parse_xbm(XBM).unwrap();
+        assert_eq!(rows(&icon), ["##########", "#........#"]);
+    }
+
+    #[test]
+    fn broken_xbm_is_rejected() {
+        assert!(error(parse_xbm(&XBM.replace("0x01, 0x02 ", ""))).contains("truncated"));
+        assert!(error(parse_xbm(&XBM.replace("0x01", "0xzz"))).contains("invalid byte"));
+        assert!(error(parse_xbm(&XBM.replace("{", "("))).contains("missing bitmap data"));
+        assert!(error(parse_xbm(&XBM.replace("icon_height", "h"))).contains("missing _height"));
+        let huge = XBM.replace("width 10", "width 100000");
+        assert!(error(parse_xbm(&huge)).contains("unsupported size 100000x2"));
+    }
+
+    #[test]
+    fn icon_names() {
+        assert_eq!(icon_name(":fan:"), Some("fan"));
+        assert_eq!(icon_name("::"), None);
+        assert_eq!(icon_name("fan"), None);
+        assert!(Icons::load(&HashMap::new()).width("thermometer").is_some());
+        assert_eq!(Icons::load(&HashMap::new()).width("nosuch"), None);
+    }
+}
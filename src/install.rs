@@ -0,0 +1,407 @@
+use crate::config::Config;
+use anyhow::{anyhow, Context, Result};
+use std::env;
+use std::fs;
+use std::io::ErrorKind;
+use std::os::unix::fs::{chown, PermissionsExt};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+const BINARY_PATH: &str = "/usr/local/bin/rustberry-poe-monitor";
+const UNIT_DIR: &str = "/etc/systemd/system";
+const UNIT_NAME: &str = "rustberry-poe-monitor.service";
+/// The commented example config, installed when the user has none yet.
+const DEFAULT_CONFIG: &str = include_str!("../config.toml");
+/// Newer Raspberry Pi OS releases moved config.txt to the firmware
+/// partition.
+const BOOT_CONFIGS: [&str; 2] = ["/boot/firmware/config.txt", "/boot/config.txt"];
+
+/// The user the service runs as.
+struct User {
+    name: String,
+    /// Owner of the config. `None` if the user isn't in /etc/passwd.
+    ids: Option<(u32, u32)>,
+    home: PathBuf,
+}
+
+/// Copies the running binary to /usr/local/bin, writes the systemd unit and
+/// a default config below `root`, and checks that the monitor will be able
+/// to reach the I2C bus. The service is only enabled and started when
+/// installing to `/`.
+pub fn install(root: &Path, user: Option<String>) -> Result<()> {
+    let user = find_user(root, user)?;
+    install_binary(root)?;
+
+    let unit_path = under(root, UNIT_DIR).join(UNIT_NAME);
+    create_dir(&under(root, UNIT_DIR))?;
+    fs::write(&unit_path, unit_file(&user.name))
+        .with_context(|| format!("failed to write {}", unit_path.display()))?;
+    println!("Wrote {} to run as {}", unit_path.display(), user.name);
+
+    install_config(root, &user)?;
+    let i2c_ready = check_i2c(root);
+    let group_ready = check_i2c_group(root, &user);
+
+    if is_system_root(root) {
+        systemctl(&["daemon-reload"])?;
+        systemctl(&["enable", UNIT_NAME])?;
+        systemctl(&["restart", UNIT_NAME])?;
+        println!("Enabled and started {}", UNIT_NAME);
+    } else {
+        println!("Not enabling {} below {}", UNIT_NAME, root.display());
+    }
+    if !(i2c_ready && group_ready) {
+        println!(
+            "Installed, but the monitor can't reach the display until the warnings above are fixed"
+        );
+    }
+    Ok(())
+}
+
+/// Stops and removes the service and the binary below `root`. The config
+/// of `user` is only removed with `purge`.
+pub fn uninstall(root: &Path, user: Option<String>, purge: bool) -> Result<()> {
+    if is_system_root(root) {
+        // Also fails when the unit is gone already, which is fine.
+        if let Err(e) = systemctl(&["disable", "--now", UNIT_NAME]) {
+            println!("Warning: {:#}", e);
+        }
+    }
+    remove_file(&under(root, UNIT_DIR).join(UNIT_NAME))?;
+    if is_system_root(root) {
+        systemctl(&["daemon-reload"])?;
+    }
+    remove_file(&under(root, BINARY_PATH))?;
+
+    if purge {
+        let user = find_user(root, user)?;
+        let config_path = Config::path_in_home(&under(root, &user.home));
+        let config_dir = config_path.parent().unwrap_or(&config_path);
+        match fs::remove_dir_all(config_dir) {
+            Ok(()) => println!("Removed {}", config_dir.display()),
+            Err(e) if e.kind() == ErrorKind::NotFound => {}
+            Err(e) => {
+                let context = format!("failed to remove {}", config_dir.display());
+                return Err(anyhow!(e).context(context));
+            }
+        }
+    } else {
+        println!("Kept the config, pass --purge to remove it too");
+    }
+    Ok(())
+}
+
+fn unit_file(user: &str) -> String {
+    format!(
+        "[Unit]
+Description=RustBerry PoE Monitor
+After=network.target
+
+[Service]
+User={user}
+# Holds the control socket
+RuntimeDirectory=rustberry-poe-monitor
+# The monitor reports when it's up and pings the watchdog from its main
+# loop, a loop stuck on I2C gets it restarted
+Type=notify
+WatchdogSec=30
+ExecStart={BINARY_PATH}
+Restart=always
+RestartSec=30
+
+[Install]
+WantedBy=multi-user.target
+"
+    )
+}
+
+/// `name`, or the user who ran sudo, looked up in the passwd file below
+/// `root`.
+fn find_user(root: &Path, name: Option<String>) -> Result<User> {
+    let name = name
+        .or_else(|| env::var("SUDO_USER").ok())
+        .or_else(|| env::var("USER").ok())
+        .ok_or_else(|| anyhow!("can't tell which user to run the monitor as, pass --user"))?;
+
+    let passwd = fs::read_to_string(under(root, "/etc/passwd")).unwrap_or_default();
+    // name:password:uid:gid:gecos:home:shell
+    let entry = passwd
+        .lines()
+        .map(|line| line.split(':').collect::<Vec<_>>())
+        .find(|fields| fields.len() >= 6 && fields[0] == name);
+    match entry {
+        Some(fields) => Ok(User {
+            ids: fields[2].parse().ok().zip(fields[3].parse().ok()),
+            home: PathBuf::from(fields[5]),
+            name,
+        }),
+        None => {
+            let home = PathBuf::from("/home").join(&name);
+            println!(
+                "Warning: {} is not in /etc/passwd, assuming {}",
+                name,
+                home.display()
+            );
+            Ok(User {
+                name,
+                ids: None,
+                home,
+            })
+        }
+    }
+}
+
+/// Copies the running binary into place. Replaced by renaming, since a
+/// running binary can't be overwritten.
+fn install_binary(root: &Path) -> Result<()> {
+    let source = env::current_exe().context("failed to find the running binary")?;
+    let target = under(root, BINARY_PATH);
+    if fs::canonicalize(&target).is_ok_and(|target| target == source) {
+        println!("{} is already in place", target.display());
+        return Ok(());
+    }
+    create_dir(target.parent().unwrap_or(root))?;
+    let tmp_path = target.with_extension("new");
+    fs::copy(&source, &tmp_path)
+        .with_context(|| format!("failed to copy the binary to {}", tmp_path.display()))?;
+    fs::set_permissions(&tmp_path, fs::Permissions::from_mode(0o755))?;
+    fs::rename(&tmp_path, &target)
+        .with_context(|| format!("failed to replace {}", target.display()))?;
+    println!("Installed {}", target.display());
+    Ok(())
+}
+
+/// Writes the default config unless the user has one, owned by the user so
+/// they can edit it.
+fn install_config(root: &Path, user: &User) -> Result<()> {
+    let home = under(root, &user.home);
+    let path = Config::path_in_home(&home);
+    if path.exists() {
+        println!("Kept the existing config {}", path.display());
+        return Ok(());
+    }
+    let parent = path.parent().unwrap_or(&home);
+    let missing: Vec<PathBuf> = parent
+        .ancestors()
+        .take_while(|dir| *dir != home && !dir.exists())
+        .map(Path::to_path_buf)
+        .collect();
+    create_dir(parent)?;
+    fs::write(&path, DEFAULT_CONFIG)
+        .with_context(|| format!("failed to write {}", path.display()))?;
+    println!("Created the default config {}", path.display());
+
+    if let Some((uid, gid)) = user.ids {
+        for created in missing.iter().map(PathBuf::as_path).chain([path.as_path()]) {
+            if let Err(e) = chown(created, Some(uid), Some(gid)) {
+                println!(
+                    "Warning: failed to hand {} to {}: {}",
+                    created.display(),
+                    user.name,
+                    e
+                );
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Whether an I2C bus is available, with a hint if it isn't.
+fn check_i2c(root: &Path) -> bool {
+    let mut buses: Vec<String> = fs::read_dir(under(root, "/dev"))
+        .map(|entries| {
+            entries
+                .filter_map(|entry| entry.ok())
+                .map(|entry| entry.file_name().to_string_lossy().to_string())
+                .filter(|name| name.starts_with("i2c-"))
+                .collect()
+        })
+        .unwrap_or_default();
+    buses.sort();
+    if !buses.is_empty() {
+        println!("I2C: found /dev/{}", buses.join(", /dev/"));
+        return true;
+    }
+
+    let enabled = BOOT_CONFIGS.iter().any(|boot_config| {
+        fs::read_to_string(under(root, boot_config)).is_ok_and(|content| {
+            content
+                .lines()
+                .any(|line| line.trim() == "dtparam=i2c_arm=on")
+        })
+    });
+    if enabled {
+        println!(
+            "Warning: I2C is enabled in config.txt but no /dev/i2c-* exists yet, reboot to load it"
+        );
+    } else {
+        println!("Warning: I2C is disabled, enable it with `sudo raspi-config nonint do_i2c 0` and reboot");
+    }
+    false
+}
+
+/// Whether `user` may open the I2C devices, with a hint if not.
+fn check_i2c_group(root: &Path, user: &User) -> bool {
+    let group = fs::read_to_string(under(root, "/etc/group")).unwrap_or_default();
+    // name:password:gid:member,member
+    let entry = group
+        .lines()
+        .map(|line| line.split(':').collect::<Vec<_>>())
+        .find(|fields| fields.len() >= 4 && fields[0] == "i2c");
+    let Some(fields) = entry else {
+        println!("Warning: there is no i2c group, enabling I2C creates it");
+        return false;
+    };
+    let primary = user
+        .ids
+        .is_some_and(|(_, gid)| fields[2].parse() == Ok(gid));
+    let member = fields[3].split(',').any(|member| member == user.name);
+    if primary || member || user.name == "root" {
+        println!("I2C: {} is in the i2c group", user.name);
+        return true;
+    }
+    println!(
+        "Warning: {} is not in the i2c group, add it with `sudo usermod -aG i2c {}`",
+        user.name, user.name
+    );
+    false
+}
+
+fn remove_file(path: &Path) -> Result<()> {
+    match fs::remove_file(path) {
+        Ok(()) => println!("Removed {}", path.display()),
+        Err(e) if e.kind() == ErrorKind::NotFound => {
+            println!("{} is not installed", path.display())
+        }
+        Err(e) => return Err(anyhow!(e).context(format!("failed to remove {}", path.display()))),
+    }
+    Ok(())
+}
+
+fn create_dir(dir: &Path) -> Result<()> {
+    fs::create_dir_all(dir).with_context(|| format!("failed to create {}", dir.display()))
+}
+
+fn systemctl(args: &[&str]) -> Result<()> {
+    let status = Command::new("systemctl")
+        .args(args)
+        .status()
+        .context("failed to run systemctl")?;
+    if !status.success() {
+        return Err(anyhow!("systemctl {} failed", args.join(" ")));
+    }
+    Ok(())
+}
+
+/// Whether `root` is the running system, also when spelled `//`, `/.` or
+/// through a symlink.
+fn is_system_root(root: &Path) -> bool {
+    fs::canonicalize(root).is_ok_and(|root| root == Path::new("/"))
+}
+
+/// `path` moved below `root`.
+fn under(root: &Path, path: impl AsRef<Path>) -> PathBuf {
+    let path = path.as_ref();
+    root.join(path.strip_prefix("/").unwrap_or(path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An empty directory to install into, removed again by the test.
+    fn test_root(name: &str) -> PathBuf {
+        let root =
+            env::temp_dir().join(format!("rustberry-install-{}-{}", std::process::id(), name));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(root.join("etc")).unwrap();
+        fs::write(
+            root.join("etc/passwd"),
+            "root:x:0:0:root:/root:/bin/bash\npi:x:1000:1000:,,,:/home/pi:/bin/bash\n",
+        )
+        .unwrap();
+        root
+    }
+
+    fn pi(root: &Path) -> User {
+        find_user(root, Some("pi".to_string())).unwrap()
+    }
+
+    #[test]
+    fn install_and_purge_below_a_root() {
+        let root = test_root("purge");
+        fs::write(root.join("etc/group"), "i2c:x:998:pi\n").unwrap();
+        fs::create_dir_all(root.join("dev")).unwrap();
+        fs::write(root.join("dev/i2c-1"), "").unwrap();
+
+        install(&root, Some("pi".to_string())).unwrap();
+        let unit = fs::read_to_string(root.join("etc/systemd/system").join(UNIT_NAME)).unwrap();
+        assert!(unit.contains("\nUser=pi\n"));
+        assert!(unit.contains("\nRuntimeDirectory=rustberry-poe-monitor\n"));
+        assert!(unit.contains("\nType=notify\n"));
+        assert!(unit.contains(&format!("\nExecStart={}\n", BINARY_PATH)));
+        let config_path = root.join("home/pi/.config/rustberry-poe-monitor/config.toml");
+        assert_eq!(fs::read_to_string(&config_path).unwrap(), DEFAULT_CONFIG);
+        assert!(root.join("usr/local/bin/rustberry-poe-monitor").is_file());
+        assert!(check_i2c(&root));
+        assert!(check_i2c_group(&root, &pi(&root)));
+
+        // A second install keeps the edited config.
+        fs::write(&config_path, "[display]\n").unwrap();
+        install(&root, Some("pi".to_string())).unwrap();
+        assert_eq!(fs::read_to_string(&config_path).unwrap(), "[display]\n");
+
+        uninstall(&root, Some("pi".to_string()), true).unwrap();
+        assert!(!root.join("etc/systemd/system").join(UNIT_NAME).exists());
+        assert!(!root.join("usr/local/bin/rustberry-poe-monitor").exists());
+        assert!(!root.join("home/pi/.config/rustberry-poe-monitor").exists());
+        assert!(root.join("home/pi/.config").exists());
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn uninstall_without_purge_keeps_the_config() {
+        let root = test_root("keep");
+        install(&root, Some("pi".to_string())).unwrap();
+        uninstall(&root, Some("pi".to_string()), false).unwrap();
+        assert!(!root.join("etc/systemd/system").join(UNIT_NAME).exists());
+        assert!(root
+            .join("home/pi/.config/rustberry-poe-monitor/config.toml")
+            .exists());
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn i2c_checks_warn_about_a_missing_bus_and_group() {
+        let root = test_root("i2c");
+        // Enabled, but not rebooted yet.
+        fs::create_dir_all(root.join("boot/firmware")).unwrap();
+        fs::write(
+            root.join("boot/firmware/config.txt"),
+            "dtparam=i2c_arm=on\n",
+        )
+        .unwrap();
+        assert!(!check_i2c(&root));
+        assert!(!check_i2c_group(&root, &pi(&root)));
+
+        fs::write(root.join("etc/group"), "i2c:x:998:\n").unwrap();
+        assert!(!check_i2c_group(&root, &pi(&root)));
+        let root_user = find_user(&root, Some("root".to_string())).unwrap();
+        assert!(check_i2c_group(&root, &root_user));
+
+        // Members through their primary group count too.
+        fs::write(root.join("etc/group"), "i2c:x:1000:\n").unwrap();
+        assert!(check_i2c_group(&root, &pi(&root)));
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn system_root_is_recognized_however_it_is_spelled() {
+        assert!(is_system_root(Path::new("/")));
+        assert!(is_system_root(Path::new("//")));
+        assert!(is_system_root(Path::new("/.")));
+        assert!(is_system_root(Path::new("/tmp/..")));
+        assert!(!is_system_root(&env::temp_dir()));
+        assert!(!is_system_root(Path::new("/no/such/root")));
+    }
+}
@@ -0,0 +1,313 @@
+use crate::config::Overflow;
+use embedded_graphics::{
+    mono_font::{MonoFont, MonoTextStyle},
+    pixelcolor::BinaryColor,
+    prelude::*,
+    primitives::Rectangle,
+    text::Text,
+};
+
+/// Width of the three-dot ellipsis drawn after truncated text, including
+/// the gap to the last character.
+const ELLIPSIS_WIDTH: i32 = 6;
+
+/// Blank space between the end of a scrolling text and its next repetition.
+const MARQUEE_GAP: i32 = 16;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Align {
+    Left,
+    Center,
+    Right,
+}
+
+/// Horizontal span on a text line that a field has to fit in.
+#[derive(Debug, Clone, Copy)]
+pub struct Slot {
+    pub left: i32,
+    pub right: i32,
+    pub baseline: i32,
+    pub align: Align,
+}
+
+impl Slot {
+    pub fn new(left: i32, right: i32, baseline: i32, align: Align) -> Self {
+        Slot {
+            left,
+            right,
+            baseline,
+            align,
+        }
+    }
+
+    pub fn width(&self) -> i32 {
+        self.right - self.left
+    }
+
+    pub fn translate(self, offset: Point) -> Self {
+        Slot {
+            left: self.left + offset.x,
+            right: self.right + offset.x,
+            baseline: self.baseline + offset.y,
+            ..self
+        }
+    }
+
+    /// X position where text of the given width starts.
+    pub fn start_for(&self, width: i32) -> i32 {
+        match self.align {
+            Align::Left => self.left,
+            Align::Center => self.left + (self.width() - width) / 2,
+            Align::Right => self.right - width,
+        }
+    }
+}
+
+/// Width in pixels of `text` rendered in `font`.
+pub fn text_width(text: &str, font: &MonoFont) -> i32 {
+    let chars = text.chars().count() as i32;
+    if chars == 0 {
+        return 0;
+    }
+    let advance = (font.character_size.width + font.character_spacing) as i32;
+    chars * advance - font.character_spacing as i32
+}
+
+/// Breaks `text` into lines no wider than `width`, between words where
+/// possible and inside words longer than a line.
+pub fn wrap_text(text: &str, font: &MonoFont, width: i32) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut line = String::new();
+    for word in text.split_whitespace() {
+        let joined = if line.is_empty() {
+            word.to_string()
+        } else {
+            format!("{} {}", line, word)
+        };
+        if text_width(&joined, font) <= width {
+            line = joined;
+            continue;
+        }
+        if !line.is_empty() {
+            lines.push(std::mem::take(&mut line));
+        }
+        for c in word.chars() {
+            line.push(c);
+            if line.chars().count() > 1 && text_width(&line, font) > width {
+                line.pop();
+                lines.push(std::mem::take(&mut line));
+                line.push(c);
+            }
+        }
+    }
+    if !line.is_empty() {
+        lines.push(line);
+    }
+    lines
+}
+
+/// Draws `text` into `slot` using `font`, handling text that doesn't fit
+/// according to `overflow`. Shrinking tries the `narrower` fonts in order.
+/// `scroll` is the marquee position in pixels. Returns the point right after
+/// the slot's text, like `Text::draw`.
+pub fn draw_text<D>(
+    disp: &mut D,
+    text: &str,
+    slot: Slot,
+    font: &MonoFont,
+    narrower: &[&MonoFont],
+    overflow: Overflow,
+    scroll: u32,
+) -> Result<Point, D::Error>
+where
+    D: DrawTarget<Color = BinaryColor>,
+{
+    if text_width(text, font) <= slot.width() {
+        return draw_aligned(disp, text, slot, font);
+    }
+
+    match overflow {
+        Overflow::Truncate => draw_truncated(disp, text, slot, font),
+        Overflow::Shrink => {
+            match narrower
+                .iter()
+                .find(|font| text_width(text, font) <= slot.width())
+            {
+                Some(font) => draw_aligned(disp, text, slot, font),
+                None => {
+                    let smallest = narrower.last().copied().unwrap_or(font);
+                    draw_truncated(disp, text, slot, smallest)
+                }
+            }
+        }
+        Overflow::Marquee => draw_marquee(disp, text, slot, font, scroll),
+    }
+}
+
+fn draw_aligned<D>(disp: &mut D, text: &str, slot: Slot, font: &MonoFont) -> Result<Point, D::Error>
+where
+    D: DrawTarget<Color = BinaryColor>,
+{
+    let style = MonoTextStyle::new(font, BinaryColor::On);
+    let x = slot.start_for(text_width(text, font));
+    Text::new(text, Point::new(x, slot.baseline), style).draw(disp)
+}
+
+/// Cuts `text` off so that it fits together with a trailing ellipsis.
+fn draw_truncated<D>(
+    disp: &mut D,
+    text: &str,
+    slot: Slot,
+    font: &MonoFont,
+) -> Result<Point, D::Error>
+where
+    D: DrawTarget<Color = BinaryColor>,
+{
+    let mut end = 0;
+    for (index, c) in text.char_indices() {
+        let next = index + c.len_utf8();
+        if text_width(&text[..next], font) + ELLIPSIS_WIDTH > slot.width() {
+            break;
+        }
+        end = next;
+    }
+
+    let visible = &text[..end];
+    let width = text_width(visible, font) + ELLIPSIS_WIDTH;
+    let x = slot.start_for(width.min(slot.width()));
+    let style = MonoTextStyle::new(font, BinaryColor::On);
+    let next = Text::new(visible, Point::new(x, slot.baseline), style).draw(disp)?;
+
+    // The fonts have no ellipsis glyph, so draw three single-pixel dots
+    // on the baseline.
+    let dots = (0..3).map(|i| {
+        Pixel(
+            Point::new(next.x + 1 + i * 2, slot.baseline),
+            BinaryColor::On,
+        )
+    });
+    disp.draw_iter(dots)?;
+    Ok(Point::new(next.x + ELLIPSIS_WIDTH, next.y))
+}
+
+/// Scrolls `text` through the slot, repeating it after a short gap.
+fn draw_marquee<D>(
+    disp: &mut D,
+    text: &str,
+    slot: Slot,
+    font: &MonoFont,
+    scroll: u32,
+) -> Result<Point, D::Error>
+where
+    D: DrawTarget<Color = BinaryColor>,
+{
+    let period = text_width(text, font) + MARQUEE_GAP;
+    let x = slot.left - (scroll % period as u32) as i32;
+    let area = Rectangle::new(
+        Point::new(slot.left, slot.baseline - font.baseline as i32),
+        Size::new(slot.width().max(0) as u32, font.character_size.height),
+    );
+
+    let style = MonoTextStyle::new(font, BinaryColor::On);
+    let mut clipped = disp.clipped(&area);
+    Text::new(text, Point::new(x, slot.baseline), style).draw(&mut clipped)?;
+    Text::new(text, Point::new(x + period, slot.baseline), style).draw(&mut clipped)?;
+    Ok(Point::new(slot.right, slot.baseline))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::framebuffer::Framebuffer;
+    use embedded_graphics::mono_font::ascii::{FONT_5X8, FONT_6X10};
+
+    #[test]
+    fn text_width_counts_characters() {
+        assert_eq!(text_width("", &FONT_6X10), 0);
+        assert_eq!(text_width("abc", &FONT_6X10), 18);
+        assert_eq!(text_width("äöü", &FONT_6X10), 18);
+        // No spacing after the last character.
+        let spaced = MonoFont {
+            character_spacing: 1,
+            ..FONT_6X10
+        };
+        assert_eq!(text_width("ab", &spaced), 13);
+    }
+
+    #[test]
+    fn wrap_text_breaks_between_words() {
+        // 11 characters per line.
+        assert_eq!(
+            wrap_text("hello world  and\tmore", &FONT_6X10, 66),
+            ["hello world", "and more"]
+        );
+        assert!(wrap_text("   ", &FONT_6X10, 66).is_empty());
+    }
+
+    #[test]
+    fn wrap_text_splits_words_longer_than_a_line() {
+        assert_eq!(
+            wrap_text("hi abcdefghijkl", &FONT_6X10, 30),
+            ["hi", "abcde", "fghij", "kl"]
+        );
+        assert_eq!(wrap_text("äöüßäöüß", &FONT_6X10, 18), ["äöü", "ßäö", "üß"]);
+        // Narrower than one character: still one per line.
+        assert_eq!(wrap_text("ab", &FONT_6X10, 3), ["a", "b"]);
+    }
+
+    fn draw(text: &str, width: i32, narrower: &[&MonoFont], overflow: Overflow) -> Point {
+        let mut framebuffer = Framebuffer::new(128, 16);
+        let slot = Slot::new(0, width, 10, Align::Left);
+        draw_text(
+            &mut framebuffer,
+            text,
+            slot,
+            &FONT_6X10,
+            narrower,
+            overflow,
+            0,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn truncated_text_ends_with_an_ellipsis() {
+        let mut framebuffer = Framebuffer::new(128, 16);
+        let slot = Slot::new(0, 30, 10, Align::Left);
+        let next = draw_truncated(&mut framebuffer, "abcdefgh", slot, &FONT_6X10).unwrap();
+        // Four characters and the dots fill the 30 pixels.
+        assert_eq!(next, Point::new(30, 10));
+        let dots: Vec<(u32, u32)> = framebuffer.lit_pixels().filter(|(x, _)| *x >= 24).collect();
+        assert_eq!(dots, [(25, 10), (27, 10), (29, 10)]);
+
+        // Multibyte characters are cut between characters.
+        assert_eq!(
+            draw("äöüßäöüß", 30, &[], Overflow::Truncate),
+            Point::new(30, 10)
+        );
+        // Even a slot too narrow for a single character.
+        assert_eq!(draw("abc", 4, &[], Overflow::Truncate), Point::new(6, 10));
+    }
+
+    #[test]
+    fn shrink_tries_the_narrower_fonts() {
+        assert_eq!(
+            draw("abcdefgh", 48, &[&FONT_5X8], Overflow::Shrink),
+            Point::new(48, 10)
+        );
+        // 5x8 fits 40 pixels exactly.
+        assert_eq!(
+            draw("abcdefgh", 40, &[&FONT_5X8], Overflow::Shrink),
+            Point::new(40, 10)
+        );
+        // Nothing fits: truncated in the narrowest font.
+        assert_eq!(
+            draw("abcdefgh", 31, &[&FONT_5X8], Overflow::Shrink),
+            Point::new(31, 10)
+        );
+        assert_eq!(
+            draw("abcdefgh", 30, &[], Overflow::Shrink),
+            Point::new(30, 10)
+        );
+    }
+}
@@ -1,49 +1,218 @@
-use embedded_graphics::prelude::Point;
+use clap::Parser;
 use env_logger::{Builder, Env};
-use log::{debug, info, trace};
-use ssd1306::prelude::Brightness;
+use log::{debug, info, trace, warn};
 use std::error::Error;
 use std::fs;
 use std::process::Command;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::sync::Arc;
 use std::thread;
 use std::time::{Duration, Instant};
 use sysinfo::{CpuRefreshKind, Disks, MemoryRefreshKind, RefreshKind, System};
 
 mod fan_controller;
-use fan_controller::FanController;
+use fan_controller::{FanController, FanMode};
 
 mod config;
-use config::Config;
+use config::{BrightnessSetting, Config, FormatConfig, Page, RamUsed};
 
 mod display;
-use display::PoeDisplay;
+use display::{BrightnessMode, Overview, PoeDisplay, Screen};
 
 mod display_types;
 
+mod framebuffer;
+
+mod panel;
+
+mod sh1106;
+
+mod layout;
+
+mod fonts;
+
+mod icons;
+
+mod units;
+use units::{Reading, Unit};
+
+mod burn_in;
+use burn_in::{InverseRefresh, PixelShifter, PixelUsage};
+
+mod alerts;
+use alerts::{AlertInputs, AlertKind, AlertManager};
+
+mod notifier;
+
+mod cli;
+use cli::{Cli, Commands};
+
+mod control;
+use control::{ControlCommand, ControlServer};
+
+mod messages;
+use messages::{MessageQueue, Priority};
+
+mod pi_health;
+use pi_health::{PiHealth, PiHealthMonitor};
+
+mod status;
+use status::{StatusFile, StatusReport};
+
+mod web;
+use web::{Dashboard, Frame, Snapshot, WebServer};
+
+mod schedule;
+use config::ScheduleDisplay;
+use schedule::{Schedule, ScheduleState};
+
+mod cpu_info;
+use cpu_info::CpuInfo;
+
+mod pages;
+use pages::PageRotation;
+
+mod system_info;
+use system_info::SystemInfo;
+
+mod processes;
+use processes::top_processes;
+
+mod services;
+use services::{ServiceWatch, Systemctl};
+
+mod containers;
+use containers::ContainerMonitor;
+
+mod checks;
+use checks::Checks;
+
+mod memory;
+use memory::MemoryInfo;
+
+mod sd_notify;
+use sd_notify::SdNotify;
+
+mod install;
+
+mod diagnose;
+
 struct AppState {
-    last_shift_time: Instant,
-    shift_index: usize,
-    shift_offset: Point,
     last_periodic_toggle_time: Instant,
     is_display_periodically_on: bool,
     screen_dimmed: bool,
+    last_activity: Instant,
+    wake_button_down: bool,
+    schedule_display: ScheduleDisplay,
+    /// Turned off from the dashboard or control socket until the next wake
+    /// event.
+    display_off_by_user: bool,
 }
 
+/// How often the wake button is polled while waiting for the next refresh.
+const BUTTON_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Time between the two CPU refreshes of a one-shot `status`, CPU usage is
+/// the difference between them.
+const CPU_SAMPLE_TIME: Duration = Duration::from_millis(500);
+
+/// Shown in place of the IP address until the network is up.
+const WAITING_FOR_NETWORK: &str = "Waiting for network";
+
+/// Shown in place of the temperature while the sensor can't be read.
+const SENSOR_ERROR: &str = "ERR";
+
 struct SystemStats {
-    ip_address: String,
-    cpu_usage: String,
-    cpu_temp: f32,
-    cpu_temp_str: String,
-    ram_usage: String,
-    disk_usage: String,
+    hostname: String,
+    ip_address: Option<String>,
+    cpu_usage: f32,
+    cpu_temp: Option<f32>,
+    /// By the configured definition of used.
+    ram_used: u64,
+    memory: MemoryInfo,
+    disk_usage: f64,
+    disk_used: u64,
+    disk_total: u64,
+    pi_health: PiHealth,
+}
+
+/// The values of `SystemStats` formatted for people, shared by every output.
+struct Readings {
+    cpu: Reading,
+    temp: Option<Reading>,
+    ram: Reading,
+    disk: Reading,
+}
+
+impl SystemStats {
+    fn readings(&self, format: &FormatConfig) -> Readings {
+        Readings {
+            cpu: units::format_percent(self.cpu_usage as f64, &format.cpu),
+            temp: self
+                .cpu_temp
+                .map(|temp| units::format_temp(temp, &format.temp)),
+            ram: units::format_size(self.ram_used, self.memory.total, &format.ram),
+            disk: units::format_size(self.disk_used, self.disk_total, &format.disk),
+        }
+    }
 }
 
 fn main() -> Result<(), Box<dyn Error>> {
+    let cli = Cli::parse();
+
     let env = Env::default().default_filter_or("info");
     Builder::from_env(env).init();
 
+    // These run as root, before the service user has a config.
+    match cli.command {
+        Some(Commands::Install { root, user }) => return Ok(install::install(&root, user)?),
+        Some(Commands::Uninstall { root, user, purge }) => {
+            return Ok(install::uninstall(&root, user, purge)?)
+        }
+        _ => {}
+    }
+
     let config = Config::load()?;
 
+    match cli.command {
+        Some(Commands::TestAlert) => Ok(notifier::send_test(&config.alerts)?),
+        Some(Commands::BurnInReport) => Ok(burn_in::print_report(
+            &config.display.burn_in.usage_file_path(),
+        )?),
+        Some(Commands::Status { json }) => print_status(&config, json),
+        Some(Commands::Diagnose) => Ok(diagnose::run(&config)?),
+        Some(Commands::Wake) => {
+            control::send(&config.control.socket, &ControlCommand::Wake)?;
+            println!("Display woken");
+            Ok(())
+        }
+        Some(Commands::Notify {
+            text,
+            duration,
+            priority,
+            id,
+        }) => {
+            let command = ControlCommand::Notify {
+                text,
+                priority,
+                duration: duration.map(|duration| duration.as_secs()),
+                id,
+            };
+            control::send(&config.control.socket, &command)?;
+            println!("Message sent");
+            Ok(())
+        }
+        Some(Commands::Clear { id }) => {
+            control::send(&config.control.socket, &ControlCommand::Clear { id })?;
+            println!("Messages cleared");
+            Ok(())
+        }
+        Some(Commands::Install { .. } | Commands::Uninstall { .. }) => unreachable!(),
+        None => run_monitor(config),
+    }
+}
+
+fn run_monitor(config: Config) -> Result<(), Box<dyn Error>> {
     let version = env!("CARGO_PKG_VERSION");
 
     debug!("Binary info:");
@@ -54,19 +223,66 @@ fn main() -> Result<(), Box<dyn Error>> {
     debug!("Target Architecture:     {}", std::env::consts::ARCH);
     debug!("Config loaded: {:?}", config);
 
-    let mut poe_disp = PoeDisplay::new(&config.display)?;
+    let mut poe_disp = PoeDisplay::new(&config.display).map_err(|e| {
+        format!(
+            "Failed to open the display: {} (`rustberry-poe-monitor diagnose` checks the hardware)",
+            e
+        )
+    })?;
+    if config.display.splash_duration > 0 {
+        poe_disp
+            .show_splash(config.display.splash_logo.as_deref())
+            .map_err(|e| format!("Display update error: {:?}", e))?;
+    }
+    let splash_shown = Instant::now();
+
+    let mut sd_notify = SdNotify::from_env();
+    let result = monitor(&config, &mut poe_disp, splash_shown, &mut sd_notify);
+    if let Err(e) = &result {
+        if let Some(sd_notify) = sd_notify.as_mut() {
+            sd_notify.status(&format!("Error: {}", e));
+        }
+        // Leave the reason on the panel, there may be no one reading the log.
+        if let Err(display_error) = poe_disp.show_message("Error", &e.to_string()) {
+            warn!(
+                "Failed to show the error on the display: {:?}",
+                display_error
+            );
+        }
+    }
+    result
+}
+
+/// Sets up everything after the display and runs the main loop. Errors end
+/// up on the display.
+fn monitor(
+    config: &Config,
+    poe_disp: &mut PoeDisplay,
+    splash_shown: Instant,
+    sd_notify: &mut Option<SdNotify>,
+) -> Result<(), Box<dyn Error>> {
+    let schedule = Schedule::new(&config.schedule)?;
+
+    if let Some(pin) = config.display.wake_pin {
+        if !(1..=7).contains(&pin) {
+            return Err(format!("Invalid wake_pin p{}, expected a free pin p1-p7", pin).into());
+        }
+    }
 
-    let mut fan_controller = FanController::new(config.fan.temp_on, config.fan.temp_off)?;
+    let mut fan_controller = FanController::new(config.fan.temp_on, config.fan.temp_off)
+        .map_err(|e| format!("Failed to set up the fan: {:#}", e))?;
     info!(
         "Fan controller initialized. temp-on: {}, temp-off: {}",
         fan_controller.temp_on, fan_controller.temp_off
     );
+    if config.fan.safety_temp < fan_controller.temp_on {
+        warn!(
+            "Fan safety-temp {} is below temp-on {}, schedules can't raise the fan threshold",
+            config.fan.safety_temp, fan_controller.temp_on
+        );
+    }
 
-    let mut sys: System = System::new_with_specifics(
-        RefreshKind::nothing()
-            .with_cpu(CpuRefreshKind::nothing().with_cpu_usage())
-            .with_memory(MemoryRefreshKind::nothing().with_ram()),
-    );
+    let mut sys = new_system();
 
     debug!("System initialized. System info:");
     debug!("================================");
@@ -86,82 +302,596 @@ fn main() -> Result<(), Box<dyn Error>> {
     info!("Starting main loop");
 
     fan_controller.fan_off()?;
+    if let Some(sd_notify) = sd_notify.as_mut() {
+        sd_notify.ready("Starting");
+    }
 
     let screen_timeout_duration = config.display_timeout();
     let periodic_on_duration = config.periodic_on_duration();
     let periodic_off_duration = config.periodic_off_duration();
-    let shift_interval = Duration::from_secs(60);
-    let shift_pattern = [Point::new(0, 0), Point::new(1, 0)];
     let refresh_interval = config.refresh_interval();
 
+    let burn_in_config = &config.display.burn_in;
+    let mut pixel_shifter = PixelShifter::new(burn_in_config, Instant::now());
+    let inverse_refresh = InverseRefresh::new(burn_in_config, Instant::now());
+    let mut pixel_usage = if burn_in_config.track_pixels {
+        let frame = poe_disp.framebuffer();
+        Some(PixelUsage::load_or_new(
+            &burn_in_config.usage_file_path(),
+            frame.width(),
+            frame.height(),
+            Instant::now(),
+        ))
+    } else {
+        None
+    };
+
     let mut app_state = AppState {
-        last_shift_time: Instant::now(),
-        shift_index: 0,
-        shift_offset: Point::new(0, 0),
         last_periodic_toggle_time: Instant::now(),
         is_display_periodically_on: true,
         screen_dimmed: false,
+        last_activity: Instant::now(),
+        wake_button_down: false,
+        schedule_display: ScheduleDisplay::On,
+        display_off_by_user: false,
+    };
+
+    let (event_sender, events) = mpsc::channel();
+    spawn_signal_listener(event_sender.clone())?;
+    let _control_server = if config.control.enabled {
+        match ControlServer::start(&config.control, event_sender.clone()) {
+            Ok(server) => Some(server),
+            Err(e) => {
+                warn!("Control socket disabled: {:#}", e);
+                None
+            }
+        }
+    } else {
+        None
+    };
+
+    let dashboard = config.web.enabled.then(|| Arc::new(Dashboard::default()));
+    let _web_server = match &dashboard {
+        Some(dashboard) => {
+            match WebServer::start(&config.web, Arc::clone(dashboard), event_sender) {
+                Ok(server) => Some(server),
+                Err(e) => {
+                    warn!("Dashboard disabled: {:#}", e);
+                    None
+                }
+            }
+        }
+        None => None,
+    };
+
+    let mut alert_manager = AlertManager::new(config.alerts.clone())?;
+    let mut messages = MessageQueue::default();
+    let mut alert_messages = Vec::new();
+    let mut service_watch = ServiceWatch::new(
+        Box::new(Systemctl),
+        config.services.units.clone(),
+        config.services.interval(),
+    );
+    let container_monitor = if config.containers.enabled {
+        match ContainerMonitor::start(&config.containers) {
+            Ok(monitor) => Some(monitor),
+            Err(e) => {
+                warn!("Container monitoring disabled: {:#}", e);
+                None
+            }
+        }
+    } else {
+        None
     };
+    let checks = Checks::start(&config.check)?;
+    let pi_health = PiHealthMonitor::start()?;
+    let mut page_rotation = PageRotation::new(
+        &config.display.pages,
+        config.page_interval(),
+        Instant::now(),
+    );
+    let mut fan_mode = FanMode::default();
+    let mut sensor_failed = false;
+    let mut status_file = config.status.file.clone().map(StatusFile::new);
 
-    let start_time = Instant::now();
+    thread::sleep(
+        config
+            .splash_duration()
+            .saturating_sub(splash_shown.elapsed()),
+    );
 
     loop {
         let now = Instant::now();
+        let schedule_state = schedule.now();
 
-        handle_screen_timeout(
-            start_time,
-            now,
-            screen_timeout_duration,
-            &mut app_state,
-            &mut poe_disp,
-        )?;
+        handle_schedule(&schedule_state, &mut app_state, poe_disp)?;
+
+        handle_screen_timeout(now, screen_timeout_duration, &mut app_state, poe_disp)?;
 
         handle_periodic_display(
-            &config,
+            config,
             now,
             periodic_on_duration,
             periodic_off_duration,
             &mut app_state,
-            &mut poe_disp,
+            poe_disp,
         )?;
 
-        update_pixel_shift(now, shift_interval, &shift_pattern, &mut app_state);
+        let shift_offset = pixel_shifter.update(now);
+        poe_disp.set_inverted(inverse_refresh.is_inverted(now));
 
-        let stats = gather_stats(&mut sys);
+        let stats = gather_stats(&mut sys, config.display.ram_used, pi_health.current());
 
-        handle_fan_control(&mut fan_controller, stats.cpu_temp)?;
+        let fan_temp_on = schedule_state
+            .fan_temp_on
+            .map_or(fan_controller.temp_on, |t| {
+                t.max(fan_controller.temp_on).min(config.fan.safety_temp)
+            });
+        // An unknown temperature counts as hot, so the fan keeps cooling.
+        let fan_temp = match stats.cpu_temp {
+            Some(temp) => {
+                sensor_failed = false;
+                temp
+            }
+            None => {
+                if !sensor_failed {
+                    warn!(
+                        "CPU temperature unknown, running the fan as at the safety temperature {}",
+                        config.fan.safety_temp
+                    );
+                    sensor_failed = true;
+                }
+                config.fan.safety_temp
+            }
+        };
+        handle_fan_control(
+            &mut fan_controller,
+            fan_mode,
+            fan_temp,
+            fan_temp_on,
+            config.fan.safety_temp,
+        )?;
 
-        if app_state.is_display_periodically_on {
+        service_watch.poll(now);
+        if let Some(status_file) = status_file.as_mut() {
+            let fan_running = Some(fan_controller.is_running);
+            let report = StatusReport::new(&stats, config, fan_running, &service_watch);
+            status_file.update(&report);
+        }
+
+        let failed_units = service_watch.failed_units();
+        let check_results = checks.results();
+        let failed_checks: Vec<String> = check_results
+            .iter()
+            .filter(|result| result.is_failed())
+            .map(|result| result.name.clone())
+            .collect();
+        let container_summary = container_monitor
+            .as_ref()
+            .map(ContainerMonitor::summary)
+            .unwrap_or_default();
+        // Without the API the container states are unknown, not fine.
+        let containers_known = container_summary.error.is_none();
+        let unhealthy_containers = container_summary.unhealthy();
+        let restart_loop_containers = container_summary.restart_loops();
+        let fired_alerts = alert_manager.evaluate(
+            now,
+            &AlertInputs {
+                cpu_temp: stats.cpu_temp,
+                disk_usage: stats.disk_usage,
+                fan_running: fan_controller.is_running,
+                under_voltage: stats.pi_health.under_voltage(),
+                failed_units: &failed_units,
+                unhealthy_containers: containers_known.then_some(&unhealthy_containers),
+                restart_loop_containers: containers_known.then_some(&restart_loop_containers),
+                failed_checks: &failed_checks,
+            },
+        );
+        if config.display.wake_on_alert && !fired_alerts.is_empty() {
+            handle_wake(now, &mut app_state, poe_disp)?;
+        }
+        handle_alert_messages(
+            now,
+            &fired_alerts,
+            &alert_manager,
+            &mut alert_messages,
+            &mut messages,
+        );
+
+        let readings = stats.readings(&config.format);
+        let message = messages.current(now);
+        let display_on = display_visible(&app_state);
+        let page = page_rotation.current(now);
+        if display_on {
+            let temp = readings.temp.clone().unwrap_or_else(|| Reading {
+                value: SENSOR_ERROR.to_string(),
+                unit: Unit::None,
+            });
+            debug!(
+                "CPU {}, temperature {}, RAM {}, disk {}",
+                readings.cpu, temp, readings.ram, readings.disk
+            );
+            let cpu_info;
+            let system_info;
+            let processes;
+            let screen = match page {
+                Page::Overview => Screen::Overview(Overview {
+                    hostname: &stats.hostname,
+                    ip_address: stats.ip_address.as_deref().unwrap_or(WAITING_FOR_NETWORK),
+                    cpu_usage: &readings.cpu,
+                    temp: &temp,
+                    ram_usage: &readings.ram,
+                    disk_usage: &readings.disk,
+                    fan_running: fan_controller.is_running,
+                    alert_active: alert_manager.has_active(),
+                    under_voltage: stats.pi_health.under_voltage(),
+                }),
+                Page::Cpu => {
+                    cpu_info = CpuInfo::read(&sys);
+                    Screen::Cpu(&cpu_info)
+                }
+                Page::System => {
+                    system_info = SystemInfo::read();
+                    Screen::System(&system_info)
+                }
+                Page::Processes => {
+                    let sort = config.display.process_sort;
+                    processes = top_processes(&mut sys, sort, config.display.process_count);
+                    Screen::Processes(&processes, sort)
+                }
+                Page::Services => Screen::Services(service_watch.statuses()),
+                Page::Containers => {
+                    Screen::Containers(&container_summary, page_rotation.shown_for(now))
+                }
+                Page::Checks => Screen::Checks(&check_results),
+                Page::Memory => Screen::Memory(&stats.memory, config.display.ram_used),
+            };
             poe_disp
-                .update(
-                    &stats.ip_address,
-                    stats.cpu_usage,
-                    stats.cpu_temp_str,
-                    stats.ram_usage,
-                    &stats.disk_usage,
-                    app_state.shift_offset,
-                )
+                .update(&screen, message, shift_offset)
                 .map_err(|e| format!("Display update error: {:?}", e))?;
+            if let Some(usage) = pixel_usage.as_mut() {
+                usage.record(poe_disp.framebuffer(), now);
+            }
+        } else if let Some(usage) = pixel_usage.as_mut() {
+            usage.pause();
+        }
+
+        if let Some(dashboard) = &dashboard {
+            dashboard.publish(Snapshot {
+                hostname: stats.hostname.clone(),
+                ip_address: stats.ip_address.clone(),
+                cpu: readings.cpu.to_string(),
+                temp: readings.temp.as_ref().map(ToString::to_string),
+                ram: readings.ram.to_string(),
+                disk: readings.disk.to_string(),
+                fan_running: fan_controller.is_running,
+                fan_mode,
+                display_on,
+                alert_active: alert_manager.has_active(),
+                failed_units: service_watch.failed_count(),
+                message: message.map(str::to_string),
+                frame: Frame::new(poe_disp.framebuffer()),
+            });
+        }
+
+        if let Some(sd_notify) = sd_notify.as_mut() {
+            let temp = readings
+                .temp
+                .as_ref()
+                .map_or(SENSOR_ERROR.to_string(), |t| t.to_string());
+            let fan = if fan_controller.is_running {
+                "on"
+            } else {
+                "off"
+            };
+            let status = format!(
+                "CPU {}, {}, RAM {}, fan {}",
+                readings.cpu, temp, readings.ram, fan
+            );
+            sd_notify.update(now, &status);
+        }
+
+        let commands = wait_for_events(
+            refresh_interval,
+            &events,
+            config.display.wake_pin,
+            &mut app_state,
+            &mut fan_controller,
+            poe_disp,
+            sd_notify,
+        )?;
+        for command in commands {
+            let now = Instant::now();
+            match command {
+                ControlCommand::Wake => handle_wake(now, &mut app_state, poe_disp)?,
+                ControlCommand::Notify {
+                    text,
+                    priority,
+                    duration,
+                    id,
+                } => {
+                    if priority == Priority::High {
+                        handle_wake(now, &mut app_state, poe_disp)?;
+                    }
+                    let duration = duration.map(Duration::from_secs);
+                    messages.push(now, text, priority, duration, id);
+                }
+                ControlCommand::Clear { id } => messages.clear(id.as_deref()),
+                ControlCommand::Display { on: true } => handle_wake(now, &mut app_state, poe_disp)?,
+                ControlCommand::Display { on: false } => {
+                    info!("Display turned OFF by request");
+                    app_state.display_off_by_user = true;
+                    poe_disp
+                        .display_off()
+                        .map_err(|e| format!("Failed to turn display OFF: {:?}", e))?;
+                }
+                ControlCommand::Brightness { level } => {
+                    info!("Active brightness set to {} by request", level);
+                    poe_disp.set_active_brightness(BrightnessSetting::Level(level));
+                    handle_wake(now, &mut app_state, poe_disp)?;
+                    poe_disp
+                        .set_brightness_mode(brightness_mode(&app_state))
+                        .map_err(|e| format!("Failed to change brightness: {:?}", e))?;
+                }
+                ControlCommand::Fan { mode } => {
+                    info!("Fan mode set to {:?} by request", mode);
+                    fan_mode = mode;
+                }
+                ControlCommand::Shutdown => {
+                    if let Some(sd_notify) = sd_notify.as_mut() {
+                        sd_notify.stopping();
+                    }
+                    // Saves the usage data, the signal would have skipped
+                    // the destructors.
+                    drop(pixel_usage);
+                    return Ok(());
+                }
+            }
+        }
+    }
+}
+
+fn print_status(config: &Config, json: bool) -> Result<(), Box<dyn Error>> {
+    let mut sys = new_system();
+    thread::sleep(CPU_SAMPLE_TIME.max(sysinfo::MINIMUM_CPU_UPDATE_INTERVAL));
+    let stats = gather_stats(&mut sys, config.display.ram_used, PiHealth::read());
+    let mut service_watch = ServiceWatch::new(
+        Box::new(Systemctl),
+        config.services.units.clone(),
+        config.services.interval(),
+    );
+    service_watch.poll(Instant::now());
+
+    let report = StatusReport::new(&stats, config, None, &service_watch);
+    if json {
+        println!("{}", serde_json::to_string_pretty(&report)?);
+    } else {
+        report.print(&config.format);
+    }
+    Ok(())
+}
+
+fn new_system() -> System {
+    System::new_with_specifics(
+        RefreshKind::nothing()
+            .with_cpu(CpuRefreshKind::nothing().with_cpu_usage())
+            .with_memory(MemoryRefreshKind::nothing().with_ram().with_swap()),
+    )
+}
+
+fn spawn_signal_listener(sender: Sender<ControlCommand>) -> Result<(), Box<dyn Error>> {
+    use signal_hook::consts::{SIGINT, SIGTERM, SIGUSR1};
+    let mut signals = signal_hook::iterator::Signals::new([SIGUSR1, SIGTERM, SIGINT])?;
+    thread::Builder::new()
+        .name("signals".to_string())
+        .spawn(move || {
+            for signal in signals.forever() {
+                let command = if signal == SIGUSR1 {
+                    info!("SIGUSR1 received");
+                    ControlCommand::Wake
+                } else {
+                    info!("Signal {} received, stopping", signal);
+                    ControlCommand::Shutdown
+                };
+                if sender.send(command).is_err() {
+                    break;
+                }
+            }
+        })?;
+    Ok(())
+}
+
+/// Waits until the next refresh is due, returning early when a command or a
+/// press of the wake button arrives. Steps a running fade and keeps pinging
+/// the systemd watchdog meanwhile.
+fn wait_for_events(
+    interval: Duration,
+    events: &Receiver<ControlCommand>,
+    wake_pin: Option<u8>,
+    state: &mut AppState,
+    fan_controller: &mut FanController,
+    poe_disp: &mut PoeDisplay,
+    sd_notify: &mut Option<SdNotify>,
+) -> Result<Vec<ControlCommand>, Box<dyn Error>> {
+    let deadline = Instant::now() + interval;
+    let mut commands = Vec::new();
+
+    loop {
+        poe_disp
+            .advance_fade(Instant::now())
+            .map_err(|e| format!("Failed to fade the display: {:?}", e))?;
+        if let Some(sd_notify) = sd_notify.as_mut() {
+            sd_notify.keep_alive(Instant::now());
+        }
+        if let Some(pin) = wake_pin {
+            match fan_controller.is_pin_low(pin) {
+                Ok(pressed) => {
+                    if pressed && !state.wake_button_down {
+                        info!("Wake button on p{} pressed", pin);
+                        commands.push(ControlCommand::Wake);
+                    }
+                    state.wake_button_down = pressed;
+                }
+                Err(e) => debug!("Failed to read wake button: {:#}", e),
+            }
+        }
+        commands.extend(events.try_iter());
+        if !commands.is_empty() {
+            return Ok(commands);
+        }
+
+        let now = Instant::now();
+        if now >= deadline {
+            return Ok(commands);
+        }
+        let mut timeout = match wake_pin {
+            Some(_) => BUTTON_POLL_INTERVAL.min(deadline - now),
+            None => deadline - now,
+        };
+        if let Some(step) = poe_disp.next_fade_step() {
+            timeout = timeout.min(step.saturating_duration_since(now));
+        }
+        if let Some(next) = sd_notify.as_ref().and_then(SdNotify::next_update) {
+            timeout = timeout.min(next.saturating_duration_since(now));
+        }
+        match events.recv_timeout(timeout) {
+            Ok(command) => commands.push(command),
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => thread::sleep(timeout),
+        }
+    }
+}
+
+/// Shows alerts about a unit, container or check on the display while they
+/// last.
+fn handle_alert_messages(
+    now: Instant,
+    fired_alerts: &[AlertKind],
+    alert_manager: &AlertManager,
+    shown: &mut Vec<AlertKind>,
+    messages: &mut MessageQueue,
+) {
+    for kind in fired_alerts {
+        if kind.subject().is_some() {
+            messages.push(now, kind.title(), Priority::High, None, Some(kind.id()));
+            shown.push(kind.clone());
+        }
+    }
+    shown.retain(|kind| {
+        let active = alert_manager.is_active(kind);
+        if !active {
+            messages.clear(Some(&kind.id()));
+        }
+        active
+    });
+}
+
+fn handle_wake(
+    now: Instant,
+    state: &mut AppState,
+    poe_disp: &mut PoeDisplay,
+) -> Result<(), Box<dyn Error>> {
+    state.last_activity = now;
+
+    if state.display_off_by_user {
+        debug!("Wake event received. Ending display OFF request.");
+        state.display_off_by_user = false;
+        if state.is_display_periodically_on && state.schedule_display != ScheduleDisplay::Off {
+            poe_disp
+                .display_on()
+                .map_err(|e| format!("Failed to wake display: {:?}", e))?;
         }
+    }
+
+    if state.screen_dimmed {
+        info!("Wake event received. Restoring brightness.");
+        state.screen_dimmed = false;
+        poe_disp
+            .set_brightness_mode(brightness_mode(state))
+            .map_err(|e| format!("Failed to restore brightness: {:?}", e))?;
+    }
+
+    if !state.is_display_periodically_on {
+        debug!("Wake event received. Turning display ON.");
+        if state.schedule_display != ScheduleDisplay::Off {
+            poe_disp
+                .display_on()
+                .map_err(|e| format!("Failed to wake display: {:?}", e))?;
+        }
+        state.is_display_periodically_on = true;
+    }
+    state.last_periodic_toggle_time = now;
+    Ok(())
+}
+
+fn handle_schedule(
+    schedule_state: &ScheduleState,
+    state: &mut AppState,
+    poe_disp: &mut PoeDisplay,
+) -> Result<(), Box<dyn Error>> {
+    let previous = state.schedule_display;
+    let current = schedule_state.display;
+    if previous == current {
+        return Ok(());
+    }
+
+    info!(
+        "Schedule changed display mode: {:?} -> {:?}",
+        previous, current
+    );
+    if previous == ScheduleDisplay::Off
+        && state.is_display_periodically_on
+        && !state.display_off_by_user
+    {
+        poe_disp
+            .display_on()
+            .map_err(|e| format!("Failed scheduled display ON: {:?}", e))?;
+    }
+    state.schedule_display = current;
+    match current {
+        ScheduleDisplay::Off => poe_disp
+            .display_off()
+            .map_err(|e| format!("Failed scheduled display OFF: {:?}", e))?,
+        ScheduleDisplay::Dim | ScheduleDisplay::On => poe_disp
+            .set_brightness_mode(brightness_mode(state))
+            .map_err(|e| format!("Failed to change brightness: {:?}", e))?,
+    }
+    Ok(())
+}
 
-        thread::sleep(refresh_interval);
+/// Whether the display is on, i.e. not turned off by the periodic cycle, a
+/// schedule or a request.
+fn display_visible(state: &AppState) -> bool {
+    state.is_display_periodically_on
+        && state.schedule_display != ScheduleDisplay::Off
+        && !state.display_off_by_user
+}
+
+/// Night brightness from a schedule wins over the idle level after
+/// `screen_timeout`, which in turn wins over the active level.
+fn brightness_mode(state: &AppState) -> BrightnessMode {
+    if state.schedule_display == ScheduleDisplay::Dim {
+        BrightnessMode::Night
+    } else if state.screen_dimmed {
+        BrightnessMode::Idle
+    } else {
+        BrightnessMode::Active
     }
 }
 
 fn handle_screen_timeout(
-    start_time: Instant,
     now: Instant,
     timeout_duration: Duration,
     state: &mut AppState,
     poe_disp: &mut PoeDisplay,
 ) -> Result<(), Box<dyn Error>> {
-    let elapsed_time = now.duration_since(start_time);
+    let elapsed_time = now.duration_since(state.last_activity);
     if timeout_duration.as_secs() > 0 && !state.screen_dimmed && elapsed_time >= timeout_duration {
         info!("Screen timeout reached. Dimming display.");
+        state.screen_dimmed = true;
         poe_disp
-            .set_brightness(Brightness::DIMMEST)
+            .set_brightness_mode(brightness_mode(state))
             .map_err(|e| format!("Failed to dim display: {:?}", e))?;
-        state.screen_dimmed = true;
     }
     Ok(())
 }
@@ -186,9 +916,11 @@ fn handle_periodic_display(
             state.last_periodic_toggle_time = now;
         } else if !state.is_display_periodically_on && time_since_last_toggle >= off_duration {
             debug!("Periodic timer: Turning display ON.");
-            poe_disp
-                .display_on()
-                .map_err(|e| format!("Failed periodic display ON: {:?}", e))?;
+            if state.schedule_display != ScheduleDisplay::Off && !state.display_off_by_user {
+                poe_disp
+                    .display_on()
+                    .map_err(|e| format!("Failed periodic display ON: {:?}", e))?;
+            }
             state.is_display_periodically_on = true;
             state.last_periodic_toggle_time = now;
         }
@@ -196,65 +928,63 @@ fn handle_periodic_display(
     Ok(())
 }
 
-fn update_pixel_shift(
-    now: Instant,
-    shift_interval: Duration,
-    shift_pattern: &[Point],
-    state: &mut AppState,
-) {
-    if now.duration_since(state.last_shift_time) >= shift_interval {
-        state.shift_index = (state.shift_index + 1) % shift_pattern.len();
-        state.shift_offset = shift_pattern[state.shift_index];
-        state.last_shift_time = now;
-        debug!(
-            "Shifting display pixels to offset: {:?}",
-            state.shift_offset
-        );
-    }
-}
-
-fn gather_stats(sys: &mut System) -> SystemStats {
+fn gather_stats(sys: &mut System, ram_used: RamUsed, pi_health: PiHealth) -> SystemStats {
     sys.refresh_cpu_usage();
     sys.refresh_memory();
 
+    let hostname = System::host_name().unwrap_or_default();
     let ip_address = get_ip_address();
-    let disk_usage = format!("{:.1}", get_disk_usage());
+    let (disk_used, disk_total) = get_disk_usage();
     let cpu_temp = get_cpu_temperature();
-    let cpu_temp_str = format!("{:.1}", cpu_temp);
-    let cpu_usage = format!("{:.1}", sys.global_cpu_usage());
-    let ram_usage = format!("{:.1}", get_ram_usage(sys));
+    let cpu_usage = sys.global_cpu_usage();
+    let memory = MemoryInfo::read(sys);
 
     SystemStats {
+        hostname,
         ip_address,
         cpu_usage,
         cpu_temp,
-        cpu_temp_str,
-        ram_usage,
-        disk_usage,
+        ram_used: memory.used(ram_used),
+        memory,
+        disk_usage: percentage(disk_used, disk_total),
+        disk_used,
+        disk_total,
+        pi_health,
     }
 }
 
 fn handle_fan_control(
     fan_controller: &mut FanController,
+    mode: FanMode,
     cpu_temp: f32,
+    temp_on: f32,
+    safety_temp: f32,
 ) -> Result<(), Box<dyn Error>> {
     trace!(
-        "Checking fan controller. Fan running: {}",
-        fan_controller.is_running
+        "Checking fan controller. Fan running: {}, mode: {:?}",
+        fan_controller.is_running,
+        mode
     );
-    trace!("CPU Temp: {}", cpu_temp);
+    trace!("CPU Temp: {}, temp-on: {}", cpu_temp, temp_on);
 
+    // Forced off, the fan still runs from the safety temperature on.
+    let temp_on = match mode {
+        FanMode::Auto => temp_on,
+        FanMode::On => f32::NEG_INFINITY,
+        FanMode::Off => safety_temp,
+    };
     if fan_controller.is_running {
-        if cpu_temp <= fan_controller.temp_off {
+        if cpu_temp <= fan_controller.temp_off && mode != FanMode::On {
             fan_controller.fan_off()?;
         }
-    } else if cpu_temp >= fan_controller.temp_on {
+    } else if cpu_temp >= temp_on {
         fan_controller.fan_on()?;
     }
     Ok(())
 }
 
-fn get_ip_address() -> String {
+/// The first address from `hostname -I`, `None` until the network is up.
+fn get_ip_address() -> Option<String> {
     Command::new("hostname")
         .arg("-I")
         .output()
@@ -268,39 +998,42 @@ fn get_ip_address() -> String {
                 None
             }
         })
-        .unwrap_or_else(|| "0.0.0.0".to_string())
-        .trim()
-        .to_string()
 }
 
-fn get_cpu_temperature() -> f32 {
+fn get_cpu_temperature() -> Option<f32> {
     match fs::read_to_string("/sys/class/thermal/thermal_zone0/temp") {
-        Ok(contents) => contents.trim().parse::<f32>().unwrap_or(0.0) / 1000.0,
+        Ok(contents) => match contents.trim().parse::<f32>() {
+            Ok(millidegrees) => Some(millidegrees / 1000.0),
+            Err(e) => {
+                log::warn!("Failed to parse CPU temperature {:?}: {}", contents, e);
+                None
+            }
+        },
         Err(e) => {
             log::warn!("Failed to read CPU temperature: {}", e);
-            0.0
+            None
         }
     }
 }
 
-fn get_ram_usage(sys: &System) -> f64 {
-    let total_memory = sys.total_memory();
-    let used_memory = sys.used_memory();
-    (used_memory as f64 / total_memory as f64) * 100.0
+fn percentage(used: u64, total: u64) -> f64 {
+    if total > 0 {
+        used as f64 / total as f64 * 100.0
+    } else {
+        0.0
+    }
 }
 
-fn get_disk_usage() -> f64 {
+/// Used and total bytes of the first disk. Space reserved for root counts
+/// as used.
+fn get_disk_usage() -> (u64, u64) {
     let mut disks = Disks::new_with_refreshed_list();
     if let Some(disk) = disks.first_mut() {
         disk.refresh();
         let total_space = disk.total_space();
         let available_space = disk.available_space();
-        if total_space > 0 {
-            (1.0 - (available_space as f64 / total_space as f64)) * 100.0
-        } else {
-            0.0
-        }
+        (total_space.saturating_sub(available_space), total_space)
     } else {
-        0.0
+        (0, 0)
     }
 }
@@ -0,0 +1,109 @@
+use crate::config::RamUsed;
+use log::debug;
+use std::fs;
+use sysinfo::System;
+
+const MEMINFO: &str = "/proc/meminfo";
+const BLOCK_DIR: &str = "/sys/block";
+
+/// RAM and swap, in bytes, for the memory page and the overview.
+#[derive(Debug, Clone, Default)]
+pub struct MemoryInfo {
+    pub total: u64,
+    pub free: u64,
+    /// What the kernel estimates can be handed out without swapping.
+    pub available: u64,
+    /// Buffers, page cache and reclaimable slab, like `free`'s buff/cache.
+    pub cache: u64,
+    pub swap_total: u64,
+    pub swap_used: u64,
+    /// Totals over all zram devices, `None` without any.
+    pub zram: Option<ZramStats>,
+}
+
+/// Compressed swap in RAM, from the devices' `mm_stat`.
+#[derive(Debug, Clone, Default)]
+pub struct ZramStats {
+    /// Data stored, before compression.
+    pub original: u64,
+    pub compressed: u64,
+    /// RAM taken, including the allocator's overhead.
+    pub used: u64,
+}
+
+impl ZramStats {
+    /// How many times smaller the stored data got, `None` while empty.
+    pub fn ratio(&self) -> Option<f64> {
+        (self.compressed > 0).then(|| self.original as f64 / self.compressed as f64)
+    }
+}
+
+impl MemoryInfo {
+    /// Reads RAM and swap from the last `refresh_memory` of `sys`, the
+    /// cache and zram from the kernel.
+    pub fn read(sys: &System) -> Self {
+        MemoryInfo {
+            total: sys.total_memory(),
+            free: sys.free_memory(),
+            available: sys.available_memory(),
+            cache: read_cache().unwrap_or(0),
+            swap_total: sys.total_swap(),
+            swap_used: sys.used_swap(),
+            zram: read_zram(),
+        }
+    }
+
+    /// Used RAM by the given definition.
+    pub fn used(&self, definition: RamUsed) -> u64 {
+        match definition {
+            RamUsed::Available => self.total.saturating_sub(self.available),
+            RamUsed::Cache => self.total.saturating_sub(self.free + self.cache),
+            RamUsed::Free => self.total.saturating_sub(self.free),
+        }
+    }
+}
+
+/// `Buffers`, `Cached` and `SReclaimable` from /proc/meminfo, which add up
+/// to the buff/cache column of `free`.
+fn read_cache() -> Option<u64> {
+    let meminfo = match fs::read_to_string(MEMINFO) {
+        Ok(meminfo) => meminfo,
+        Err(e) => {
+            debug!("Failed to read {}: {}", MEMINFO, e);
+            return None;
+        }
+    };
+    let cache_kib: u64 = meminfo
+        .lines()
+        .filter_map(|line| line.split_once(':'))
+        .filter(|(key, _)| matches!(*key, "Buffers" | "Cached" | "SReclaimable"))
+        .filter_map(|(_, value)| value.trim().trim_end_matches(" kB").parse::<u64>().ok())
+        .sum();
+    Some(cache_kib * 1024)
+}
+
+fn read_zram() -> Option<ZramStats> {
+    let entries = fs::read_dir(BLOCK_DIR).ok()?;
+    let mut total: Option<ZramStats> = None;
+    for entry in entries.filter_map(|entry| entry.ok()) {
+        if !entry.file_name().to_string_lossy().starts_with("zram") {
+            continue;
+        }
+        // orig_data_size compr_data_size mem_used_total mem_limit ...
+        let Ok(mm_stat) = fs::read_to_string(entry.path().join("mm_stat")) else {
+            continue;
+        };
+        let fields: Vec<u64> = mm_stat
+            .split_whitespace()
+            .take(3)
+            .filter_map(|field| field.parse().ok())
+            .collect();
+        if let [original, compressed, used] = fields[..] {
+            let sum = total.get_or_insert_with(ZramStats::default);
+            sum.original += original;
+            sum.compressed += compressed;
+            sum.used += used;
+        }
+    }
+    total
+}
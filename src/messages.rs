@@ -0,0 +1,163 @@
+use clap::ValueEnum;
+use log::info;
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant};
+
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Deserialize, Serialize, ValueEnum,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum Priority {
+    Low,
+    #[default]
+    Normal,
+    /// Also wakes the display.
+    High,
+}
+
+struct Message {
+    id: Option<String>,
+    text: String,
+    priority: Priority,
+    expires: Option<Instant>,
+    received: Instant,
+}
+
+/// Messages pushed with `notify`. The one with the highest priority is
+/// shown, the newest among equals.
+#[derive(Default)]
+pub struct MessageQueue {
+    messages: Vec<Message>,
+}
+
+impl MessageQueue {
+    /// Adds a message that stays for `duration`, or until cleared if `None`.
+    /// A message with the same `id` is replaced, so progress updates don't
+    /// pile up.
+    pub fn push(
+        &mut self,
+        now: Instant,
+        text: String,
+        priority: Priority,
+        duration: Option<Duration>,
+        id: Option<String>,
+    ) {
+        info!(
+            "Message ({:?} priority, {}): {}",
+            priority,
+            duration.map_or("until cleared".to_string(), |d| format!("{:?}", d)),
+            text
+        );
+        if id.is_some() {
+            self.messages.retain(|message| message.id != id);
+        }
+        self.messages.push(Message {
+            id,
+            text,
+            priority,
+            // Too far out to represent is as good as until cleared.
+            expires: duration.and_then(|duration| now.checked_add(duration)),
+            received: now,
+        });
+    }
+
+    /// Removes the message with `id`, or all of them.
+    pub fn clear(&mut self, id: Option<&str>) {
+        match id {
+            Some(id) => self
+                .messages
+                .retain(|message| message.id.as_deref() != Some(id)),
+            None => self.messages.clear(),
+        }
+    }
+
+    /// The message to show now, after dropping the expired ones.
+    pub fn current(&mut self, now: Instant) -> Option<&str> {
+        self.messages
+            .retain(|message| message.expires.is_none_or(|expires| expires > now));
+        self.messages
+            .iter()
+            .max_by_key(|message| (message.priority, message.received))
+            .map(|message| message.text.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn push(queue: &mut MessageQueue, now: Instant, text: &str, priority: Priority, secs: u64) {
+        let duration = Some(Duration::from_secs(secs));
+        queue.push(now, text.to_string(), priority, duration, None);
+    }
+
+    #[test]
+    fn highest_priority_then_newest_is_shown() {
+        let mut queue = MessageQueue::default();
+        let start = Instant::now();
+        push(&mut queue, start, "first", Priority::Normal, 60);
+        push(
+            &mut queue,
+            start + Duration::from_secs(1),
+            "second",
+            Priority::Normal,
+            60,
+        );
+        assert_eq!(
+            queue.current(start + Duration::from_secs(1)),
+            Some("second")
+        );
+        push(&mut queue, start, "urgent", Priority::High, 10);
+        push(
+            &mut queue,
+            start + Duration::from_secs(2),
+            "later",
+            Priority::Low,
+            60,
+        );
+        assert_eq!(
+            queue.current(start + Duration::from_secs(2)),
+            Some("urgent")
+        );
+        // The high priority one expired.
+        assert_eq!(
+            queue.current(start + Duration::from_secs(10)),
+            Some("second")
+        );
+    }
+
+    #[test]
+    fn messages_with_the_same_id_are_replaced() {
+        let mut queue = MessageQueue::default();
+        let now = Instant::now();
+        for percent in [10, 50] {
+            let text = format!("Backup {}%", percent);
+            queue.push(
+                now,
+                text,
+                Priority::Normal,
+                None,
+                Some("backup".to_string()),
+            );
+        }
+        queue.push(now, "other".to_string(), Priority::Low, None, None);
+        assert_eq!(queue.current(now), Some("Backup 50%"));
+        queue.clear(Some("backup"));
+        assert_eq!(queue.current(now), Some("other"));
+        queue.clear(None);
+        assert_eq!(queue.current(now), None);
+    }
+
+    #[test]
+    fn huge_durations_last_until_cleared() {
+        let mut queue = MessageQueue::default();
+        let now = Instant::now();
+        push(&mut queue, now, "forever", Priority::Normal, u64::MAX);
+        let duration = Some(Duration::MAX);
+        queue.push(now, "max".to_string(), Priority::Low, duration, None);
+        let later = now + Duration::from_secs(10 * 365 * 24 * 3600);
+        assert_eq!(queue.current(later), Some("forever"));
+        queue.clear(None);
+        assert_eq!(queue.current(later), None);
+    }
+}
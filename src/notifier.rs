@@ -0,0 +1,595 @@
+use crate::config::{AlertsConfig, PushConfig, PushKind, SmtpConfig, SmtpSecurity, WebhookConfig};
+use anyhow::{anyhow, Context, Result};
+use lettre::message::Mailbox;
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{Message, SmtpTransport, Transport};
+use log::{debug, info, warn};
+use serde::Serialize;
+use std::sync::mpsc::{self, Sender};
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NotificationState {
+    Firing,
+    Resolved,
+    Test,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Notification {
+    pub host: String,
+    pub alert: String,
+    pub state: NotificationState,
+    pub title: String,
+    pub message: String,
+    pub timestamp: u64,
+}
+
+impl Notification {
+    pub fn new(alert: &str, state: NotificationState, title: String, message: String) -> Self {
+        Notification {
+            host: sysinfo::System::host_name().unwrap_or_else(|| "unknown".to_string()),
+            alert: alert.to_string(),
+            state,
+            title,
+            message,
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or(0),
+        }
+    }
+
+    fn subject(&self) -> String {
+        let prefix = match self.state {
+            NotificationState::Firing => "[ALERT]",
+            NotificationState::Resolved => "[RESOLVED]",
+            NotificationState::Test => "[TEST]",
+        };
+        format!("{} {}: {}", prefix, self.host, self.title)
+    }
+}
+
+pub trait Channel: Send {
+    fn name(&self) -> String;
+    fn send(&self, notification: &Notification) -> Result<()>;
+}
+
+pub struct WebhookChannel {
+    agent: ureq::Agent,
+    config: WebhookConfig,
+}
+
+impl Channel for WebhookChannel {
+    fn name(&self) -> String {
+        format!("webhook {}", self.config.url)
+    }
+
+    fn send(&self, notification: &Notification) -> Result<()> {
+        let mut request = self.agent.post(&self.config.url);
+        for (key, value) in &self.config.headers {
+            request = request.set(key, value);
+        }
+        request
+            .send_json(notification)
+            .map_err(|e| anyhow!("webhook request failed: {}", e))?;
+        Ok(())
+    }
+}
+
+pub struct PushChannel {
+    agent: ureq::Agent,
+    config: PushConfig,
+}
+
+impl Channel for PushChannel {
+    fn name(&self) -> String {
+        match self.config.kind {
+            PushKind::Ntfy => format!("ntfy {}", self.config.url),
+            PushKind::Gotify => format!("gotify {}", self.config.url),
+        }
+    }
+
+    fn send(&self, notification: &Notification) -> Result<()> {
+        let result = match self.config.kind {
+            PushKind::Ntfy => {
+                let (priority, tags) = match notification.state {
+                    NotificationState::Firing => ("high", "warning"),
+                    NotificationState::Resolved => ("default", "white_check_mark"),
+                    NotificationState::Test => ("low", "test_tube"),
+                };
+                let mut request = self
+                    .agent
+                    .post(&self.config.url)
+                    .set("Title", &encode_header(&notification.subject()))
+                    .set("Priority", priority)
+                    .set("Tags", tags);
+                if let Some(token) = &self.config.token {
+                    request = request.set("Authorization", &format!("Bearer {}", token));
+                }
+                request.send_string(&notification.message)
+            }
+            PushKind::Gotify => {
+                let priority = match notification.state {
+                    NotificationState::Firing => 8,
+                    NotificationState::Resolved => 4,
+                    NotificationState::Test => 2,
+                };
+                let url = format!("{}/message", self.config.url.trim_end_matches('/'));
+                let mut request = self.agent.post(&url);
+                if let Some(token) = &self.config.token {
+                    request = request.set("X-Gotify-Key", token);
+                }
+                request.send_json(serde_json::json!({
+                    "title": notification.subject(),
+                    "message": notification.message,
+                    "priority": priority,
+                }))
+            }
+        };
+        result.map_err(|e| anyhow!("push request failed: {}", e))?;
+        Ok(())
+    }
+}
+
+/// HTTP headers only carry ASCII, anything else is sent as an RFC 2047
+/// encoded word, which ntfy decodes.
+fn encode_header(value: &str) -> String {
+    if value.bytes().all(|b| (b' '..=b'~').contains(&b)) {
+        return value.to_string();
+    }
+    let encoded: String = value
+        .bytes()
+        .map(|b| match b {
+            b' ' => "_".to_string(),
+            b'0'..=b'9' | b'a'..=b'z' | b'A'..=b'Z' => (b as char).to_string(),
+            _ => format!("={:02X}", b),
+        })
+        .collect();
+    format!("=?UTF-8?Q?{}?=", encoded)
+}
+
+pub struct SmtpChannel {
+    transport: SmtpTransport,
+    from: Mailbox,
+    to: Vec<Mailbox>,
+    host: String,
+}
+
+impl SmtpChannel {
+    fn new(config: &SmtpConfig, timeout: Duration) -> Result<Self> {
+        let builder = match config.security {
+            SmtpSecurity::None => SmtpTransport::builder_dangerous(&config.host),
+            SmtpSecurity::Starttls => SmtpTransport::starttls_relay(&config.host)?,
+            SmtpSecurity::Tls => SmtpTransport::relay(&config.host)?,
+        };
+        let mut builder = builder.port(config.port).timeout(Some(timeout));
+        if let (Some(username), Some(password)) = (&config.username, &config.password) {
+            builder = builder.credentials(Credentials::new(username.clone(), password.clone()));
+        }
+
+        let from = config
+            .from
+            .parse()
+            .with_context(|| format!("invalid smtp sender address '{}'", config.from))?;
+        let to = config
+            .to
+            .iter()
+            .map(|addr| {
+                addr.parse()
+                    .with_context(|| format!("invalid smtp recipient address '{}'", addr))
+            })
+            .collect::<Result<Vec<Mailbox>>>()?;
+        if to.is_empty() {
+            return Err(anyhow!(
+                "smtp channel for {} has no recipients",
+                config.host
+            ));
+        }
+
+        Ok(SmtpChannel {
+            transport: builder.build(),
+            from,
+            to,
+            host: format!("{}:{}", config.host, config.port),
+        })
+    }
+}
+
+impl Channel for SmtpChannel {
+    fn name(&self) -> String {
+        format!("smtp {}", self.host)
+    }
+
+    fn send(&self, notification: &Notification) -> Result<()> {
+        let mut builder = Message::builder()
+            .from(self.from.clone())
+            .subject(notification.subject());
+        for to in &self.to {
+            builder = builder.to(to.clone());
+        }
+        let email = builder.body(notification.message.clone())?;
+        self.transport.send(&email)?;
+        Ok(())
+    }
+}
+
+/// Builds every channel configured under `[alerts]`. Channels with invalid
+/// settings are skipped with a warning so one typo doesn't disable the rest.
+pub fn build_channels(config: &AlertsConfig) -> Vec<Box<dyn Channel>> {
+    let agent = ureq::AgentBuilder::new().timeout(config.timeout()).build();
+    let mut channels: Vec<Box<dyn Channel>> = Vec::new();
+
+    for webhook in &config.webhook {
+        channels.push(Box::new(WebhookChannel {
+            agent: agent.clone(),
+            config: webhook.clone(),
+        }));
+    }
+    for push in &config.push {
+        channels.push(Box::new(PushChannel {
+            agent: agent.clone(),
+            config: push.clone(),
+        }));
+    }
+    for smtp in &config.smtp {
+        match SmtpChannel::new(smtp, config.timeout()) {
+            Ok(channel) => channels.push(Box::new(channel)),
+            Err(e) => warn!("Skipping smtp channel {}: {:#}", smtp.host, e),
+        }
+    }
+    channels
+}
+
+/// Delivers notifications on a background thread so slow or unreachable
+/// endpoints never stall the display loop.
+pub struct Notifier {
+    sender: Sender<Notification>,
+}
+
+impl Notifier {
+    pub fn spawn(channels: Vec<Box<dyn Channel>>) -> Result<Self> {
+        let (sender, receiver) = mpsc::channel::<Notification>();
+        thread::Builder::new()
+            .name("notifier".to_string())
+            .spawn(move || {
+                for notification in receiver {
+                    for channel in &channels {
+                        match channel.send(&notification) {
+                            Ok(()) => debug!(
+                                "Sent '{}' notification via {}",
+                                notification.alert,
+                                channel.name()
+                            ),
+                            Err(e) => warn!(
+                                "Failed to send '{}' notification via {}: {:#}",
+                                notification.alert,
+                                channel.name(),
+                                e
+                            ),
+                        }
+                    }
+                }
+            })
+            .context("failed to spawn the notifier thread")?;
+        Ok(Notifier { sender })
+    }
+
+    pub fn send(&self, notification: Notification) {
+        info!("Queueing notification: {}", notification.subject());
+        if self.sender.send(notification).is_err() {
+            warn!("Notifier thread is not running, notification dropped");
+        }
+    }
+}
+
+/// Sends a test notification through every configured channel and reports
+/// the outcome of each one.
+pub fn send_test(config: &AlertsConfig) -> Result<()> {
+    let channels = build_channels(config);
+    if channels.is_empty() {
+        return Err(anyhow!("No alert channels configured in [alerts]"));
+    }
+
+    let notification = Notification::new(
+        "test",
+        NotificationState::Test,
+        "Test notification".to_string(),
+        "This is a test notification from rustberry-poe-monitor.".to_string(),
+    );
+
+    let mut failures = 0;
+    for channel in &channels {
+        match channel.send(&notification) {
+            Ok(()) => println!("OK    {}", channel.name()),
+            Err(e) => {
+                println!("FAIL  {}: {:#}", channel.name(), e);
+                failures += 1;
+            }
+        }
+    }
+
+    if failures > 0 {
+        return Err(anyhow!(
+            "{} of {} channels failed",
+            failures,
+            channels.len()
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+    use std::thread::JoinHandle;
+
+    fn notification(state: NotificationState) -> Notification {
+        Notification {
+            host: "pi".to_string(),
+            alert: "high_temperature".to_string(),
+            state,
+            title: "CPU at 81°C".to_string(),
+            message: "Above 80°C for 60s".to_string(),
+            timestamp: 1700000000,
+        }
+    }
+
+    fn agent() -> ureq::Agent {
+        ureq::AgentBuilder::new()
+            .timeout(Duration::from_secs(5))
+            .build()
+    }
+
+    /// Answers one HTTP request with 200 and returns its head, with lowercase
+    /// header names, and body.
+    fn http_stand_in() -> (String, JoinHandle<(String, String)>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let handle = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream);
+            let mut head = String::new();
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                if line == "\r\n" {
+                    break;
+                }
+                head.push_str(&line);
+            }
+            let length = head
+                .lines()
+                .find_map(|line| {
+                    line.to_lowercase()
+                        .strip_prefix("content-length:")
+                        .map(|v| v.trim().parse().unwrap())
+                })
+                .unwrap_or(0);
+            let mut body = vec![0; length];
+            reader.read_exact(&mut body).unwrap();
+            reader
+                .get_mut()
+                .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\nConnection: close\r\n\r\n")
+                .unwrap();
+            (head, String::from_utf8(body).unwrap())
+        });
+        (url, handle)
+    }
+
+    fn header<'a>(head: &'a str, name: &str) -> Option<&'a str> {
+        head.lines().find_map(|line| {
+            let (key, value) = line.split_once(':')?;
+            key.eq_ignore_ascii_case(name).then(|| value.trim())
+        })
+    }
+
+    #[test]
+    fn webhook_posts_the_notification_as_json_with_custom_headers() {
+        let (url, server) = http_stand_in();
+        let channel = WebhookChannel {
+            agent: agent(),
+            config: WebhookConfig {
+                url: format!("{}/hook", url),
+                headers: HashMap::from([("X-Api-Key".to_string(), "secret".to_string())]),
+            },
+        };
+        channel
+            .send(&notification(NotificationState::Firing))
+            .unwrap();
+
+        let (head, body) = server.join().unwrap();
+        assert!(head.starts_with("POST /hook HTTP/1.1"));
+        assert_eq!(header(&head, "X-Api-Key"), Some("secret"));
+        assert_eq!(header(&head, "Content-Type"), Some("application/json"));
+        let json: serde_json::Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(json["host"], "pi");
+        assert_eq!(json["alert"], "high_temperature");
+        assert_eq!(json["state"], "firing");
+        assert_eq!(json["title"], "CPU at 81°C");
+        assert_eq!(json["message"], "Above 80°C for 60s");
+        assert_eq!(json["timestamp"], 1700000000);
+    }
+
+    #[test]
+    fn ntfy_sends_the_message_as_body_and_the_rest_as_headers() {
+        let (url, server) = http_stand_in();
+        let channel = PushChannel {
+            agent: agent(),
+            config: PushConfig {
+                kind: PushKind::Ntfy,
+                url: format!("{}/alerts", url),
+                token: Some("tk_1".to_string()),
+            },
+        };
+        channel
+            .send(&notification(NotificationState::Resolved))
+            .unwrap();
+
+        let (head, body) = server.join().unwrap();
+        assert!(head.starts_with("POST /alerts HTTP/1.1"));
+        assert_eq!(
+            header(&head, "Title"),
+            Some("=?UTF-8?Q?=5BRESOLVED=5D_pi=3A_CPU_at_81=C2=B0C?=")
+        );
+        assert_eq!(header(&head, "Priority"), Some("default"));
+        assert_eq!(header(&head, "Tags"), Some("white_check_mark"));
+        assert_eq!(header(&head, "Authorization"), Some("Bearer tk_1"));
+        assert_eq!(body, "Above 80°C for 60s");
+    }
+
+    #[test]
+    fn gotify_posts_json_to_the_message_endpoint() {
+        let (url, server) = http_stand_in();
+        let channel = PushChannel {
+            agent: agent(),
+            config: PushConfig {
+                kind: PushKind::Gotify,
+                url: format!("{}/", url),
+                token: Some("app-token".to_string()),
+            },
+        };
+        channel
+            .send(&notification(NotificationState::Firing))
+            .unwrap();
+
+        let (head, body) = server.join().unwrap();
+        assert!(head.starts_with("POST /message HTTP/1.1"));
+        assert_eq!(header(&head, "X-Gotify-Key"), Some("app-token"));
+        let json: serde_json::Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(json["title"], "[ALERT] pi: CPU at 81°C");
+        assert_eq!(json["message"], "Above 80°C for 60s");
+        assert_eq!(json["priority"], 8);
+    }
+
+    #[test]
+    fn http_errors_fail_the_send() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let server = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut request = [0; 4096];
+            let _ = stream.read(&mut request).unwrap();
+            stream
+                .write_all(b"HTTP/1.1 500 Internal Server Error\r\nContent-Length: 0\r\n\r\n")
+                .unwrap();
+        });
+        let channel = WebhookChannel {
+            agent: agent(),
+            config: WebhookConfig {
+                url,
+                headers: HashMap::new(),
+            },
+        };
+        assert!(channel
+            .send(&notification(NotificationState::Test))
+            .is_err());
+        server.join().unwrap();
+    }
+
+    /// Plays an SMTP server for one mail and returns the commands and the
+    /// message data it received.
+    fn smtp_stand_in() -> (u16, JoinHandle<(Vec<String>, String)>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let handle = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut writer = stream.try_clone().unwrap();
+            let mut reader = BufReader::new(stream);
+            writer.write_all(b"220 stand-in ESMTP\r\n").unwrap();
+            let mut commands = Vec::new();
+            let mut data = String::new();
+            loop {
+                let mut line = String::new();
+                if reader.read_line(&mut line).unwrap() == 0 {
+                    break;
+                }
+                let command = line.trim_end().to_string();
+                let verb = command.split(' ').next().unwrap_or("").to_uppercase();
+                commands.push(command);
+                match verb.as_str() {
+                    "EHLO" => writer.write_all(b"250 stand-in\r\n").unwrap(),
+                    "DATA" => {
+                        writer.write_all(b"354 go ahead\r\n").unwrap();
+                        loop {
+                            let mut line = String::new();
+                            reader.read_line(&mut line).unwrap();
+                            if line == ".\r\n" {
+                                break;
+                            }
+                            data.push_str(&line);
+                        }
+                        writer.write_all(b"250 queued\r\n").unwrap();
+                    }
+                    "QUIT" => {
+                        writer.write_all(b"221 bye\r\n").unwrap();
+                        break;
+                    }
+                    _ => writer.write_all(b"250 ok\r\n").unwrap(),
+                }
+            }
+            (commands, data)
+        });
+        (port, handle)
+    }
+
+    #[test]
+    fn smtp_mails_every_recipient() {
+        let (port, server) = smtp_stand_in();
+        let channel = SmtpChannel::new(
+            &SmtpConfig {
+                host: "127.0.0.1".to_string(),
+                port,
+                security: SmtpSecurity::None,
+                username: None,
+                password: None,
+                from: "Pi <pi@example.com>".to_string(),
+                to: vec!["a@example.com".to_string(), "b@example.com".to_string()],
+            },
+            Duration::from_secs(5),
+        )
+        .unwrap();
+        let notification = Notification {
+            title: "Disk almost full".to_string(),
+            message: "Root filesystem is 93% full".to_string(),
+            ..notification(NotificationState::Firing)
+        };
+        channel.send(&notification).unwrap();
+        // The pooled connection is only closed when the transport goes.
+        drop(channel);
+
+        let (commands, data) = server.join().unwrap();
+        assert!(commands.contains(&"MAIL FROM:<pi@example.com>".to_string()));
+        assert!(commands.contains(&"RCPT TO:<a@example.com>".to_string()));
+        assert!(commands.contains(&"RCPT TO:<b@example.com>".to_string()));
+        assert!(data.contains("Subject: [ALERT] pi: Disk almost full\r\n"));
+        assert!(data.contains("Root filesystem is 93% full"));
+        assert!(data.contains("To: a@example.com, b@example.com"));
+        assert!(data.contains("From: Pi <pi@example.com>"));
+    }
+
+    #[test]
+    fn smtp_rejects_invalid_addresses_and_missing_recipients() {
+        let config = SmtpConfig {
+            host: "127.0.0.1".to_string(),
+            port: 25,
+            security: SmtpSecurity::None,
+            username: None,
+            password: None,
+            from: "not an address".to_string(),
+            to: vec!["a@example.com".to_string()],
+        };
+        assert!(SmtpChannel::new(&config, Duration::from_secs(1)).is_err());
+        let config = SmtpConfig {
+            from: "pi@example.com".to_string(),
+            to: Vec::new(),
+            ..config
+        };
+        assert!(SmtpChannel::new(&config, Duration::from_secs(1)).is_err());
+    }
+}
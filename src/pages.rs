@@ -0,0 +1,617 @@
+use crate::checks::CheckResult;
+use crate::config::{Overflow, Page, ProcessSort, RamUsed, SizePrefix};
+use crate::containers::{ContainerStatus, ContainerSummary};
+use crate::cpu_info::CpuInfo;
+use crate::display::ScreenStyle;
+use crate::layout::{self, Align, Slot};
+use crate::memory::MemoryInfo;
+use crate::processes::TopProcess;
+use crate::services::UnitStatus;
+use crate::system_info::{self, SystemInfo};
+use crate::units;
+use embedded_graphics::{
+    pixelcolor::BinaryColor,
+    prelude::*,
+    primitives::{PrimitiveStyle, Rectangle},
+};
+use log::debug;
+use std::time::{Duration, Instant};
+
+/// How long each screenful of the containers page stays up.
+const CONTAINER_SCREEN_TIME: Duration = Duration::from_secs(3);
+/// How long uptime and boot time take turns on short system pages, in
+/// seconds.
+const ALTERNATE_SECS: u64 = 5;
+
+/// Cycles through the configured pages, showing each for `interval`.
+pub struct PageRotation {
+    pages: Vec<Page>,
+    interval: Duration,
+    index: usize,
+    last_switch: Instant,
+}
+
+impl PageRotation {
+    pub fn new(pages: &[Page], interval: Duration, now: Instant) -> Self {
+        let pages = if pages.is_empty() {
+            vec![Page::Overview]
+        } else {
+            pages.to_vec()
+        };
+        PageRotation {
+            pages,
+            interval,
+            index: 0,
+            last_switch: now,
+        }
+    }
+
+    /// The page to show now, moving on to the next one once it is due.
+    pub fn current(&mut self, now: Instant) -> Page {
+        if self.pages.len() > 1
+            && !self.interval.is_zero()
+            && now.duration_since(self.last_switch) >= self.interval
+        {
+            self.index = (self.index + 1) % self.pages.len();
+            self.last_switch = now;
+            debug!("Switching to the {:?} page", self.pages[self.index]);
+        }
+        self.pages[self.index]
+    }
+
+    /// How long the current page has been up.
+    pub fn shown_for(&self, now: Instant) -> Duration {
+        now.duration_since(self.last_switch)
+    }
+}
+
+/// Draws one line of text in the label font. Lines are numbered from the
+/// top, without gaps between them.
+fn draw_line<D>(
+    disp: &mut D,
+    text: &str,
+    line: i32,
+    align: Align,
+    style: &ScreenStyle,
+    offset: Point,
+) -> Result<(), D::Error>
+where
+    D: DrawTarget<Color = BinaryColor>,
+{
+    let font = style.fonts.label;
+    let width = disp.bounding_box().size.width as i32;
+    let baseline = line * font.character_size.height as i32 + font.baseline as i32;
+    let slot = Slot::new(0, width, baseline, align).translate(offset);
+    layout::draw_text(disp, text, slot, font, &[], Overflow::Truncate, 0)?;
+    Ok(())
+}
+
+/// Draws `name` on the left and `value` on the right of a line. The name is
+/// truncated to leave `value_width` pixels for the value.
+fn draw_row<D>(
+    disp: &mut D,
+    name: &str,
+    value: &str,
+    line: i32,
+    value_width: i32,
+    style: &ScreenStyle,
+    offset: Point,
+) -> Result<(), D::Error>
+where
+    D: DrawTarget<Color = BinaryColor>,
+{
+    let font = style.fonts.label;
+    let width = disp.bounding_box().size.width as i32;
+    let baseline = line * font.character_size.height as i32 + font.baseline as i32;
+    let name_slot = Slot::new(0, width - value_width, baseline, Align::Left).translate(offset);
+    layout::draw_text(disp, name, name_slot, font, &[], Overflow::Truncate, 0)?;
+    draw_line(disp, value, line, Align::Right, style, offset)
+}
+
+/// Load averages, frequency and governor on top, one usage bar per core
+/// below.
+pub fn draw_cpu<D>(
+    disp: &mut D,
+    info: &CpuInfo,
+    style: &ScreenStyle,
+    offset: Point,
+) -> Result<(), D::Error>
+where
+    D: DrawTarget<Color = BinaryColor>,
+{
+    disp.clear(BinaryColor::Off)?;
+
+    let [one, five, fifteen] = info.load_average;
+    let load = format!("LOAD {:.2} {:.2} {:.2}", one, five, fifteen);
+    draw_line(disp, &load, 0, Align::Left, style, offset)?;
+
+    let mhz = |khz: Option<u64>| khz.map_or("?".to_string(), |khz| (khz / 1000).to_string());
+    let frequency = match info.policies.first() {
+        Some(first) => {
+            let frequencies: Vec<String> = info
+                .policies
+                .iter()
+                .map(|policy| format!("{}/{}", mhz(policy.cur_khz), mhz(policy.max_khz)))
+                .collect();
+            let governor = first.governor.as_deref().unwrap_or_default();
+            format!("{}MHz {}", frequencies.join(" "), governor)
+        }
+        None => "NO CPUFREQ".to_string(),
+    };
+    draw_line(disp, &frequency, 1, Align::Left, style, offset)?;
+
+    let size = disp.bounding_box().size;
+    let top = 2 * style.fonts.label.character_size.height as i32 + 1;
+    let area = Rectangle::new(
+        Point::new(0, top) + offset,
+        Size::new(size.width, (size.height as i32 - top).max(0) as u32),
+    );
+    draw_bars(disp, &info.core_usage, area)
+}
+
+/// Hostname, board, uptime and versions. Panels with room for fewer lines
+/// get a condensed version without the boot time.
+pub fn draw_system<D>(
+    disp: &mut D,
+    info: &SystemInfo,
+    style: &ScreenStyle,
+    offset: Point,
+) -> Result<(), D::Error>
+where
+    D: DrawTarget<Color = BinaryColor>,
+{
+    disp.clear(BinaryColor::Off)?;
+
+    let height = disp.bounding_box().size.height;
+    let line_count = height / style.fonts.label.character_size.height;
+    let model = info.model.as_deref().unwrap_or("Unknown board");
+    let uptime = format!("UP {}", system_info::format_uptime(info.uptime));
+    let version = format!("v{}", info.version);
+    let boot_time = |format| {
+        let time = info.boot_time.map(|time| time.format(format).to_string());
+        format!("BOOT {}", time.as_deref().unwrap_or("?"))
+    };
+
+    if line_count >= 7 {
+        let lines = [
+            info.hostname.clone(),
+            model.to_string(),
+            uptime,
+            boot_time("%Y-%m-%d %H:%M"),
+            info.os.clone(),
+            info.kernel.clone(),
+            format!("MONITOR {}", version),
+        ];
+        for (line, text) in lines.iter().enumerate() {
+            draw_line(disp, text, line as i32, Align::Left, style, offset)?;
+        }
+    } else {
+        let model = model.replace("Raspberry Pi", "Pi");
+        let system = format!("{} {}", info.os, info.kernel);
+        // Uptime and boot time take turns, a few seconds each.
+        let since = if (info.uptime / ALTERNATE_SECS).is_multiple_of(2) {
+            uptime
+        } else {
+            boot_time("%m-%d %H:%M")
+        };
+        draw_line(disp, &info.hostname, 0, Align::Left, style, offset)?;
+        draw_line(disp, &model, 1, Align::Left, style, offset)?;
+        draw_line(disp, &since, 2, Align::Left, style, offset)?;
+        draw_line(disp, &version, 2, Align::Right, style, offset)?;
+        draw_line(disp, &system, 3, Align::Left, style, offset)?;
+    }
+    Ok(())
+}
+
+/// The heaviest processes, one per line, with the share of CPU or memory
+/// they are sorted by.
+pub fn draw_processes<D>(
+    disp: &mut D,
+    processes: &[TopProcess],
+    sort: ProcessSort,
+    style: &ScreenStyle,
+    offset: Point,
+) -> Result<(), D::Error>
+where
+    D: DrawTarget<Color = BinaryColor>,
+{
+    disp.clear(BinaryColor::Off)?;
+
+    let font = style.fonts.label;
+    let line_count = disp.bounding_box().size.height / font.character_size.height;
+    let percent_width = layout::text_width("99.9%", font) + font.character_size.width as i32;
+
+    for (line, process) in processes.iter().take(line_count as usize).enumerate() {
+        let percent = match sort {
+            ProcessSort::Cpu => process.cpu_usage,
+            ProcessSort::Memory => process.memory_usage,
+        };
+        let percent = if percent < 10.0 {
+            format!("{:.1}%", percent)
+        } else {
+            format!("{:.0}%", percent)
+        };
+        let line = line as i32;
+        draw_row(
+            disp,
+            &process.name,
+            &percent,
+            line,
+            percent_width,
+            style,
+            offset,
+        )?;
+    }
+    Ok(())
+}
+
+/// A summary line, then one line per watched unit with its state. Failed
+/// units come first so they are visible on small panels.
+pub fn draw_services<D>(
+    disp: &mut D,
+    statuses: &[UnitStatus],
+    style: &ScreenStyle,
+    offset: Point,
+) -> Result<(), D::Error>
+where
+    D: DrawTarget<Color = BinaryColor>,
+{
+    let items: Vec<ListItem> = statuses
+        .iter()
+        .map(|status| {
+            let state = if status.is_failed() {
+                "FAILED"
+            } else if status.load_state == "not-found" {
+                "missing"
+            } else {
+                &status.active_state
+            };
+            ListItem {
+                name: status.name.trim_end_matches(".service"),
+                value: state.to_string(),
+                failed: status.is_failed(),
+            }
+        })
+        .collect();
+    draw_list(disp, "SERVICES", "NO UNITS", &items, style, offset)
+}
+
+/// A summary line, then OK with the latency or FAIL for every check,
+/// failed ones first.
+pub fn draw_checks<D>(
+    disp: &mut D,
+    results: &[CheckResult],
+    style: &ScreenStyle,
+    offset: Point,
+) -> Result<(), D::Error>
+where
+    D: DrawTarget<Color = BinaryColor>,
+{
+    let items: Vec<ListItem> = results
+        .iter()
+        .map(|result| ListItem {
+            name: &result.name,
+            value: match &result.outcome {
+                Some(Ok(latency)) => format!("OK {}ms", latency.as_millis()),
+                Some(Err(_)) => "FAIL".to_string(),
+                None => "...".to_string(),
+            },
+            failed: result.is_failed(),
+        })
+        .collect();
+    draw_list(disp, "CHECKS", "NO CHECKS", &items, style, offset)
+}
+
+struct ListItem<'a> {
+    name: &'a str,
+    value: String,
+    failed: bool,
+}
+
+/// `title` and a count of failed items on the first line, the items below
+/// with the failed ones first. `empty` replaces the count without items.
+fn draw_list<D>(
+    disp: &mut D,
+    title: &str,
+    empty: &str,
+    items: &[ListItem],
+    style: &ScreenStyle,
+    offset: Point,
+) -> Result<(), D::Error>
+where
+    D: DrawTarget<Color = BinaryColor>,
+{
+    disp.clear(BinaryColor::Off)?;
+
+    let failed = items.iter().filter(|item| item.failed).count();
+    let summary = match failed {
+        _ if items.is_empty() => empty.to_string(),
+        0 => "ALL OK".to_string(),
+        failed => format!("{} FAILED", failed),
+    };
+    draw_line(disp, title, 0, Align::Left, style, offset)?;
+    draw_line(disp, &summary, 0, Align::Right, style, offset)?;
+
+    let font = style.fonts.label;
+    let line_count = disp.bounding_box().size.height / font.character_size.height;
+    let mut sorted: Vec<&ListItem> = items.iter().collect();
+    sorted.sort_by_key(|item| !item.failed);
+
+    let rows = (line_count as usize).saturating_sub(1);
+    for (line, item) in sorted.iter().take(rows).enumerate() {
+        let value_width = layout::text_width(&item.value, font) + font.character_size.width as i32;
+        let line = line as i32 + 1;
+        draw_row(
+            disp,
+            item.name,
+            &item.value,
+            line,
+            value_width,
+            style,
+            offset,
+        )?;
+    }
+    Ok(())
+}
+
+/// Container counts on top, the containers below, a screenful at a time.
+/// Unhealthy and restart-looping containers come first.
+pub fn draw_containers<D>(
+    disp: &mut D,
+    summary: &ContainerSummary,
+    shown_for: Duration,
+    style: &ScreenStyle,
+    offset: Point,
+) -> Result<(), D::Error>
+where
+    D: DrawTarget<Color = BinaryColor>,
+{
+    disp.clear(BinaryColor::Off)?;
+
+    if summary.error.is_some() {
+        draw_line(disp, "NO CONTAINER API", 0, Align::Left, style, offset)?;
+        return Ok(());
+    }
+    let counts = format!("{} UP {} DOWN", summary.running(), summary.stopped());
+    draw_line(disp, &counts, 0, Align::Left, style, offset)?;
+    let unhealthy = summary.unhealthy().len();
+    if unhealthy > 0 {
+        let unhealthy = format!("{} UNHEALTHY", unhealthy);
+        draw_line(disp, &unhealthy, 0, Align::Right, style, offset)?;
+    }
+
+    let font = style.fonts.label;
+    let rows = (disp.bounding_box().size.height / font.character_size.height).max(2) as usize - 1;
+    let mut sorted: Vec<&ContainerStatus> = summary.containers.iter().collect();
+    sorted.sort_by_key(|container| {
+        let problem = container.is_unhealthy() || container.restart_loop;
+        (!problem, !container.is_running())
+    });
+    let screens = sorted.len().div_ceil(rows).max(1);
+    let screen = (shown_for.as_secs() / CONTAINER_SCREEN_TIME.as_secs()) as usize % screens;
+
+    for (line, container) in sorted.iter().skip(screen * rows).take(rows).enumerate() {
+        let value = if container.restart_loop {
+            "restarting".to_string()
+        } else if container.is_unhealthy() {
+            "unhealthy".to_string()
+        } else if container.is_running() {
+            let cpu = container
+                .cpu_percent
+                .map_or("-".to_string(), |cpu| format!("{:.0}%", cpu));
+            let memory = container.memory_bytes.map_or("-".to_string(), |bytes| {
+                units::human_size(bytes, 1, SizePrefix::Iec)
+            });
+            format!("{} {}", cpu, memory)
+        } else {
+            container.state.clone()
+        };
+        let value_width = layout::text_width(&value, font) + font.character_size.width as i32;
+        let line = line as i32 + 1;
+        draw_row(
+            disp,
+            &container.name,
+            &value,
+            line,
+            value_width,
+            style,
+            offset,
+        )?;
+    }
+    Ok(())
+}
+
+/// Total RAM on top, then used, available, cache, swap and zram, with
+/// bars for the RAM and swap usage in the space left. Panels with room for
+/// fewer lines drop the title and the bars, and show the zram ratio next
+/// to the swap.
+pub fn draw_memory<D>(
+    disp: &mut D,
+    info: &MemoryInfo,
+    ram_used: RamUsed,
+    style: &ScreenStyle,
+    offset: Point,
+) -> Result<(), D::Error>
+where
+    D: DrawTarget<Color = BinaryColor>,
+{
+    disp.clear(BinaryColor::Off)?;
+
+    let size = |bytes| units::human_size(bytes, 1, SizePrefix::Iec);
+    let percent = |part: u64, total: u64| part as f32 / total.max(1) as f32 * 100.0;
+    let used = info.used(ram_used);
+    let used_percent = percent(used, info.total);
+    let swap = match info.swap_total {
+        0 => "off".to_string(),
+        total => format!("{}/{}", size(info.swap_used), size(total)),
+    };
+    let zram = info.zram.as_ref().map(|zram| {
+        let ratio = zram
+            .ratio()
+            .map_or("-".to_string(), |ratio| format!("{:.1}x", ratio));
+        (ratio, size(zram.used))
+    });
+
+    let font = style.fonts.label;
+    let line_height = font.character_size.height;
+    let line_count = disp.bounding_box().size.height / line_height;
+    let mut rows = vec![
+        ("USED", format!("{} {:.0}%", size(used), used_percent)),
+        ("AVAIL", size(info.available)),
+        ("CACHE", size(info.cache)),
+    ];
+    let full = line_count as usize >= rows.len() + 3 + zram.is_some() as usize;
+    let first_row = if full {
+        draw_line(disp, "MEMORY", 0, Align::Left, style, offset)?;
+        draw_line(disp, &size(info.total), 0, Align::Right, style, offset)?;
+        rows.push(("SWAP", swap));
+        if let Some((ratio, used)) = zram {
+            rows.push(("ZRAM", format!("{} {}", ratio, used)));
+        }
+        1
+    } else {
+        match zram {
+            Some((ratio, _)) => rows.push(("SWAP", format!("{} {}", swap, ratio))),
+            None => rows.push(("SWAP", swap)),
+        }
+        0
+    };
+
+    for (line, (name, value)) in rows.iter().take(line_count as usize).enumerate() {
+        let value_width = layout::text_width(value, font) + font.character_size.width as i32;
+        let line = first_row + line as i32;
+        draw_row(disp, name, value, line, value_width, style, offset)?;
+    }
+
+    if full {
+        let top = (first_row as u32 + rows.len() as u32) * line_height + 1;
+        let height = disp.bounding_box().size.height.saturating_sub(top);
+        let width = disp.bounding_box().size.width;
+        let area = Rectangle::new(Point::new(0, top as i32) + offset, Size::new(width, height));
+        let mut bars = vec![used_percent];
+        if info.swap_total > 0 {
+            bars.push(percent(info.swap_used, info.swap_total));
+        }
+        draw_bars(disp, &bars, area)?;
+    }
+    Ok(())
+}
+
+/// Horizontal percentage bars stacked in `area`, 1 pixel apart. Bars of at
+/// least 3 pixels get an outline showing the full scale.
+fn draw_bars<D>(disp: &mut D, values: &[f32], area: Rectangle) -> Result<(), D::Error>
+where
+    D: DrawTarget<Color = BinaryColor>,
+{
+    if values.is_empty() {
+        return Ok(());
+    }
+    let count = values.len() as i32;
+    let height = ((area.size.height as i32 + 1) / count - 1).max(1);
+    let width = area.size.width as i32;
+    let outlined = height >= 3;
+    let fill = PrimitiveStyle::with_fill(BinaryColor::On);
+
+    for (index, value) in values.iter().enumerate() {
+        let top_left = area.top_left + Point::new(0, index as i32 * (height + 1));
+        let inner_width = if outlined { width - 2 } else { width };
+        let filled = (value.clamp(0.0, 100.0) / 100.0 * inner_width as f32).round() as u32;
+        if outlined {
+            Rectangle::new(top_left, Size::new(width as u32, height as u32))
+                .into_styled(PrimitiveStyle::with_stroke(BinaryColor::On, 1))
+                .draw(disp)?;
+            Rectangle::new(
+                top_left + Point::new(1, 1),
+                Size::new(filled, height as u32 - 2),
+            )
+            .into_styled(fill)
+            .draw(disp)?;
+        } else {
+            Rectangle::new(top_left, Size::new(filled, height as u32))
+                .into_styled(fill)
+                .draw(disp)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{FontsConfig, LabelsConfig, OverflowConfig};
+    use crate::fonts::Fonts;
+    use crate::framebuffer::Framebuffer;
+    use crate::icons::Icons;
+    use chrono::{Local, TimeZone};
+    use embedded_graphics::mono_font::{ascii::FONT_10X20, MonoFont};
+
+    fn with_style(label_font: Option<&'static MonoFont<'static>>, test: impl FnOnce(&ScreenStyle)) {
+        let mut fonts = Fonts::load(&FontsConfig::default());
+        if let Some(font) = label_font {
+            fonts.label = font;
+        }
+        let icons = Icons::load(&Default::default());
+        let style = ScreenStyle {
+            fonts: &fonts,
+            labels: &LabelsConfig::default(),
+            overflow: &OverflowConfig::default(),
+            icons: &icons,
+            scroll: 0,
+            fan_frame: None,
+        };
+        test(&style);
+    }
+
+    fn pixels(framebuffer: &Framebuffer) -> Vec<(u32, u32)> {
+        framebuffer.lit_pixels().collect()
+    }
+
+    fn system_info(uptime: u64) -> SystemInfo {
+        SystemInfo {
+            hostname: "pi".to_string(),
+            uptime,
+            boot_time: Local.with_ymd_and_hms(2026, 10, 19, 8, 12, 0).single(),
+            os: "Debian GNU/Linux 12".to_string(),
+            kernel: "6.6.51".to_string(),
+            model: Some("Raspberry Pi 4 Model B".to_string()),
+            version: "1.2.0",
+        }
+    }
+
+    #[test]
+    fn short_system_page_alternates_uptime_and_boot_time() {
+        with_style(None, |style| {
+            let draw = |uptime| {
+                let mut framebuffer = Framebuffer::new(128, 32);
+                let Ok(()) =
+                    draw_system(&mut framebuffer, &system_info(uptime), style, Point::zero());
+                pixels(&framebuffer)
+            };
+            let line = |text: &str| {
+                let mut framebuffer = Framebuffer::new(128, 32);
+                let Ok(()) =
+                    draw_line(&mut framebuffer, text, 2, Align::Left, style, Point::zero());
+                pixels(&framebuffer)
+            };
+            let shows = |page: &[(u32, u32)], text| line(text).iter().all(|p| page.contains(p));
+
+            let first = draw(ALTERNATE_SECS - 1);
+            assert!(shows(&first, "UP 0m"));
+            assert!(!shows(&first, "BOOT 10-19 08:12"));
+            let second = draw(ALTERNATE_SECS);
+            assert!(shows(&second, "BOOT 10-19 08:12"));
+        });
+    }
+
+    #[test]
+    fn lists_survive_a_font_taller_than_the_panel() {
+        with_style(Some(&FONT_10X20), |style| {
+            let results = [CheckResult {
+                name: "nas".to_string(),
+                outcome: None,
+            }];
+            let mut framebuffer = Framebuffer::new(128, 16);
+            let Ok(()) = draw_checks(&mut framebuffer, &results, style, Point::zero());
+        });
+    }
+}
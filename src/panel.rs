@@ -0,0 +1,111 @@
+use crate::config::{Controller, DisplayConfig as AppDisplayConfig};
+use crate::display_types::{Sh1106Display, Ssd1306Display128x32, Ssd1306Display128x64};
+use crate::framebuffer::Framebuffer;
+use crate::sh1106::Sh1106;
+use display_interface::DisplayError;
+use linux_embedded_hal::I2cdev;
+use ssd1306::mode::{BufferedGraphicsMode, DisplayConfig};
+use ssd1306::prelude::*;
+use ssd1306::{I2CDisplayInterface, Ssd1306};
+
+/// Raw contrast and precharge values, understood by both controllers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BrightnessLevel {
+    pub precharge: u8,
+    pub contrast: u8,
+}
+
+/// The OLED module behind the HAT, selected by `controller` and `height`.
+pub enum Panel {
+    Ssd1306x32(Box<Ssd1306Display128x32>),
+    Ssd1306x64(Box<Ssd1306Display128x64>),
+    Sh1106(Sh1106Display),
+}
+
+impl Panel {
+    pub fn new(
+        i2c: I2cdev,
+        display_config: &AppDisplayConfig,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        let rotation = match display_config.rotation {
+            0 => DisplayRotation::Rotate0,
+            180 => DisplayRotation::Rotate180,
+            other => {
+                return Err(
+                    format!("Unsupported display rotation {}, expected 0 or 180", other).into(),
+                )
+            }
+        };
+
+        let interface = I2CDisplayInterface::new(i2c);
+        match (display_config.controller, display_config.height) {
+            (Controller::Ssd1306, 32) => Ok(Panel::Ssd1306x32(Box::new(
+                Ssd1306::new(interface, DisplaySize128x32, rotation).into_buffered_graphics_mode(),
+            ))),
+            (Controller::Ssd1306, 64) => Ok(Panel::Ssd1306x64(Box::new(
+                Ssd1306::new(interface, DisplaySize128x64, rotation).into_buffered_graphics_mode(),
+            ))),
+            (Controller::Sh1106, 64) => Ok(Panel::Sh1106(Sh1106::new(
+                interface,
+                display_config.rotation == 180,
+            ))),
+            (controller, height) => Err(format!(
+                "Unsupported display: {:?} with height {}, expected ssd1306 128x32/128x64 or sh1106 128x64",
+                controller, height
+            )
+            .into()),
+        }
+    }
+
+    pub fn init(&mut self) -> Result<(), DisplayError> {
+        match self {
+            Panel::Ssd1306x32(display) => display.init(),
+            Panel::Ssd1306x64(display) => display.init(),
+            Panel::Sh1106(display) => display.init(),
+        }
+    }
+
+    pub fn dimensions(&self) -> (u8, u8) {
+        match self {
+            Panel::Ssd1306x32(display) => display.dimensions(),
+            Panel::Ssd1306x64(display) => display.dimensions(),
+            Panel::Sh1106(display) => display.dimensions(),
+        }
+    }
+
+    pub fn set_brightness(&mut self, level: BrightnessLevel) -> Result<(), DisplayError> {
+        let brightness = Brightness::custom(level.precharge, level.contrast);
+        match self {
+            Panel::Ssd1306x32(display) => display.set_brightness(brightness),
+            Panel::Ssd1306x64(display) => display.set_brightness(brightness),
+            Panel::Sh1106(display) => display.set_brightness(level.precharge, level.contrast),
+        }
+    }
+
+    pub fn set_display_on(&mut self, on: bool) -> Result<(), DisplayError> {
+        match self {
+            Panel::Ssd1306x32(display) => display.set_display_on(on),
+            Panel::Ssd1306x64(display) => display.set_display_on(on),
+            Panel::Sh1106(display) => display.set_display_on(on),
+        }
+    }
+
+    pub fn flush(&mut self, frame: &Framebuffer) -> Result<(), DisplayError> {
+        match self {
+            Panel::Ssd1306x32(display) => flush_ssd1306(display, frame),
+            Panel::Ssd1306x64(display) => flush_ssd1306(display, frame),
+            Panel::Sh1106(display) => display.flush(frame),
+        }
+    }
+}
+
+fn flush_ssd1306<SIZE: DisplaySize>(
+    display: &mut Ssd1306<I2CInterface<I2cdev>, SIZE, BufferedGraphicsMode<SIZE>>,
+    frame: &Framebuffer,
+) -> Result<(), DisplayError> {
+    display.clear_buffer();
+    for (x, y) in frame.lit_pixels() {
+        display.set_pixel(x, y, true);
+    }
+    display.flush()
+}
@@ -0,0 +1,234 @@
+use anyhow::Result;
+use log::debug;
+use serde::Serialize;
+use std::fs;
+use std::io::ErrorKind;
+use std::process::Command;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+/// How often the running monitor reads the firmware state. vcgencmd takes
+/// a while, so not on every refresh.
+const READ_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Firmware throttling state, readable without vcgencmd on newer kernels.
+const THROTTLED_PATH: &str = "/sys/devices/platform/soc/soc:firmware/get_throttled";
+
+/// Current frequency of the first core in kHz.
+const ARM_FREQ_PATH: &str = "/sys/devices/system/cpu/cpu0/cpufreq/scaling_cur_freq";
+
+/// Bits of the `get_throttled` mask for conditions that are active now. The
+/// same condition since boot is 16 bits higher.
+const THROTTLED_FLAGS: [(u32, &str); 4] = [
+    (0, UNDER_VOLTAGE),
+    (1, "frequency capped"),
+    (2, "throttled"),
+    (3, "soft temperature limit"),
+];
+
+const SINCE_BOOT_SHIFT: u32 = 16;
+
+const UNDER_VOLTAGE: &str = "under-voltage";
+
+/// Set once vcgencmd turned out not to be installed, e.g. on other boards.
+static VCGENCMD_MISSING: AtomicBool = AtomicBool::new(false);
+
+/// Power and clock state reported by the Raspberry Pi firmware. Every value
+/// is `None` on other boards or when the firmware can't be queried.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct PiHealth {
+    /// Raw `get_throttled` bitmask.
+    pub throttled: Option<u32>,
+    pub arm_clock_hz: Option<u64>,
+    pub core_clock_hz: Option<u64>,
+}
+
+impl PiHealth {
+    pub fn read() -> Self {
+        PiHealth {
+            throttled: read_throttled(),
+            arm_clock_hz: read_arm_clock(),
+            core_clock_hz: measure_clock("core"),
+        }
+    }
+
+    /// Whether the supply voltage is too low right now.
+    pub fn under_voltage(&self) -> bool {
+        self.current_flags().contains(&UNDER_VOLTAGE)
+    }
+
+    /// Names of the conditions active now.
+    pub fn current_flags(&self) -> Vec<&'static str> {
+        self.decoded().current
+    }
+
+    /// Names of the conditions that occurred at some point since boot.
+    pub fn since_boot_flags(&self) -> Vec<&'static str> {
+        self.decoded().since_boot
+    }
+
+    fn decoded(&self) -> Throttled {
+        decode_throttled(self.throttled.unwrap_or(0))
+    }
+}
+
+/// Names of the conditions set in a `get_throttled` mask.
+#[derive(Debug, PartialEq)]
+struct Throttled {
+    current: Vec<&'static str>,
+    since_boot: Vec<&'static str>,
+}
+
+fn decode_throttled(mask: u32) -> Throttled {
+    let flags = |shift: u32| {
+        THROTTLED_FLAGS
+            .iter()
+            .filter(|(bit, _)| mask & (1 << (bit + shift)) != 0)
+            .map(|(_, name)| *name)
+            .collect()
+    };
+    Throttled {
+        current: flags(0),
+        since_boot: flags(SINCE_BOOT_SHIFT),
+    }
+}
+
+/// Reads the firmware state on its own thread, so the main loop never waits
+/// for vcgencmd.
+pub struct PiHealthMonitor {
+    health: Arc<Mutex<PiHealth>>,
+}
+
+impl PiHealthMonitor {
+    pub fn start() -> Result<Self> {
+        let health = Arc::new(Mutex::new(PiHealth::default()));
+        let shared = Arc::clone(&health);
+        thread::Builder::new()
+            .name("pi-health".to_string())
+            .spawn(move || loop {
+                let health = PiHealth::read();
+                *shared.lock().unwrap() = health;
+                thread::sleep(READ_INTERVAL);
+            })?;
+        Ok(PiHealthMonitor { health })
+    }
+
+    /// The last reading, all `None` until the first one finished.
+    pub fn current(&self) -> PiHealth {
+        self.health.lock().unwrap().clone()
+    }
+}
+
+fn read_throttled() -> Option<u32> {
+    let text = match fs::read_to_string(THROTTLED_PATH) {
+        Ok(text) => text,
+        Err(_) => vcgencmd(&["get_throttled"])?,
+    };
+    parse_throttled(&text)
+}
+
+/// sysfs has plain hex, vcgencmd prints `throttled=0x50000`.
+fn parse_throttled(text: &str) -> Option<u32> {
+    let value = text.trim().trim_start_matches("throttled=");
+    let value = value.trim_start_matches("0x");
+    match u32::from_str_radix(value, 16) {
+        Ok(mask) => Some(mask),
+        Err(e) => {
+            debug!("Failed to parse throttled state {:?}: {}", text, e);
+            None
+        }
+    }
+}
+
+fn read_arm_clock() -> Option<u64> {
+    match fs::read_to_string(ARM_FREQ_PATH) {
+        Ok(khz) => khz.trim().parse::<u64>().ok().map(|khz| khz * 1000),
+        Err(_) => measure_clock("arm"),
+    }
+}
+
+/// Clock in Hz from `vcgencmd measure_clock`, which prints
+/// `frequency(48)=1500398464`.
+fn measure_clock(clock: &str) -> Option<u64> {
+    let output = vcgencmd(&["measure_clock", clock])?;
+    output.trim().split_once('=')?.1.parse().ok()
+}
+
+fn vcgencmd(args: &[&str]) -> Option<String> {
+    if VCGENCMD_MISSING.load(Ordering::Relaxed) {
+        return None;
+    }
+    match Command::new("vcgencmd").args(args).output() {
+        Ok(output) if output.status.success() => String::from_utf8(output.stdout).ok(),
+        Ok(output) => {
+            debug!(
+                "vcgencmd {} failed: {}",
+                args.join(" "),
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+            None
+        }
+        Err(e) if e.kind() == ErrorKind::NotFound => {
+            debug!("vcgencmd is not installed, not trying it again");
+            VCGENCMD_MISSING.store(true, Ordering::Relaxed);
+            None
+        }
+        Err(e) => {
+            debug!("vcgencmd {} failed: {}", args.join(" "), e);
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decodes_current_bits() {
+        let decoded = decode_throttled(0xf);
+        assert_eq!(
+            decoded.current,
+            [
+                "under-voltage",
+                "frequency capped",
+                "throttled",
+                "soft temperature limit"
+            ]
+        );
+        assert!(decoded.since_boot.is_empty());
+        assert_eq!(decode_throttled(0x4).current, ["throttled"]);
+    }
+
+    #[test]
+    fn decodes_sticky_bits() {
+        let decoded = decode_throttled(0x50000);
+        assert!(decoded.current.is_empty());
+        assert_eq!(decoded.since_boot, ["under-voltage", "throttled"]);
+        let decoded = decode_throttled(0x80008);
+        assert_eq!(decoded.current, ["soft temperature limit"]);
+        assert_eq!(decoded.since_boot, ["soft temperature limit"]);
+        assert_eq!(decode_throttled(0), decode_throttled(0xfff0_fff0));
+    }
+
+    #[test]
+    fn under_voltage_only_while_active() {
+        let health = |throttled| PiHealth {
+            throttled,
+            ..PiHealth::default()
+        };
+        assert!(health(Some(0x50005)).under_voltage());
+        assert!(!health(Some(0x50000)).under_voltage());
+        assert!(!health(None).under_voltage());
+    }
+
+    #[test]
+    fn parses_sysfs_and_vcgencmd_output() {
+        assert_eq!(parse_throttled("50005\n"), Some(0x50005));
+        assert_eq!(parse_throttled("throttled=0x50000\n"), Some(0x50000));
+        assert_eq!(parse_throttled("throttled=0x0"), Some(0));
+        assert_eq!(parse_throttled("error"), None);
+    }
+}
@@ -0,0 +1,47 @@
+use crate::config::ProcessSort;
+use sysinfo::{ProcessRefreshKind, ProcessesToUpdate, System};
+
+/// One row of the processes page.
+pub struct TopProcess {
+    pub name: String,
+    /// Share of all cores in percent, so it never exceeds 100.
+    pub cpu_usage: f32,
+    /// Share of the total RAM in percent.
+    pub memory_usage: f32,
+}
+
+/// Refreshes the process list of `sys` and returns the `count` processes
+/// using the most CPU or memory. Only called while the processes page is
+/// shown, so the regular refresh stays lightweight. CPU usage needs two
+/// refreshes, so it reads 0 for the first one after the page comes up.
+pub fn top_processes(sys: &mut System, sort: ProcessSort, count: usize) -> Vec<TopProcess> {
+    sys.refresh_processes_specifics(
+        ProcessesToUpdate::All,
+        true,
+        ProcessRefreshKind::nothing()
+            .with_cpu()
+            .with_memory()
+            .without_tasks(),
+    );
+
+    let cores = sys.cpus().len().max(1) as f32;
+    let total_memory = sys.total_memory().max(1) as f32;
+    let mut processes: Vec<TopProcess> = sys
+        .processes()
+        .values()
+        .filter(|process| process.thread_kind().is_none())
+        .map(|process| TopProcess {
+            name: process.name().to_string_lossy().to_string(),
+            cpu_usage: process.cpu_usage() / cores,
+            memory_usage: process.memory() as f32 / total_memory * 100.0,
+        })
+        .collect();
+
+    let key = |process: &TopProcess| match sort {
+        ProcessSort::Cpu => process.cpu_usage,
+        ProcessSort::Memory => process.memory_usage,
+    };
+    processes.sort_by(|a, b| key(b).total_cmp(&key(a)));
+    processes.truncate(count);
+    processes
+}
@@ -0,0 +1,239 @@
+use crate::config::{ScheduleConfig, ScheduleDisplay};
+use anyhow::{anyhow, Context, Result};
+use chrono::{Datelike, Duration, NaiveDateTime, NaiveTime, Weekday};
+
+/// A recurring wall-clock window such as "weekdays 22:00-07:00".
+///
+/// Windows whose end is before their start run past midnight and belong to
+/// the day they start on, so a Friday 22:00-07:00 window covers Saturday
+/// morning but not Friday morning.
+#[derive(Debug)]
+struct Window {
+    name: String,
+    days: [bool; 7],
+    start: NaiveTime,
+    end: NaiveTime,
+    display: Option<ScheduleDisplay>,
+    fan_temp_on: Option<f32>,
+}
+
+impl Window {
+    fn from_config(config: &ScheduleConfig) -> Result<Self> {
+        let name = config
+            .name
+            .clone()
+            .unwrap_or_else(|| format!("{}-{}", config.start, config.end));
+        let start = parse_time(&config.start).with_context(|| format!("schedule '{}'", name))?;
+        let end = parse_time(&config.end).with_context(|| format!("schedule '{}'", name))?;
+        if start == end {
+            return Err(anyhow!("schedule '{}': start and end must differ", name));
+        }
+
+        let mut days = [false; 7];
+        for day in &config.days {
+            for weekday in parse_days(day).with_context(|| format!("schedule '{}'", name))? {
+                days[weekday.num_days_from_monday() as usize] = true;
+            }
+        }
+
+        Ok(Window {
+            name,
+            days,
+            start,
+            end,
+            display: config.display,
+            fan_temp_on: config.fan_temp_on,
+        })
+    }
+
+    fn runs_on(&self, weekday: Weekday) -> bool {
+        self.days[weekday.num_days_from_monday() as usize]
+    }
+
+    fn contains(&self, now: NaiveDateTime) -> bool {
+        let time = now.time();
+        let today = now.weekday();
+        if self.start < self.end {
+            self.runs_on(today) && time >= self.start && time < self.end
+        } else {
+            let yesterday = (now - Duration::days(1)).weekday();
+            (self.runs_on(today) && time >= self.start)
+                || (self.runs_on(yesterday) && time < self.end)
+        }
+    }
+}
+
+/// What the active schedule windows ask for at a given moment.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ScheduleState {
+    pub display: ScheduleDisplay,
+    pub fan_temp_on: Option<f32>,
+}
+
+pub struct Schedule {
+    windows: Vec<Window>,
+}
+
+impl Schedule {
+    pub fn new(configs: &[ScheduleConfig]) -> Result<Self> {
+        let windows = configs
+            .iter()
+            .map(Window::from_config)
+            .collect::<Result<Vec<_>>>()?;
+        for window in &windows {
+            log::debug!(
+                "Schedule '{}': {} - {}, display {:?}, fan temp-on {:?}",
+                window.name,
+                window.start,
+                window.end,
+                window.display,
+                window.fan_temp_on
+            );
+        }
+        Ok(Schedule { windows })
+    }
+
+    /// Combines all windows active at `now`. When windows overlap the most
+    /// restrictive setting wins: `off` beats `dim` beats `on`, and the
+    /// highest fan threshold is used.
+    pub fn state_at(&self, now: NaiveDateTime) -> ScheduleState {
+        let mut state = ScheduleState {
+            display: ScheduleDisplay::On,
+            fan_temp_on: None,
+        };
+        for window in self.windows.iter().filter(|w| w.contains(now)) {
+            if let Some(display) = window.display {
+                state.display = match (state.display, display) {
+                    (ScheduleDisplay::Off, _) | (_, ScheduleDisplay::Off) => ScheduleDisplay::Off,
+                    (ScheduleDisplay::Dim, _) | (_, ScheduleDisplay::Dim) => ScheduleDisplay::Dim,
+                    _ => ScheduleDisplay::On,
+                };
+            }
+            if let Some(temp) = window.fan_temp_on {
+                state.fan_temp_on = Some(state.fan_temp_on.map_or(temp, |t| t.max(temp)));
+            }
+        }
+        state
+    }
+
+    pub fn now(&self) -> ScheduleState {
+        self.state_at(chrono::Local::now().naive_local())
+    }
+}
+
+fn parse_time(value: &str) -> Result<NaiveTime> {
+    if value == "24:00" {
+        return Ok(NaiveTime::MIN);
+    }
+    NaiveTime::parse_from_str(value, "%H:%M")
+        .with_context(|| format!("invalid time '{}', expected HH:MM", value))
+}
+
+fn parse_days(value: &str) -> Result<Vec<Weekday>> {
+    use Weekday::*;
+    let days = match value.to_lowercase().as_str() {
+        "daily" | "all" => vec![Mon, Tue, Wed, Thu, Fri, Sat, Sun],
+        "weekdays" => vec![Mon, Tue, Wed, Thu, Fri],
+        "weekends" | "weekend" => vec![Sat, Sun],
+        other => vec![other
+            .parse::<Weekday>()
+            .map_err(|_| anyhow!("invalid day '{}'", value))?],
+    };
+    Ok(days)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+
+    fn window(days: &[&str], start: &str, end: &str) -> ScheduleConfig {
+        ScheduleConfig {
+            name: None,
+            days: days.iter().map(|day| day.to_string()).collect(),
+            start: start.to_string(),
+            end: end.to_string(),
+            display: Some(ScheduleDisplay::Off),
+            fan_temp_on: None,
+        }
+    }
+
+    /// 2024-01-05 is a Friday.
+    fn at(day: u32, time: &str) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2024, 1, day)
+            .unwrap()
+            .and_time(NaiveTime::parse_from_str(time, "%H:%M").unwrap())
+    }
+
+    fn display_at(schedule: &Schedule, day: u32, time: &str) -> ScheduleDisplay {
+        schedule.state_at(at(day, time)).display
+    }
+
+    #[test]
+    fn window_within_a_day() {
+        let schedule = Schedule::new(&[window(&["weekdays"], "09:00", "17:00")]).unwrap();
+        assert_eq!(display_at(&schedule, 5, "08:59"), ScheduleDisplay::On);
+        assert_eq!(display_at(&schedule, 5, "09:00"), ScheduleDisplay::Off);
+        assert_eq!(display_at(&schedule, 5, "16:59"), ScheduleDisplay::Off);
+        assert_eq!(display_at(&schedule, 5, "17:00"), ScheduleDisplay::On);
+        // Saturday.
+        assert_eq!(display_at(&schedule, 6, "12:00"), ScheduleDisplay::On);
+    }
+
+    #[test]
+    fn window_past_midnight_belongs_to_its_start_day() {
+        let schedule = Schedule::new(&[window(&["fri"], "22:00", "07:00")]).unwrap();
+        // Friday morning is the end of Thursday's window, which doesn't run.
+        assert_eq!(display_at(&schedule, 5, "06:00"), ScheduleDisplay::On);
+        assert_eq!(display_at(&schedule, 5, "21:59"), ScheduleDisplay::On);
+        assert_eq!(display_at(&schedule, 5, "22:00"), ScheduleDisplay::Off);
+        assert_eq!(display_at(&schedule, 6, "06:59"), ScheduleDisplay::Off);
+        assert_eq!(display_at(&schedule, 6, "07:00"), ScheduleDisplay::On);
+        assert_eq!(display_at(&schedule, 6, "22:00"), ScheduleDisplay::On);
+    }
+
+    #[test]
+    fn window_until_24_00_ends_at_midnight() {
+        let schedule = Schedule::new(&[window(&["fri"], "20:00", "24:00")]).unwrap();
+        assert_eq!(display_at(&schedule, 5, "23:59"), ScheduleDisplay::Off);
+        assert_eq!(display_at(&schedule, 6, "00:00"), ScheduleDisplay::On);
+        assert_eq!(display_at(&schedule, 6, "20:00"), ScheduleDisplay::On);
+    }
+
+    #[test]
+    fn overlapping_windows_use_the_most_restrictive_setting() {
+        let mut dim = window(&["daily"], "18:00", "08:00");
+        dim.display = Some(ScheduleDisplay::Dim);
+        dim.fan_temp_on = Some(60.0);
+        let mut quiet = window(&["daily"], "22:00", "06:00");
+        quiet.fan_temp_on = Some(70.0);
+        let schedule = Schedule::new(&[dim, quiet]).unwrap();
+
+        let evening = schedule.state_at(at(5, "19:00"));
+        assert_eq!(evening.display, ScheduleDisplay::Dim);
+        assert_eq!(evening.fan_temp_on, Some(60.0));
+        let night = schedule.state_at(at(5, "23:00"));
+        assert_eq!(night.display, ScheduleDisplay::Off);
+        assert_eq!(night.fan_temp_on, Some(70.0));
+        assert_eq!(schedule.state_at(at(5, "12:00")).fan_temp_on, None);
+    }
+
+    #[test]
+    fn invalid_windows_are_rejected() {
+        let error = |config| format!("{:#}", Schedule::new(&[config]).err().unwrap());
+        assert!(error(window(&["daily"], "07:00", "07:00")).contains("must differ"));
+        assert!(error(window(&["daily"], "00:00", "24:00")).contains("must differ"));
+        assert!(error(window(&["daily"], "25:00", "07:00")).contains("invalid time '25:00'"));
+        assert!(error(window(&["daily"], "7pm", "07:00")).contains("expected HH:MM"));
+        assert!(error(window(&["someday"], "22:00", "07:00")).contains("invalid day 'someday'"));
+    }
+
+    #[test]
+    fn day_names_and_groups() {
+        use Weekday::*;
+        assert_eq!(parse_days("Weekends").unwrap(), [Sat, Sun]);
+        assert_eq!(parse_days("daily").unwrap().len(), 7);
+        assert_eq!(parse_days("tuesday").unwrap(), [Tue]);
+        assert_eq!(parse_days("Wed").unwrap(), [Wed]);
+    }
+}
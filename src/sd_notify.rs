@@ -0,0 +1,188 @@
+use anyhow::{anyhow, Context, Result};
+use log::{debug, info, warn};
+use std::env;
+use std::os::linux::net::SocketAddrExt;
+use std::os::unix::net::{SocketAddr, UnixDatagram};
+use std::time::{Duration, Instant};
+
+/// How often the status is updated when systemd doesn't expect watchdog
+/// pings more often.
+const STATUS_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Tells systemd about the service state over `$NOTIFY_SOCKET`, see
+/// sd_notify(3). Only set up when the unit has `Type=notify`.
+pub struct SdNotify {
+    socket: UnixDatagram,
+    address: SocketAddr,
+    /// Time between two pings, half the unit's `WatchdogSec`. `None` without
+    /// a watchdog.
+    watchdog_interval: Option<Duration>,
+    last_update: Option<Instant>,
+    /// Sent again with the pings between two updates.
+    status: String,
+    failed: bool,
+}
+
+impl SdNotify {
+    /// Connects to the socket from the environment, `None` when the monitor
+    /// wasn't started by systemd with `Type=notify`.
+    pub fn from_env() -> Option<Self> {
+        let path = env::var_os("NOTIFY_SOCKET")?;
+        match Self::connect(&path.to_string_lossy()) {
+            Ok(notify) => {
+                if let Some(interval) = notify.watchdog_interval {
+                    info!("Pinging the systemd watchdog every {:?}", interval);
+                }
+                Some(notify)
+            }
+            Err(e) => {
+                warn!("systemd notifications disabled: {:#}", e);
+                None
+            }
+        }
+    }
+
+    fn connect(path: &str) -> Result<Self> {
+        // A leading @ stands for a socket in the abstract namespace.
+        let address = match path.strip_prefix('@') {
+            Some(name) => SocketAddr::from_abstract_name(name),
+            None => SocketAddr::from_pathname(path),
+        }
+        .with_context(|| format!("invalid NOTIFY_SOCKET {}", path))?;
+        let socket = UnixDatagram::unbound()?;
+        Ok(SdNotify {
+            socket,
+            address,
+            watchdog_interval: watchdog_interval()?,
+            last_update: None,
+            status: String::new(),
+            failed: false,
+        })
+    }
+
+    /// Reports the start as complete.
+    pub fn ready(&mut self, status: &str) {
+        self.send(&format!("READY=1\nSTATUS={}", status));
+    }
+
+    /// Pings the watchdog and updates the status, if due. Called on every
+    /// pass of the main loop, so a hanging loop stops the pings.
+    pub fn update(&mut self, now: Instant, status: &str) {
+        status.clone_into(&mut self.status);
+        self.keep_alive(now);
+    }
+
+    /// Pings the watchdog with the last status, if due. Called while the
+    /// main loop waits for the next refresh, which may be longer than the
+    /// watchdog allows.
+    pub fn keep_alive(&mut self, now: Instant) {
+        if self.next_update().is_some_and(|next| now < next) {
+            return;
+        }
+        self.last_update = Some(now);
+        let state = match self.watchdog_interval {
+            Some(_) => format!("WATCHDOG=1\nSTATUS={}", self.status),
+            None => format!("STATUS={}", self.status),
+        };
+        self.send(&state);
+    }
+
+    /// When the next ping or status update is due, `None` if right away.
+    pub fn next_update(&self) -> Option<Instant> {
+        let interval = self
+            .watchdog_interval
+            .map_or(STATUS_INTERVAL, |watchdog| watchdog.min(STATUS_INTERVAL));
+        self.last_update.map(|last| last + interval)
+    }
+
+    /// Reports that the service is shutting down.
+    pub fn stopping(&mut self) {
+        self.send("STOPPING=1\nSTATUS=Stopping");
+    }
+
+    /// Sets the status shown by `systemctl status`, e.g. before exiting.
+    pub fn status(&mut self, status: &str) {
+        self.send(&format!("STATUS={}", status));
+    }
+
+    fn send(&mut self, state: &str) {
+        match self.socket.send_to_addr(state.as_bytes(), &self.address) {
+            Ok(_) => {
+                debug!("Notified systemd: {}", state.replace('\n', ", "));
+                self.failed = false;
+            }
+            // Logged once, until sending works again.
+            Err(e) if !self.failed => {
+                warn!("Failed to notify systemd: {}", e);
+                self.failed = true;
+            }
+            Err(e) => debug!("Failed to notify systemd: {}", e),
+        }
+    }
+}
+
+/// Half of `$WATCHDOG_USEC`, if the watchdog is meant for this process.
+fn watchdog_interval() -> Result<Option<Duration>> {
+    let Ok(usec) = env::var("WATCHDOG_USEC") else {
+        return Ok(None);
+    };
+    if let Ok(pid) = env::var("WATCHDOG_PID") {
+        if pid.parse::<u32>().ok() != Some(std::process::id()) {
+            return Ok(None);
+        }
+    }
+    let usec: u64 = usec
+        .parse()
+        .map_err(|_| anyhow!("invalid WATCHDOG_USEC {}", usec))?;
+    Ok(Some(Duration::from_micros(usec) / 2))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A notifier with a 10 s watchdog ping interval that sends to the
+    /// returned socket.
+    fn stand_in(name: &str) -> (SdNotify, UnixDatagram) {
+        let path = env::temp_dir().join(format!(
+            "rustberry-notify-{}-{}.sock",
+            std::process::id(),
+            name
+        ));
+        let _ = std::fs::remove_file(&path);
+        let systemd = UnixDatagram::bind(&path).unwrap();
+        systemd.set_nonblocking(true).unwrap();
+        let mut notify = SdNotify::connect(&path.to_string_lossy()).unwrap();
+        notify.watchdog_interval = Some(Duration::from_secs(10));
+        (notify, systemd)
+    }
+
+    fn received(systemd: &UnixDatagram) -> Vec<String> {
+        let mut buffer = [0; 256];
+        std::iter::from_fn(|| {
+            let length = systemd.recv(&mut buffer).ok()?;
+            Some(String::from_utf8_lossy(&buffer[..length]).to_string())
+        })
+        .collect()
+    }
+
+    #[test]
+    fn keep_alive_pings_with_the_last_status_when_due() {
+        let (mut notify, systemd) = stand_in("ping");
+        let start = Instant::now();
+        assert_eq!(notify.next_update(), None);
+
+        notify.update(start, "CPU 5%");
+        assert_eq!(received(&systemd), ["WATCHDOG=1\nSTATUS=CPU 5%"]);
+        assert_eq!(notify.next_update(), Some(start + Duration::from_secs(10)));
+
+        // Waiting for a long refresh interval.
+        notify.keep_alive(start + Duration::from_secs(9));
+        assert!(received(&systemd).is_empty());
+        notify.keep_alive(start + Duration::from_secs(10));
+        assert_eq!(received(&systemd), ["WATCHDOG=1\nSTATUS=CPU 5%"]);
+
+        notify.update(start + Duration::from_secs(20), "CPU 7%");
+        assert_eq!(received(&systemd), ["WATCHDOG=1\nSTATUS=CPU 7%"]);
+    }
+}
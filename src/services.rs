@@ -0,0 +1,328 @@
+use anyhow::{anyhow, Context, Result};
+use log::{debug, warn};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::process::Command;
+use std::time::{Duration, Instant};
+
+/// State of one watched unit as reported by systemd.
+#[derive(Debug, Clone, Serialize)]
+pub struct UnitStatus {
+    pub name: String,
+    /// `active`, `inactive`, `failed`, `activating`, ...
+    pub active_state: String,
+    /// `running`, `exited`, `dead`, ...
+    pub sub_state: String,
+    /// `not-found` if systemd doesn't know the unit.
+    pub load_state: String,
+}
+
+impl UnitStatus {
+    pub fn is_failed(&self) -> bool {
+        self.active_state == "failed"
+    }
+}
+
+/// Where unit states come from. Implemented by `Systemctl`; other
+/// implementations can supply states without a running systemd.
+pub trait UnitSource {
+    /// States of `units`, in the same order.
+    fn unit_states(&mut self, units: &[String]) -> Result<Vec<UnitStatus>>;
+}
+
+/// Asks systemd through `systemctl show`.
+pub struct Systemctl;
+
+impl UnitSource for Systemctl {
+    fn unit_states(&mut self, units: &[String]) -> Result<Vec<UnitStatus>> {
+        let output = Command::new("systemctl")
+            .args(["show", "--property=Id,LoadState,ActiveState,SubState", "--"])
+            .args(units)
+            .output()
+            .context("failed to run systemctl")?;
+        if !output.status.success() {
+            return Err(anyhow!(
+                "systemctl show failed: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        Ok(parse_show(&stdout, units))
+    }
+}
+
+/// Parses `systemctl show` output: one block of `Key=value` lines per unit,
+/// separated by blank lines, in the order the units were given.
+fn parse_show(output: &str, units: &[String]) -> Vec<UnitStatus> {
+    let mut blocks = output.split("\n\n");
+    units
+        .iter()
+        .map(|name| {
+            let properties: HashMap<&str, &str> = blocks
+                .next()
+                .unwrap_or_default()
+                .lines()
+                .filter_map(|line| line.split_once('='))
+                .collect();
+            let property = |key| properties.get(key).unwrap_or(&"unknown").to_string();
+            UnitStatus {
+                name: name.clone(),
+                active_state: property("ActiveState"),
+                sub_state: property("SubState"),
+                load_state: property("LoadState"),
+            }
+        })
+        .collect()
+}
+
+/// Keeps the states of the configured units, refreshed every `interval`.
+pub struct ServiceWatch {
+    source: Box<dyn UnitSource>,
+    units: Vec<String>,
+    interval: Duration,
+    last_check: Option<Instant>,
+    statuses: Vec<UnitStatus>,
+    failed_to_read: bool,
+}
+
+impl ServiceWatch {
+    pub fn new(source: Box<dyn UnitSource>, units: Vec<String>, interval: Duration) -> Self {
+        ServiceWatch {
+            source,
+            units,
+            interval,
+            last_check: None,
+            statuses: Vec::new(),
+            failed_to_read: false,
+        }
+    }
+
+    /// Reads the unit states if they are due. On errors the previous states
+    /// are kept.
+    pub fn poll(&mut self, now: Instant) {
+        if self.units.is_empty()
+            || self
+                .last_check
+                .is_some_and(|last| now.duration_since(last) < self.interval)
+        {
+            return;
+        }
+        self.last_check = Some(now);
+        match self.source.unit_states(&self.units) {
+            Ok(statuses) => {
+                self.statuses = statuses;
+                self.failed_to_read = false;
+            }
+            // Logged once, until reading works again.
+            Err(e) if !self.failed_to_read => {
+                warn!("Failed to read unit states: {:#}", e);
+                self.failed_to_read = true;
+            }
+            Err(e) => debug!("Failed to read unit states: {:#}", e),
+        }
+    }
+
+    pub fn statuses(&self) -> &[UnitStatus] {
+        &self.statuses
+    }
+
+    /// Names of the units that are in the failed state.
+    pub fn failed_units(&self) -> Vec<String> {
+        self.statuses
+            .iter()
+            .filter(|status| status.is_failed())
+            .map(|status| status.name.clone())
+            .collect()
+    }
+
+    /// Number of failed units, `None` until their states could be read.
+    pub fn failed_count(&self) -> Option<usize> {
+        if self.statuses.is_empty() {
+            return None;
+        }
+        Some(
+            self.statuses
+                .iter()
+                .filter(|status| status.is_failed())
+                .count(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+    use std::collections::VecDeque;
+    use std::rc::Rc;
+
+    /// Output of `systemctl show --property=Id,LoadState,ActiveState,SubState`
+    /// for a running, a failed and an unknown unit.
+    const SHOW_OUTPUT: &str = "Id=nginx.service
+LoadState=loaded
+ActiveState=active
+SubState=running
+
+Id=backup.service
+LoadState=loaded
+ActiveState=failed
+SubState=failed
+
+Id=nosuch.service
+LoadState=not-found
+ActiveState=inactive
+SubState=dead
+";
+
+    fn names(units: &[&str]) -> Vec<String> {
+        units.iter().map(|unit| unit.to_string()).collect()
+    }
+
+    fn status(name: &str, active_state: &str) -> UnitStatus {
+        UnitStatus {
+            name: name.to_string(),
+            active_state: active_state.to_string(),
+            sub_state: "dead".to_string(),
+            load_state: "loaded".to_string(),
+        }
+    }
+
+    #[test]
+    fn parse_show_reads_one_block_per_unit() {
+        let units = names(&["nginx.service", "backup.service", "nosuch.service"]);
+        let statuses = parse_show(SHOW_OUTPUT, &units);
+
+        let states: Vec<(&str, &str, &str, &str)> = statuses
+            .iter()
+            .map(|s| {
+                (
+                    s.name.as_str(),
+                    s.load_state.as_str(),
+                    s.active_state.as_str(),
+                    s.sub_state.as_str(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            states,
+            [
+                ("nginx.service", "loaded", "active", "running"),
+                ("backup.service", "loaded", "failed", "failed"),
+                ("nosuch.service", "not-found", "inactive", "dead"),
+            ]
+        );
+        let failed: Vec<bool> = statuses.iter().map(UnitStatus::is_failed).collect();
+        assert_eq!(failed, [false, true, false]);
+    }
+
+    #[test]
+    fn parse_show_marks_missing_blocks_unknown() {
+        let units = names(&["nginx.service", "extra.service"]);
+        let statuses = parse_show("Id=nginx.service\nActiveState=active\n", &units);
+        assert_eq!(statuses.len(), 2);
+        assert_eq!(statuses[0].active_state, "active");
+        assert_eq!(statuses[0].load_state, "unknown");
+        assert_eq!(statuses[1].name, "extra.service");
+        assert_eq!(statuses[1].active_state, "unknown");
+        assert!(!statuses[1].is_failed());
+    }
+
+    /// Hands out queued answers and counts the calls.
+    #[derive(Clone, Default)]
+    struct FakeSource {
+        answers: Rc<RefCell<VecDeque<Result<Vec<UnitStatus>>>>>,
+        calls: Rc<RefCell<usize>>,
+    }
+
+    impl FakeSource {
+        fn answer(&self, answer: Result<Vec<UnitStatus>>) {
+            self.answers.borrow_mut().push_back(answer);
+        }
+
+        fn calls(&self) -> usize {
+            *self.calls.borrow()
+        }
+    }
+
+    impl UnitSource for FakeSource {
+        fn unit_states(&mut self, _units: &[String]) -> Result<Vec<UnitStatus>> {
+            *self.calls.borrow_mut() += 1;
+            self.answers
+                .borrow_mut()
+                .pop_front()
+                .unwrap_or_else(|| Err(anyhow!("no answer queued")))
+        }
+    }
+
+    const INTERVAL: Duration = Duration::from_secs(10);
+
+    fn watch(source: &FakeSource, units: &[&str]) -> ServiceWatch {
+        ServiceWatch::new(Box::new(source.clone()), names(units), INTERVAL)
+    }
+
+    #[test]
+    fn watch_follows_failed_units_until_they_recover() {
+        let source = FakeSource::default();
+        let mut watch = watch(&source, &["nginx.service", "backup.service"]);
+        let start = Instant::now();
+        assert_eq!(watch.failed_count(), None);
+
+        source.answer(Ok(vec![
+            status("nginx.service", "active"),
+            status("backup.service", "failed"),
+        ]));
+        watch.poll(start);
+        assert_eq!(watch.failed_units(), ["backup.service"]);
+        assert_eq!(watch.failed_count(), Some(1));
+
+        source.answer(Ok(vec![
+            status("nginx.service", "active"),
+            status("backup.service", "active"),
+        ]));
+        watch.poll(start + INTERVAL);
+        assert!(watch.failed_units().is_empty());
+        assert_eq!(watch.failed_count(), Some(0));
+        assert_eq!(watch.statuses()[1].active_state, "active");
+    }
+
+    #[test]
+    fn watch_polls_once_per_interval() {
+        let source = FakeSource::default();
+        let mut watch = watch(&source, &["nginx.service"]);
+        let start = Instant::now();
+        source.answer(Ok(vec![status("nginx.service", "active")]));
+        source.answer(Ok(vec![status("nginx.service", "failed")]));
+
+        watch.poll(start);
+        watch.poll(start + INTERVAL / 2);
+        assert_eq!(source.calls(), 1);
+        assert!(watch.failed_units().is_empty());
+
+        watch.poll(start + INTERVAL);
+        assert_eq!(source.calls(), 2);
+        assert_eq!(watch.failed_units(), ["nginx.service"]);
+    }
+
+    #[test]
+    fn watch_keeps_the_last_states_when_reading_fails() {
+        let source = FakeSource::default();
+        let mut watch = watch(&source, &["backup.service"]);
+        let start = Instant::now();
+        source.answer(Ok(vec![status("backup.service", "failed")]));
+        watch.poll(start);
+
+        source.answer(Err(anyhow!("systemctl show failed")));
+        watch.poll(start + INTERVAL);
+        assert_eq!(source.calls(), 2);
+        assert_eq!(watch.failed_units(), ["backup.service"]);
+    }
+
+    #[test]
+    fn watch_without_units_never_asks() {
+        let source = FakeSource::default();
+        let mut watch = watch(&source, &[]);
+        watch.poll(Instant::now());
+        assert_eq!(source.calls(), 0);
+        assert_eq!(watch.failed_count(), None);
+    }
+}
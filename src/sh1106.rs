@@ -0,0 +1,87 @@
+use crate::framebuffer::Framebuffer;
+use display_interface::{DataFormat, DisplayError, WriteOnlyDataCommand};
+
+const WIDTH: u32 = 128;
+const HEIGHT: u32 = 64;
+
+/// The SH1106 has 132 columns of RAM, the 128 visible ones start at column 2.
+const COLUMN_OFFSET: u8 = 2;
+
+/// Minimal SH1106 driver for 128x64 modules. The controller is mostly
+/// command compatible with the SSD1306 but only supports page addressing and
+/// has a different column offset, so the `ssd1306` crate can't drive it.
+pub struct Sh1106<DI> {
+    interface: DI,
+    flipped: bool,
+}
+
+impl<DI: WriteOnlyDataCommand> Sh1106<DI> {
+    pub fn new(interface: DI, flipped: bool) -> Self {
+        Sh1106 { interface, flipped }
+    }
+
+    pub fn dimensions(&self) -> (u8, u8) {
+        (WIDTH as u8, HEIGHT as u8)
+    }
+
+    pub fn init(&mut self) -> Result<(), DisplayError> {
+        let (segment_remap, com_scan) = if self.flipped {
+            (0xA0, 0xC0)
+        } else {
+            (0xA1, 0xC8)
+        };
+        self.commands(&[
+            0xAE, // display off
+            0xD5,
+            0x80, // clock divide ratio / oscillator frequency
+            0xA8,
+            0x3F, // multiplex ratio: 64
+            0xD3,
+            0x00, // display offset
+            0x40, // start line 0
+            0xAD,
+            0x8B, // DC-DC converter on
+            segment_remap,
+            com_scan,
+            0xDA,
+            0x12, // alternative COM pin configuration
+            0xD9,
+            0x22, // precharge period
+            0xDB,
+            0x40, // VCOMH deselect level
+            0xA4, // show RAM contents
+            0xA6, // normal, not inverted
+            0xAF, // display on
+        ])
+    }
+
+    pub fn set_brightness(&mut self, precharge: u8, contrast: u8) -> Result<(), DisplayError> {
+        self.commands(&[0xD9, ((precharge & 0xF) << 4) | 0x1, 0x81, contrast])
+    }
+
+    pub fn set_display_on(&mut self, on: bool) -> Result<(), DisplayError> {
+        self.commands(&[if on { 0xAF } else { 0xAE }])
+    }
+
+    pub fn flush(&mut self, frame: &Framebuffer) -> Result<(), DisplayError> {
+        let mut buffer = [0u8; (WIDTH * HEIGHT / 8) as usize];
+        for (x, y) in frame.lit_pixels() {
+            if x < WIDTH && y < HEIGHT {
+                buffer[((y / 8) * WIDTH + x) as usize] |= 1 << (y % 8);
+            }
+        }
+        for (page_index, page) in buffer.chunks(WIDTH as usize).enumerate() {
+            self.commands(&[
+                0xB0 + page_index as u8,
+                COLUMN_OFFSET & 0x0F,
+                0x10 | (COLUMN_OFFSET >> 4),
+            ])?;
+            self.interface.send_data(DataFormat::U8(page))?;
+        }
+        Ok(())
+    }
+
+    fn commands(&mut self, commands: &[u8]) -> Result<(), DisplayError> {
+        self.interface.send_commands(DataFormat::U8(commands))
+    }
+}
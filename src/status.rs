@@ -0,0 +1,218 @@
+use crate::config::{Config, FormatConfig};
+use crate::pi_health::PiHealth;
+use crate::services::{ServiceWatch, UnitStatus};
+use crate::units;
+use crate::SystemStats;
+use anyhow::{Context, Result};
+use log::{debug, warn};
+use serde::Serialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// One set of readings as printed by `status --json` and written to the
+/// status file. Raw values for scripts, formatted ones as shown on the
+/// display.
+#[derive(Debug, Serialize)]
+pub struct StatusReport {
+    pub hostname: String,
+    pub ip_address: Option<String>,
+    pub cpu_usage: f32,
+    pub cpu_temp: Option<f32>,
+    pub ram_used: u64,
+    pub ram_total: u64,
+    pub swap_used: u64,
+    pub swap_total: u64,
+    pub disk_used: u64,
+    pub disk_total: u64,
+    /// Only known to the running monitor.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fan_running: Option<bool>,
+    pub fan: FanThresholds,
+    pub pi_health: PiHealth,
+    /// Failed systemd units, `None` without a watch list or when their
+    /// states can't be read.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub failed_units: Option<usize>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub units: Vec<UnitStatus>,
+    pub formatted: FormattedReadings,
+}
+
+#[derive(Debug, Serialize)]
+pub struct FanThresholds {
+    pub temp_on: f32,
+    pub temp_off: f32,
+    pub safety_temp: f32,
+}
+
+#[derive(Debug, Serialize)]
+pub struct FormattedReadings {
+    pub cpu: String,
+    pub temp: Option<String>,
+    pub ram: String,
+    pub disk: String,
+}
+
+impl StatusReport {
+    pub fn new(
+        stats: &SystemStats,
+        config: &Config,
+        fan_running: Option<bool>,
+        service_watch: &ServiceWatch,
+    ) -> Self {
+        let readings = stats.readings(&config.format);
+        StatusReport {
+            hostname: stats.hostname.clone(),
+            ip_address: stats.ip_address.clone(),
+            cpu_usage: stats.cpu_usage,
+            cpu_temp: stats.cpu_temp,
+            ram_used: stats.ram_used,
+            ram_total: stats.memory.total,
+            swap_used: stats.memory.swap_used,
+            swap_total: stats.memory.swap_total,
+            disk_used: stats.disk_used,
+            disk_total: stats.disk_total,
+            fan_running,
+            fan: FanThresholds {
+                temp_on: config.fan.temp_on,
+                temp_off: config.fan.temp_off,
+                safety_temp: config.fan.safety_temp,
+            },
+            pi_health: stats.pi_health.clone(),
+            failed_units: service_watch.failed_count(),
+            units: service_watch.statuses().to_vec(),
+            formatted: FormattedReadings {
+                cpu: readings.cpu.to_string(),
+                temp: readings.temp.as_ref().map(ToString::to_string),
+                ram: readings.ram.to_string(),
+                disk: readings.disk.to_string(),
+            },
+        }
+    }
+
+    pub fn print(&self, format: &FormatConfig) {
+        let temp = |celsius| units::format_temp(celsius, &format.temp).to_string();
+        let formatted = &self.formatted;
+        println!("Hostname:     {}", self.hostname);
+        println!(
+            "IP address:   {}",
+            self.ip_address.as_deref().unwrap_or("none")
+        );
+        println!("CPU:          {}", formatted.cpu);
+        println!(
+            "Temperature:  {}",
+            formatted.temp.as_deref().unwrap_or("sensor error")
+        );
+        println!("RAM:          {}", formatted.ram);
+        if self.swap_total > 0 {
+            let size = |bytes| units::human_size(bytes, format.ram.precision, format.ram.prefix);
+            println!(
+                "Swap:         {}/{}",
+                size(self.swap_used),
+                size(self.swap_total)
+            );
+        }
+        println!("Disk:         {}", formatted.disk);
+        println!(
+            "Fan:          on at {}, off at {}, safety {}",
+            temp(self.fan.temp_on),
+            temp(self.fan.temp_off),
+            temp(self.fan.safety_temp)
+        );
+
+        let health = &self.pi_health;
+        if health.throttled.is_some() {
+            let list = |flags: Vec<&str>| {
+                if flags.is_empty() {
+                    "none".to_string()
+                } else {
+                    flags.join(", ")
+                }
+            };
+            println!("Throttling:   {}", list(health.current_flags()));
+            println!("  since boot: {}", list(health.since_boot_flags()));
+        }
+        if let Some(failed) = self.failed_units {
+            let failed_names: Vec<&str> = self
+                .units
+                .iter()
+                .filter(|unit| unit.is_failed())
+                .map(|unit| unit.name.as_str())
+                .collect();
+            match failed {
+                0 => println!("Services:     {} watched, none failed", self.units.len()),
+                _ => println!(
+                    "Services:     {} watched, {} failed: {}",
+                    self.units.len(),
+                    failed,
+                    failed_names.join(", ")
+                ),
+            }
+        }
+        let mhz = |hz: Option<u64>| hz.map_or("?".to_string(), |hz| (hz / 1_000_000).to_string());
+        if health.arm_clock_hz.is_some() || health.core_clock_hz.is_some() {
+            println!(
+                "Clocks:       ARM {} MHz, core {} MHz",
+                mhz(health.arm_clock_hz),
+                mhz(health.core_clock_hz)
+            );
+        }
+    }
+}
+
+/// Keeps the status file up to date, rewriting it only when the readings
+/// change.
+pub struct StatusFile {
+    path: PathBuf,
+    /// What the file holds, empty until written.
+    written: Vec<u8>,
+    /// Reported once until writing works again, not on every tick.
+    failed: bool,
+}
+
+impl StatusFile {
+    pub fn new(path: PathBuf) -> Self {
+        StatusFile {
+            path,
+            written: Vec::new(),
+            failed: false,
+        }
+    }
+
+    /// Writes `report` unless the file already holds the same.
+    pub fn update(&mut self, report: &StatusReport) {
+        let json = match serde_json::to_vec_pretty(report) {
+            Ok(json) => json,
+            Err(e) => {
+                warn!("Failed to serialize the status: {}", e);
+                return;
+            }
+        };
+        if json == self.written {
+            return;
+        }
+        match write_file(&self.path, &json) {
+            Ok(()) => {
+                self.written = json;
+                self.failed = false;
+            }
+            Err(e) if !self.failed => {
+                warn!("Failed to write status file: {:#}", e);
+                self.failed = true;
+            }
+            Err(e) => debug!("Failed to write status file: {:#}", e),
+        }
+    }
+}
+
+/// Replaces `path` with `json`. Readers never see a partly written file.
+fn write_file(path: &Path, json: &[u8]) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let tmp_path = path.with_extension("tmp");
+    fs::write(&tmp_path, json)
+        .with_context(|| format!("failed to write {}", tmp_path.display()))?;
+    fs::rename(&tmp_path, path).with_context(|| format!("failed to replace {}", path.display()))?;
+    Ok(())
+}
@@ -0,0 +1,64 @@
+use chrono::{DateTime, Local};
+use log::debug;
+use std::fs;
+use sysinfo::System;
+
+/// Board name set by the firmware, NUL terminated.
+const MODEL_PATH: &str = "/proc/device-tree/model";
+
+/// What identifies the node, for the system page.
+pub struct SystemInfo {
+    pub hostname: String,
+    /// Seconds since boot.
+    pub uptime: u64,
+    pub boot_time: Option<DateTime<Local>>,
+    /// Distribution name and version, e.g. "Debian GNU/Linux 12".
+    pub os: String,
+    pub kernel: String,
+    /// Board model, `None` on boards without a device tree.
+    pub model: Option<String>,
+    pub version: &'static str,
+}
+
+impl SystemInfo {
+    pub fn read() -> Self {
+        let os = [System::name(), System::os_version()]
+            .into_iter()
+            .flatten()
+            .collect::<Vec<_>>()
+            .join(" ");
+        SystemInfo {
+            hostname: System::host_name().unwrap_or_default(),
+            uptime: System::uptime(),
+            boot_time: DateTime::from_timestamp(System::boot_time() as i64, 0)
+                .map(|time| time.with_timezone(&Local)),
+            os,
+            kernel: System::kernel_version().unwrap_or_default(),
+            model: read_model(),
+            version: env!("CARGO_PKG_VERSION"),
+        }
+    }
+}
+
+fn read_model() -> Option<String> {
+    match fs::read_to_string(MODEL_PATH) {
+        Ok(model) => Some(model.trim_end_matches('\0').trim().to_string()),
+        Err(e) => {
+            debug!("No board model in {}: {}", MODEL_PATH, e);
+            None
+        }
+    }
+}
+
+/// Uptime as "3d 4h 12m", leaving out leading zero units.
+pub fn format_uptime(seconds: u64) -> String {
+    let minutes = seconds / 60;
+    let (days, hours, minutes) = (minutes / (24 * 60), minutes / 60 % 24, minutes % 60);
+    if days > 0 {
+        format!("{}d {}h {}m", days, hours, minutes)
+    } else if hours > 0 {
+        format!("{}h {}m", hours, minutes)
+    } else {
+        format!("{}m", minutes)
+    }
+}
@@ -0,0 +1,190 @@
+use crate::config::{PercentFormat, SizeDisplay, SizeFormat, SizePrefix, TempFormat, TempUnit};
+use std::fmt;
+
+/// Unit drawn after a formatted value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Unit {
+    Percent,
+    Celsius,
+    Fahrenheit,
+    /// The unit is already part of the value, e.g. "1.2G/4G".
+    None,
+}
+
+impl Unit {
+    pub fn symbol(self) -> &'static str {
+        match self {
+            Unit::Percent => "%",
+            Unit::Celsius => "°C",
+            Unit::Fahrenheit => "°F",
+            Unit::None => "",
+        }
+    }
+}
+
+/// A value formatted according to the `[format]` settings.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Reading {
+    pub value: String,
+    pub unit: Unit,
+}
+
+impl fmt::Display for Reading {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}{}", self.value, self.unit.symbol())
+    }
+}
+
+pub fn format_percent(percent: f64, format: &PercentFormat) -> Reading {
+    Reading {
+        value: format!("{:.*}", format.precision, percent),
+        unit: Unit::Percent,
+    }
+}
+
+pub fn format_temp(celsius: f32, format: &TempFormat) -> Reading {
+    let (value, unit) = match format.unit {
+        TempUnit::Celsius => (celsius, Unit::Celsius),
+        TempUnit::Fahrenheit => (celsius * 9.0 / 5.0 + 32.0, Unit::Fahrenheit),
+    };
+    Reading {
+        value: format!("{:.*}", format.precision, value),
+        unit,
+    }
+}
+
+/// Formats memory or disk usage from the used and total number of bytes.
+pub fn format_size(used: u64, total: u64, format: &SizeFormat) -> Reading {
+    match format.show {
+        SizeDisplay::Percent => {
+            let percent = if total > 0 {
+                used as f64 / total as f64 * 100.0
+            } else {
+                0.0
+            };
+            Reading {
+                value: format!("{:.*}", format.precision, percent),
+                unit: Unit::Percent,
+            }
+        }
+        SizeDisplay::Used => Reading {
+            // The total is a fixed capacity, decimals only waste space there.
+            value: format!(
+                "{}/{}",
+                human_size(used, format.precision, format.prefix),
+                human_size(total, 0, format.prefix)
+            ),
+            unit: Unit::None,
+        },
+        SizeDisplay::Free => Reading {
+            value: format!(
+                "{} free",
+                human_size(total.saturating_sub(used), format.precision, format.prefix)
+            ),
+            unit: Unit::None,
+        },
+    }
+}
+
+/// Short size like `df -h` (IEC, powers of 1024) or `df -H` (SI, powers of
+/// 1000): decimals only below 10, single letter prefix.
+pub fn human_size(bytes: u64, precision: usize, prefix: SizePrefix) -> String {
+    const PREFIXES: [&str; 7] = ["B", "K", "M", "G", "T", "P", "E"];
+    let base = match prefix {
+        SizePrefix::Iec => 1024.0,
+        SizePrefix::Si => 1000.0,
+    };
+    let mut value = bytes as f64;
+    let mut power = 0;
+    while value >= base && power < PREFIXES.len() - 1 {
+        value /= base;
+        power += 1;
+    }
+    let precision = if power == 0 || value >= 10.0 {
+        0
+    } else {
+        precision
+    };
+    format!("{:.*}{}", precision, value, PREFIXES[power])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn size_format(show: SizeDisplay, prefix: SizePrefix) -> SizeFormat {
+        SizeFormat {
+            show,
+            precision: 1,
+            prefix,
+        }
+    }
+
+    #[test]
+    fn binary_prefixes_step_at_1024() {
+        let size = |bytes| human_size(bytes, 1, SizePrefix::Iec);
+        assert_eq!(size(0), "0B");
+        assert_eq!(size(1023), "1023B");
+        assert_eq!(size(1024), "1.0K");
+        assert_eq!(size(1536), "1.5K");
+        assert_eq!(size(10 * 1024), "10K");
+        assert_eq!(size(1024 * 1024 - 1), "1024K");
+        assert_eq!(size(1024 * 1024), "1.0M");
+        assert_eq!(size(4 * 1024 * 1024 * 1024), "4.0G");
+        assert_eq!(size(u64::MAX), "16E");
+    }
+
+    #[test]
+    fn si_prefixes_step_at_1000() {
+        let size = |bytes| human_size(bytes, 1, SizePrefix::Si);
+        assert_eq!(size(999), "999B");
+        assert_eq!(size(1000), "1.0K");
+        assert_eq!(size(1024), "1.0K");
+        assert_eq!(size(999_000), "999K");
+        assert_eq!(size(1_000_000), "1.0M");
+        assert_eq!(size(32_000_000_000), "32G");
+    }
+
+    #[test]
+    fn precision_applies_below_10_only() {
+        assert_eq!(human_size(1_500_000, 2, SizePrefix::Si), "1.50M");
+        assert_eq!(human_size(1_500_000, 0, SizePrefix::Si), "2M");
+        assert_eq!(human_size(15_000_000, 2, SizePrefix::Si), "15M");
+    }
+
+    #[test]
+    fn sizes_as_percent_used_or_free() {
+        let gib = 1024 * 1024 * 1024;
+        let percent = size_format(SizeDisplay::Percent, SizePrefix::Iec);
+        assert_eq!(format_size(gib, 4 * gib, &percent).to_string(), "25.0%");
+        assert_eq!(format_size(0, 0, &percent).to_string(), "0.0%");
+
+        let used = size_format(SizeDisplay::Used, SizePrefix::Iec);
+        let reading = format_size(gib + gib / 5, 4 * gib, &used);
+        assert_eq!(reading.value, "1.2G/4G");
+        assert_eq!(reading.unit, Unit::None);
+
+        let free = size_format(SizeDisplay::Free, SizePrefix::Si);
+        assert_eq!(format_size(500, 2500, &free).to_string(), "2.0K free");
+        // Used above total, e.g. between two reads.
+        assert_eq!(format_size(3000, 2500, &free).to_string(), "0B free");
+    }
+
+    #[test]
+    fn temperatures_in_celsius_or_fahrenheit() {
+        let celsius = TempFormat {
+            unit: TempUnit::Celsius,
+            precision: 1,
+        };
+        assert_eq!(format_temp(48.25, &celsius).to_string(), "48.2°C");
+        let fahrenheit = TempFormat {
+            unit: TempUnit::Fahrenheit,
+            precision: 0,
+        };
+        assert_eq!(format_temp(0.0, &fahrenheit).to_string(), "32°F");
+        assert_eq!(format_temp(100.0, &fahrenheit).to_string(), "212°F");
+        assert_eq!(format_temp(-40.0, &fahrenheit).to_string(), "-40°F");
+        let percent = format_percent(12.345, &PercentFormat { precision: 0 });
+        assert_eq!(percent.to_string(), "12%");
+    }
+}
@@ -0,0 +1,422 @@
+use crate::config::WebConfig;
+use crate::control::{self, ControlCommand, ControlResponse};
+use crate::fan_controller::FanMode;
+use crate::framebuffer::Framebuffer;
+use anyhow::{anyhow, Result};
+use log::{debug, info, warn};
+use serde::Serialize;
+use std::fmt::Write as _;
+use std::io::{Read, Write};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::Sender;
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use std::time::Duration;
+use tiny_http::{Header, Method, Request, Response, Server};
+
+const INDEX_HTML: &str = include_str!("dashboard.html");
+
+/// Idle event streams get a comment this often, so proxies and browsers
+/// don't give up on them.
+const KEEP_ALIVE_INTERVAL: Duration = Duration::from_secs(15);
+
+/// Largest control request body that is read.
+const MAX_BODY: u64 = 16 * 1024;
+
+/// Most requests handled at once. Each has its own thread, more get a 503.
+const MAX_CLIENTS: usize = 16;
+
+/// Most event streams open at once. They hold their thread until the
+/// browser goes away, so they get a lower limit than other requests.
+const MAX_EVENT_STREAMS: usize = 4;
+
+/// Stats and display contents as sent to the browser.
+#[derive(Debug, Clone, Serialize)]
+pub struct Snapshot {
+    pub hostname: String,
+    pub ip_address: Option<String>,
+    pub cpu: String,
+    pub temp: Option<String>,
+    pub ram: String,
+    pub disk: String,
+    pub fan_running: bool,
+    pub fan_mode: FanMode,
+    pub display_on: bool,
+    pub alert_active: bool,
+    /// Watched systemd units in the failed state, `None` without a watch
+    /// list or when their states can't be read.
+    pub failed_units: Option<usize>,
+    pub message: Option<String>,
+    pub frame: Frame,
+}
+
+/// The image on the panel, rows packed like `Framebuffer::packed_rows`
+/// and hex encoded.
+#[derive(Debug, Clone, Serialize)]
+pub struct Frame {
+    width: u32,
+    height: u32,
+    data: String,
+}
+
+impl Frame {
+    pub fn new(framebuffer: &Framebuffer) -> Self {
+        let mut data = String::new();
+        for byte in framebuffer.packed_rows() {
+            let _ = write!(data, "{:02x}", byte);
+        }
+        Frame {
+            width: framebuffer.width(),
+            height: framebuffer.height(),
+            data,
+        }
+    }
+}
+
+/// Latest snapshot from the main loop, shared with the server threads.
+#[derive(Default)]
+pub struct Dashboard {
+    latest: Mutex<(u64, Option<Snapshot>)>,
+    updated: Condvar,
+}
+
+impl Dashboard {
+    pub fn publish(&self, snapshot: Snapshot) {
+        let mut latest = self.latest.lock().unwrap();
+        *latest = (latest.0 + 1, Some(snapshot));
+        self.updated.notify_all();
+    }
+
+    fn latest(&self) -> Option<Snapshot> {
+        self.latest.lock().unwrap().1.clone()
+    }
+
+    /// Waits for a snapshot newer than `seen`, or `None` after `timeout`.
+    fn wait_newer(&self, seen: u64, timeout: Duration) -> Option<(u64, Snapshot)> {
+        let latest = self.latest.lock().unwrap();
+        let (latest, _) = self
+            .updated
+            .wait_timeout_while(latest, timeout, |(seq, _)| *seq == seen)
+            .unwrap();
+        match &*latest {
+            (seq, Some(snapshot)) if *seq != seen => Some((*seq, snapshot.clone())),
+            _ => None,
+        }
+    }
+}
+
+/// Caps how many of something are in use at once.
+struct Limit {
+    in_use: AtomicUsize,
+    max: usize,
+}
+
+impl Limit {
+    fn new(max: usize) -> Arc<Self> {
+        Arc::new(Limit {
+            in_use: AtomicUsize::new(0),
+            max,
+        })
+    }
+
+    /// Takes one if any is left, it's given back when the permit is dropped.
+    fn acquire(self: &Arc<Self>) -> Option<Permit> {
+        self.in_use
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |in_use| {
+                (in_use < self.max).then_some(in_use + 1)
+            })
+            .ok()?;
+        Some(Permit(Arc::clone(self)))
+    }
+}
+
+struct Permit(Arc<Limit>);
+
+impl Drop for Permit {
+    fn drop(&mut self) {
+        self.0.in_use.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+/// Everything the request threads share.
+struct Shared {
+    dashboard: Arc<Dashboard>,
+    token: Option<String>,
+    sender: Sender<ControlCommand>,
+    clients: Arc<Limit>,
+    event_streams: Arc<Limit>,
+}
+
+/// Serves the dashboard page, the current snapshot as JSON and a stream of
+/// snapshots as server-sent events. Controls are forwarded to the main
+/// loop like control socket commands.
+pub struct WebServer {
+    server: Arc<Server>,
+}
+
+impl WebServer {
+    pub fn start(
+        config: &WebConfig,
+        dashboard: Arc<Dashboard>,
+        sender: Sender<ControlCommand>,
+    ) -> Result<Self> {
+        let server = Server::http(&config.listen)
+            .map_err(|e| anyhow!("failed to listen on {}: {}", config.listen, e))?;
+        let server = Arc::new(server);
+        info!(
+            "Dashboard listening on http://{}/ (controls {})",
+            config.listen,
+            if config.token.is_some() {
+                "enabled"
+            } else {
+                "disabled"
+            }
+        );
+
+        let shared = Arc::new(Shared {
+            dashboard,
+            token: config.token.clone(),
+            sender,
+            clients: Limit::new(MAX_CLIENTS),
+            event_streams: Limit::new(MAX_EVENT_STREAMS),
+        });
+        let incoming = Arc::clone(&server);
+        thread::Builder::new()
+            .name("web".to_string())
+            .spawn(move || {
+                for request in incoming.incoming_requests() {
+                    let Some(permit) = shared.clients.acquire() else {
+                        debug!("Too many dashboard clients, turning one away");
+                        let _ = respond(request, 503, "text/plain", "too many clients");
+                        continue;
+                    };
+                    if let Err(e) = spawn_client(request, Arc::clone(&shared), permit) {
+                        warn!("Failed to start dashboard client thread: {}", e);
+                    }
+                }
+            })?;
+
+        Ok(WebServer { server })
+    }
+}
+
+impl Drop for WebServer {
+    fn drop(&mut self) {
+        self.server.unblock();
+    }
+}
+
+/// Event streams stay open, so every request gets its own thread. It
+/// holds `permit` until it's done.
+fn spawn_client(request: Request, shared: Arc<Shared>, permit: Permit) -> std::io::Result<()> {
+    thread::Builder::new()
+        .name("web-client".to_string())
+        .spawn(move || {
+            if let Err(e) = handle_request(request, &shared) {
+                debug!("Dashboard client error: {:#}", e);
+            }
+            drop(permit);
+        })?;
+    Ok(())
+}
+
+fn handle_request(request: Request, shared: &Shared) -> Result<()> {
+    let token = shared.token.as_deref();
+    let path = request
+        .url()
+        .split('?')
+        .next()
+        .unwrap_or_default()
+        .to_string();
+    debug!("Dashboard request: {} {}", request.method(), path);
+    match (request.method(), path.as_str()) {
+        (Method::Get, "/") => respond(request, 200, "text/html; charset=utf-8", INDEX_HTML),
+        (Method::Get, "/api/info") => {
+            let info = serde_json::json!({
+                "version": env!("CARGO_PKG_VERSION"),
+                "controls": token.is_some(),
+            });
+            respond_json(request, 200, &info)
+        }
+        (Method::Get, "/api/status") => match shared.dashboard.latest() {
+            Some(snapshot) => respond_json(request, 200, &snapshot),
+            None => respond(request, 503, "text/plain", "starting"),
+        },
+        (Method::Get, "/api/events") => match shared.event_streams.acquire() {
+            Some(_permit) => stream_events(request, &shared.dashboard),
+            None => respond(request, 503, "text/plain", "too many event streams"),
+        },
+        (Method::Post, "/api/control") => handle_control(request, token, &shared.sender),
+        _ => respond(request, 404, "text/plain", "not found"),
+    }
+}
+
+/// Sends every new snapshot as an event until the browser disconnects.
+fn stream_events(request: Request, dashboard: &Dashboard) -> Result<()> {
+    let mut writer = request.into_writer();
+    writer.write_all(
+        b"HTTP/1.1 200 OK\r\n\
+          Content-Type: text/event-stream\r\n\
+          Cache-Control: no-cache\r\n\
+          Connection: close\r\n\r\n",
+    )?;
+    writer.flush()?;
+
+    let mut seen = 0;
+    loop {
+        match dashboard.wait_newer(seen, KEEP_ALIVE_INTERVAL) {
+            Some((seq, snapshot)) => {
+                seen = seq;
+                let json = serde_json::to_string(&snapshot)?;
+                write!(writer, "data: {}\n\n", json)?;
+            }
+            None => writer.write_all(b": keep-alive\n\n")?,
+        }
+        writer.flush()?;
+    }
+}
+
+fn handle_control(
+    mut request: Request,
+    token: Option<&str>,
+    sender: &Sender<ControlCommand>,
+) -> Result<()> {
+    let Some(token) = token else {
+        let response = ControlResponse {
+            ok: false,
+            error: Some("controls are disabled, set a token in [web]".to_string()),
+        };
+        return respond_json(request, 403, &response);
+    };
+
+    let authorized = request
+        .headers()
+        .iter()
+        .find(|header| header.field.equiv("Authorization"))
+        .and_then(|header| header.value.as_str().strip_prefix("Bearer "))
+        .is_some_and(|given| tokens_match(given, token));
+    if !authorized {
+        let response = ControlResponse {
+            ok: false,
+            error: Some("invalid token".to_string()),
+        };
+        return respond_json(request, 401, &response);
+    }
+
+    let mut body = String::new();
+    request
+        .as_reader()
+        .take(MAX_BODY)
+        .read_to_string(&mut body)?;
+    let response = control::dispatch(&body, sender);
+    let status = if response.ok { 200 } else { 400 };
+    respond_json(request, status, &response)
+}
+
+/// Compares in constant time, so the token can't be guessed byte by byte.
+fn tokens_match(given: &str, token: &str) -> bool {
+    given.len() == token.len()
+        && given
+            .bytes()
+            .zip(token.bytes())
+            .fold(0, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+fn respond(request: Request, status: u16, content_type: &str, body: &str) -> Result<()> {
+    let header = Header::from_bytes("Content-Type", content_type)
+        .map_err(|_| anyhow!("invalid content type {}", content_type))?;
+    let response = Response::from_string(body)
+        .with_status_code(status)
+        .with_header(header);
+    request.respond(response)?;
+    Ok(())
+}
+
+fn respond_json<T: Serialize>(request: Request, status: u16, value: &T) -> Result<()> {
+    let body = serde_json::to_string(value)?;
+    respond(request, status, "application/json", &body)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::BufRead;
+    use std::io::BufReader;
+    use std::net::{TcpListener, TcpStream};
+    use std::sync::mpsc;
+
+    fn start(token: Option<&str>) -> (WebServer, String, mpsc::Receiver<ControlCommand>) {
+        let port = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let config = WebConfig {
+            enabled: true,
+            listen: format!("127.0.0.1:{}", port),
+            token: token.map(str::to_string),
+        };
+        let (sender, events) = mpsc::channel();
+        let server = WebServer::start(&config, Arc::default(), sender).unwrap();
+        (server, config.listen, events)
+    }
+
+    /// Opens an event stream and returns it with the status line.
+    fn open_events(listen: &str) -> (BufReader<TcpStream>, String) {
+        let mut stream = TcpStream::connect(listen).unwrap();
+        stream
+            .write_all(b"GET /api/events HTTP/1.1\r\nHost: pi\r\n\r\n")
+            .unwrap();
+        let mut reader = BufReader::new(stream);
+        let mut status = String::new();
+        reader.read_line(&mut status).unwrap();
+        (reader, status)
+    }
+
+    #[test]
+    fn limit_gives_back_dropped_permits() {
+        let limit = Limit::new(2);
+        let first = limit.acquire().unwrap();
+        let _second = limit.acquire().unwrap();
+        assert!(limit.acquire().is_none());
+        drop(first);
+        assert!(limit.acquire().is_some());
+    }
+
+    #[test]
+    fn event_streams_over_the_limit_are_turned_away() {
+        let (_server, listen, _events) = start(None);
+        let open: Vec<_> = (0..MAX_EVENT_STREAMS)
+            .map(|_| open_events(&listen))
+            .collect();
+        assert!(open.iter().all(|(_, status)| status.contains(" 200 ")));
+        let (_, status) = open_events(&listen);
+        assert!(status.contains(" 503 "), "{}", status);
+        let status = ureq::get(&format!("http://{}/api/info", listen))
+            .call()
+            .unwrap()
+            .status();
+        assert_eq!(status, 200);
+    }
+
+    #[test]
+    fn control_rejects_out_of_range_durations() {
+        let (_server, listen, events) = start(Some("secret"));
+        let body = format!(
+            r#"{{"command":"notify","text":"x","duration":{}}}"#,
+            u64::MAX
+        );
+        let result = ureq::post(&format!("http://{}/api/control", listen))
+            .set("Authorization", "Bearer secret")
+            .send_string(&body);
+        assert!(matches!(result, Err(ureq::Error::Status(400, _))));
+        assert!(events.try_recv().is_err());
+
+        let result = ureq::post(&format!("http://{}/api/control", listen))
+            .set("Authorization", "Bearer secret")
+            .send_string(r#"{"command":"notify","text":"x","duration":30}"#);
+        assert_eq!(result.unwrap().status(), 200);
+        assert!(events.try_recv().is_ok());
+    }
+}